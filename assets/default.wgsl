@@ -0,0 +1,22 @@
+// Shown until `shader.wgsl` is created. Save a shader there to replace it.
+
+@group(0) @binding(0)
+var<uniform> time: f32;
+
+@group(0) @binding(1)
+var<uniform> resolution: vec2f;
+
+@fragment
+fn main(@builtin(position) pos: vec4f) -> @location(0) vec4f {
+    let uv = (2.0 * pos.xy - resolution) / resolution.y;
+
+    var v = sin(uv.x * 3.0 + time);
+    v += sin((uv.y * 3.0 + time) * 0.5);
+    v += sin((uv.x * 2.0 + uv.y * 2.0 + time) * 0.7);
+    let c = uv + 0.5 * vec2f(sin(time / 5.0), cos(time / 3.0));
+    v += sin(sqrt(100.0 * dot(c, c) + 1.0) + time);
+    v *= 0.5;
+
+    let color = 0.5 + 0.5 * cos(3.14159 * v + vec3f(0.0, 2.1, 4.2));
+    return vec4f(color, 1.0);
+}
@@ -0,0 +1,94 @@
+//! Draws a shader into a window this app creates and owns the event loop
+//! of, forwarding its events to a [`Renderer`].
+//!
+//! ```sh
+//! cargo run --example embed -- shader.wgsl
+//! ```
+
+use std::sync::Arc;
+
+use clap::Parser;
+use shadertoy::{Args, Event, Renderer};
+use winit::{
+    application::ApplicationHandler,
+    event::{DeviceEvent, DeviceId, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::{Window, WindowId},
+};
+
+struct Host {
+    args: Args,
+    renderer: Option<Renderer>,
+}
+
+impl ApplicationHandler for Host {
+    fn resumed(&mut self, el: &ActiveEventLoop) {
+        let attributes = Window::default_attributes().with_title("Embedded shader");
+        let window = Arc::new(el.create_window(attributes).expect("a window"));
+        match pollster::block_on(Renderer::new(window, &self.args)) {
+            Ok(renderer) => {
+                println!("Rendering on {}", renderer.gpu_info());
+                let events = renderer.subscribe();
+                std::thread::spawn(move || {
+                    for event in events {
+                        if let Event::Reloaded(reloaded) = event {
+                            println!("Reloaded `{}`: {:?}", reloaded.pass, reloaded.error);
+                        }
+                    }
+                });
+                self.renderer = Some(renderer);
+            }
+            Err(err) => {
+                eprintln!("Failed to build the renderer: {err}");
+                el.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, el: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => el.exit(),
+            WindowEvent::RedrawRequested => {
+                if let Err(err) = renderer.redraw() {
+                    eprintln!("Render error: {err}");
+                }
+            }
+            event => renderer.window_event(&event),
+        }
+    }
+
+    fn device_event(&mut self, _: &ActiveEventLoop, _: DeviceId, event: DeviceEvent) {
+        if let Some(renderer) = &mut self.renderer {
+            renderer.device_event(&event);
+        }
+    }
+
+    fn about_to_wait(&mut self, el: &ActiveEventLoop) {
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+        if renderer.is_lost()
+            && let Err(err) = pollster::block_on(renderer.recover())
+        {
+            eprintln!("Failed to recover from the device loss: {err}");
+            el.exit();
+            return;
+        }
+        match renderer.about_to_wait() {
+            Some(wake_at) => el.set_control_flow(ControlFlow::WaitUntil(wake_at)),
+            None => el.set_control_flow(ControlFlow::Wait),
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut host = Host {
+        args: Args::parse(),
+        renderer: None,
+    };
+    EventLoop::new()?.run_app(&mut host)?;
+    Ok(())
+}
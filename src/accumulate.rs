@@ -0,0 +1,350 @@
+use wgpu::{
+    Adapter, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+    BufferBindingType, BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites,
+    CommandEncoder, Device, Extent3d, FragmentState, MultisampleState, Operations,
+    PipelineCompilationOptions, PipelineLayoutDescriptor, PrimitiveState, Queue,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, Texture, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDimension, VertexState,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::blit::Blit;
+
+/// Format of the running mean, precise enough to average thousands of frames.
+const MEAN_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+/// Used instead of [`MEAN_FORMAT`] where it can't be rendered to.
+const FALLBACK_MEAN_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Averages the scene over many frames, for Monte Carlo shaders that add
+/// one noisy sample per frame, see `--accumulate`.
+///
+/// The mean is kept in a pair of float textures like the feedback buffers
+/// of the render graph, and copied to a texture in the scene's format for
+/// the blit.
+#[derive(Debug)]
+pub struct Accumulator {
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    /// Weight of the new frame in the mean.
+    weight: Buffer,
+    format: TextureFormat,
+    mean_format: TextureFormat,
+    means: [Texture; 2],
+    display: Texture,
+    /// Bind groups reading the scene and each mean.
+    bind_groups: [BindGroup; 2],
+    /// Blit bind group of the averaged scene.
+    display_bind_group: BindGroup,
+    /// Which mean is written next.
+    parity: usize,
+    samples: u32,
+    max_samples: u32,
+    /// Inputs the last frames were rendered with, see [`Self::set_inputs`].
+    inputs: Vec<f32>,
+}
+
+impl Accumulator {
+    #[tracing::instrument(skip(adapter, device, scene, blit))]
+    pub fn new(
+        adapter: &Adapter,
+        device: &Device,
+        format: TextureFormat,
+        scene: &TextureView,
+        size: PhysicalSize<u32>,
+        blit: &Blit,
+        max_samples: u32,
+    ) -> Self {
+        let mean_format = if adapter
+            .get_texture_format_features(MEAN_FORMAT)
+            .allowed_usages
+            .contains(TextureUsages::RENDER_ATTACHMENT)
+        {
+            MEAN_FORMAT
+        } else {
+            tracing::warn!(
+                "The adapter can't render to {MEAN_FORMAT:?}, accumulating in \
+                 {FALLBACK_MEAN_FORMAT:?}"
+            );
+            FALLBACK_MEAN_FORMAT
+        };
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("accumulation shader"),
+            source: ShaderSource::Wgsl(ACCUMULATE_SHADER.into()),
+        });
+
+        let texture = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: false },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("accumulation bind group layout"),
+            entries: &[
+                texture(0),
+                texture(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("accumulation pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let target = |format| {
+            Some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::default(),
+            })
+        };
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("accumulation pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[target(mean_format), target(format)],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let weight = device.create_buffer(&BufferDescriptor {
+            label: Some("accumulation weight buffer"),
+            size: size_of::<[f32; 4]>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let means = [0, 1].map(|_| create_texture(device, "accumulated mean", mean_format, size));
+        let display = create_texture(device, "accumulated scene", format, size);
+        let bind_groups = create_bind_groups(device, &bind_group_layout, scene, &means, &weight);
+        let display_bind_group = blit.bind_group(device, &display.create_view(&Default::default()));
+        Self {
+            pipeline,
+            bind_group_layout,
+            weight,
+            format,
+            mean_format,
+            means,
+            display,
+            bind_groups,
+            display_bind_group,
+            parity: 0,
+            samples: 0,
+            max_samples,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Recreates the textures for a new `scene` of `size`, starting over.
+    #[tracing::instrument(skip(self, device, scene, blit))]
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        scene: &TextureView,
+        size: PhysicalSize<u32>,
+        blit: &Blit,
+    ) {
+        self.means =
+            [0, 1].map(|_| create_texture(device, "accumulated mean", self.mean_format, size));
+        self.display = create_texture(device, "accumulated scene", self.format, size);
+        self.bind_groups = create_bind_groups(
+            device,
+            &self.bind_group_layout,
+            scene,
+            &self.means,
+            &self.weight,
+        );
+        self.display_bind_group =
+            blit.bind_group(device, &self.display.create_view(&Default::default()));
+        self.reset();
+    }
+
+    /// Starts a new mean with the next frame.
+    pub fn reset(&mut self) {
+        if self.samples > 0 {
+            tracing::debug!("Restarting accumulation after {} samples", self.samples);
+        }
+        self.samples = 0;
+    }
+
+    /// Starts over if `inputs`, the uniforms the scene depends on, differ
+    /// from those of the previous frames.
+    pub fn set_inputs(&mut self, inputs: Vec<f32>) {
+        if inputs != self.inputs {
+            self.inputs = inputs;
+            self.reset();
+        }
+    }
+
+    /// Whether the mean has all its samples, so new frames aren't needed.
+    pub fn is_done(&self) -> bool {
+        self.samples >= self.max_samples
+    }
+
+    /// Frames in the mean and the most it averages.
+    pub fn samples(&self) -> (u32, u32) {
+        (self.samples, self.max_samples)
+    }
+
+    /// The mean in the scene's format, as of the last [`Self::accumulate`].
+    pub fn display(&self) -> &Texture {
+        &self.display
+    }
+
+    /// Blit bind group of the averaged scene.
+    pub fn display_bind_group(&self) -> &BindGroup {
+        &self.display_bind_group
+    }
+
+    /// Adds the frame just rendered into the scene to the mean.
+    #[tracing::instrument(skip_all)]
+    pub fn accumulate(&mut self, queue: &Queue, encoder: &mut CommandEncoder) {
+        if self.is_done() {
+            return;
+        }
+        let weight = 1.0 / (self.samples + 1) as f32;
+        queue.write_buffer(
+            &self.weight,
+            0,
+            bytemuck::bytes_of(&[weight, 0.0, 0.0, 0.0]),
+        );
+
+        let mean = self.means[self.parity].create_view(&Default::default());
+        let display = self.display.create_view(&Default::default());
+        let attachment = |view| {
+            Some(RenderPassColorAttachment {
+                view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: Operations::default(),
+            })
+        };
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("accumulation pass"),
+            color_attachments: &[attachment(&mean), attachment(&display)],
+            ..Default::default()
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_groups[self.parity], &[]);
+        render_pass.draw(0..3, 0..1);
+
+        self.parity ^= 1;
+        self.samples += 1;
+    }
+}
+
+/// Bind groups writing each mean while reading the other one.
+fn create_bind_groups(
+    device: &Device,
+    layout: &BindGroupLayout,
+    scene: &TextureView,
+    means: &[Texture; 2],
+    weight: &Buffer,
+) -> [BindGroup; 2] {
+    [0, 1].map(|parity| {
+        let previous = means[parity ^ 1].create_view(&Default::default());
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("accumulation bind group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(scene),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&previous),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: weight.as_entire_binding(),
+                },
+            ],
+        })
+    })
+}
+
+fn create_texture(
+    device: &Device,
+    label: &str,
+    format: TextureFormat,
+    size: PhysicalSize<u32>,
+) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::RENDER_ATTACHMENT
+            | TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+const ACCUMULATE_SHADER: &str = "
+@group(0) @binding(0)
+var scene: texture_2d<f32>;
+
+@group(0) @binding(1)
+var previous: texture_2d<f32>;
+
+@group(0) @binding(2)
+var<uniform> weight: f32;
+
+struct Output {
+    @location(0) mean: vec4<f32>,
+    @location(1) display: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> Output {
+    let texel = vec2<i32>(position.xy);
+    let mean = mix(textureLoad(previous, texel, 0), textureLoad(scene, texel, 0), weight);
+    return Output(mean, mean);
+}
+";
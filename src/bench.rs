@@ -0,0 +1,181 @@
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use wgpu::PresentMode;
+
+/// Present modes `--bench-present-modes` compares, in the order they run.
+const MODES: [PresentMode; 3] = [
+    PresentMode::Fifo,
+    PresentMode::Mailbox,
+    PresentMode::Immediate,
+];
+/// Frames rendered after switching modes before timing starts, while the
+/// swapchain settles.
+const WARMUP_FRAMES: u32 = 30;
+
+/// Renders a fixed number of frames in each present mode the surface
+/// supports and compares their frame times.
+#[derive(Debug)]
+pub struct PresentModeBench {
+    /// Modes to measure, with the frame times of those already measured.
+    results: Vec<(PresentMode, Vec<Duration>)>,
+    unsupported: Vec<PresentMode>,
+    frames: usize,
+    /// Index of the mode being measured.
+    current: usize,
+    warmup: u32,
+    last_frame: Option<Instant>,
+}
+
+impl PresentModeBench {
+    /// Measures `frames` frames in each of the `supported` modes.
+    pub fn new(supported: &[PresentMode], frames: u32) -> Self {
+        let (modes, unsupported): (Vec<_>, Vec<_>) =
+            MODES.into_iter().partition(|mode| supported.contains(mode));
+        for mode in &unsupported {
+            tracing::warn!("The surface doesn't support {mode:?}, skipping it");
+        }
+        Self {
+            results: modes.into_iter().map(|mode| (mode, Vec::new())).collect(),
+            unsupported,
+            frames: frames as usize,
+            current: 0,
+            warmup: WARMUP_FRAMES,
+            last_frame: None,
+        }
+    }
+
+    /// Mode the surface has to be configured with.
+    pub fn mode(&self) -> PresentMode {
+        self.results[self.current.min(self.results.len() - 1)].0
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.current == self.results.len()
+    }
+
+    /// Records a frame presented at `now`. Returns the next mode once the
+    /// current one has all its frames.
+    pub fn tick(&mut self, now: Instant) -> Option<PresentMode> {
+        if self.is_done() {
+            return None;
+        }
+        let last_frame = self.last_frame.replace(now);
+        if self.warmup > 0 {
+            self.warmup -= 1;
+            return None;
+        }
+        let times = &mut self.results[self.current].1;
+        if let Some(last_frame) = last_frame {
+            times.push(now - last_frame);
+        }
+        if times.len() < self.frames {
+            return None;
+        }
+        tracing::info!("Measured {:?}", self.results[self.current].0);
+        self.current += 1;
+        self.warmup = WARMUP_FRAMES;
+        self.last_frame = None;
+        (!self.is_done()).then(|| self.mode())
+    }
+
+    /// Table of the frame time statistics of each mode.
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "{:<10} {:>9} {:>9} {:>9} {:>9} {:>8}\n",
+            "mode", "mean ms", "p50 ms", "p99 ms", "max ms", "fps"
+        );
+        for (mode, times) in &self.results {
+            let [mean, p50, p99, max] = statistics(times);
+            writeln!(
+                report,
+                "{:<10} {mean:>9.2} {p50:>9.2} {p99:>9.2} {max:>9.2} {:>8.1}",
+                format!("{mode:?}"),
+                1000.0 / mean
+            )
+            .expect("writing to a string can't fail");
+        }
+        for mode in &self.unsupported {
+            writeln!(report, "{:<10} unsupported", format!("{mode:?}"))
+                .expect("writing to a string can't fail");
+        }
+        report
+    }
+}
+
+/// Renders a fixed number of frames and times them, see `--max-frames`.
+#[derive(Debug)]
+pub struct FrameLimit {
+    frames: u32,
+    rendered: u32,
+    first_frame: Option<Instant>,
+    last_frame: Option<Instant>,
+    /// Time between each frame and the one before it.
+    times: Vec<Duration>,
+}
+
+impl FrameLimit {
+    pub fn new(frames: u32) -> Self {
+        Self {
+            frames,
+            rendered: 0,
+            first_frame: None,
+            last_frame: None,
+            times: Vec::with_capacity(frames as usize),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.rendered >= self.frames
+    }
+
+    /// Records a frame presented at `now`.
+    pub fn tick(&mut self, now: Instant) {
+        if self.is_done() {
+            return;
+        }
+        self.rendered += 1;
+        self.first_frame.get_or_insert(now);
+        if let Some(last_frame) = self.last_frame.replace(now) {
+            self.times.push(now - last_frame);
+        }
+    }
+
+    /// Summary of the frame times. The first frame has nothing to be timed
+    /// against, so `N` frames give `N - 1` times.
+    pub fn report(&self) -> String {
+        let mut report = format!("Rendered {} frames", self.rendered);
+        if let (Some(first_frame), Some(last_frame)) = (self.first_frame, self.last_frame) {
+            write!(report, " in {:.3?}", last_frame - first_frame)
+                .expect("writing to a string can't fail");
+        }
+        report.push('\n');
+        if !self.times.is_empty() {
+            let [mean, p50, p99, max] = statistics(&self.times);
+            writeln!(
+                report,
+                "{:>9} {:>9} {:>9} {:>9} {:>8}\n{mean:>9.2} {p50:>9.2} {p99:>9.2} {max:>9.2} {:>8.1}",
+                "mean ms",
+                "p50 ms",
+                "p99 ms",
+                "max ms",
+                "fps",
+                1000.0 / mean
+            )
+            .expect("writing to a string can't fail");
+        }
+        report
+    }
+}
+
+/// Mean, median, 99th percentile and maximum of `times` in milliseconds.
+fn statistics(times: &[Duration]) -> [f64; 4] {
+    let mut times = times.to_vec();
+    times.sort();
+    let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+    let percentile = |p: usize| ms(times[(times.len() - 1) * p / 100]);
+    let mean = ms(times.iter().sum::<Duration>()) / times.len() as f64;
+    [mean, percentile(50), percentile(99), percentile(100)]
+}
@@ -0,0 +1,409 @@
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
+    BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites, Device, FilterMode,
+    FragmentState, MultisampleState, PipelineCompilationOptions, PipelineLayoutDescriptor,
+    PrimitiveState, Queue, RenderPass, RenderPipeline, RenderPipelineDescriptor, Sampler,
+    SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    TextureFormat, TextureSampleType, TextureView, TextureViewDimension, VertexState,
+};
+use winit::dpi::PhysicalSize;
+
+/// Region of the surface the scene is drawn into, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// Largest centered region of a `width`x`height` surface with the given
+    /// aspect ratio, or the whole surface if `aspect` is `None`.
+    pub fn letterbox(width: u32, height: u32, aspect: Option<f32>) -> Self {
+        let Some(aspect) = aspect else {
+            return Self {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            };
+        };
+
+        let (fit_width, fit_height) = if width as f32 / height as f32 > aspect {
+            ((height as f32 * aspect).round() as u32, height)
+        } else {
+            (width, (width as f32 / aspect).round() as u32)
+        };
+        let (fit_width, fit_height) = (fit_width.clamp(1, width), fit_height.clamp(1, height));
+        Self {
+            x: (width - fit_width) / 2,
+            y: (height - fit_height) / 2,
+            width: fit_width,
+            height: fit_height,
+        }
+    }
+
+    pub fn size(self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.width, self.height)
+    }
+}
+
+/// Display correction applied while blitting, see `--adjust`. The default
+/// leaves colors unchanged.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Adjustment {
+    /// Added to every channel.
+    pub brightness: f32,
+    /// Scales the distance of every channel from mid-gray.
+    pub contrast: f32,
+    /// Channels are raised to `1 / gamma`, so values above 1 brighten the
+    /// midtones.
+    pub gamma: f32,
+}
+
+impl Adjustment {
+    pub fn new(brightness: f32, contrast: f32, gamma: f32) -> Self {
+        Self {
+            brightness,
+            contrast,
+            gamma,
+        }
+    }
+}
+
+impl Default for Adjustment {
+    fn default() -> Self {
+        Self::new(0.0, 1.0, 1.0)
+    }
+}
+
+/// How the viewport is shared between the two textures of a comparison,
+/// see `--compare`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitScreen {
+    /// The first texture on the left, the second on the right.
+    Vertical,
+    /// The first texture on top, the second at the bottom.
+    Horizontal,
+}
+
+/// Contents of the blit's uniform buffer.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    adjustment: Adjustment,
+    /// `0` for a single texture, `1` and `2` for a [`SplitScreen`].
+    split: u32,
+    /// `1` to flip the image upside down, see [`Blit::set_flip_y`].
+    flip_y: u32,
+}
+
+/// Draws an offscreen texture into a viewport of the current render target.
+#[derive(Debug)]
+pub struct Blit {
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    params: Params,
+    params_buffer: Buffer,
+}
+
+impl Blit {
+    #[tracing::instrument(skip(device))]
+    pub fn new(device: &Device, format: TextureFormat, filter: FilterMode) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("blit shader"),
+            source: ShaderSource::Wgsl(BLIT_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("blit bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("blit sampler"),
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("blit pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("blit pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::default(),
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let params = Params::default();
+        let params_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("blit uniform buffer"),
+            size: size_of::<Params>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: true,
+        });
+        params_buffer
+            .slice(..)
+            .get_mapped_range_mut()
+            .copy_from_slice(bytemuck::bytes_of(&params));
+        params_buffer.unmap();
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            params,
+            params_buffer,
+        }
+    }
+
+    /// Sets the display correction of the following draws.
+    pub fn set_adjustment(&mut self, queue: &Queue, adjustment: Adjustment) {
+        self.params.adjustment = adjustment;
+        self.write_params(queue);
+    }
+
+    /// Splits the following draws between the two textures of a bind group
+    /// from [`Self::comparison_bind_group`], or draws only the first one.
+    pub fn set_split(&mut self, queue: &Queue, split: Option<SplitScreen>) {
+        self.params.split = match split {
+            None => 0,
+            Some(SplitScreen::Vertical) => 1,
+            Some(SplitScreen::Horizontal) => 2,
+        };
+        self.write_params(queue);
+    }
+
+    /// Flips the following draws upside down, or draws them upright.
+    pub fn set_flip_y(&mut self, queue: &Queue, flip_y: bool) {
+        self.params.flip_y = u32::from(flip_y);
+        self.write_params(queue);
+    }
+
+    fn write_params(&self, queue: &Queue) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    /// Creates the bind group sampling `source`.
+    pub fn bind_group(&self, device: &Device, source: &TextureView) -> BindGroup {
+        self.comparison_bind_group(device, source, source)
+    }
+
+    /// Creates the bind group sampling `first` and `second` side by side,
+    /// see [`Self::set_split`].
+    pub fn comparison_bind_group(
+        &self,
+        device: &Device,
+        first: &TextureView,
+        second: &TextureView,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("blit bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(first),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(second),
+                },
+            ],
+        })
+    }
+
+    /// Draws the texture bound in `bind_group` stretched over `viewport`.
+    ///
+    /// A split screen draws the triangle once per texture, and each
+    /// instance only keeps its side of the split.
+    pub fn draw(
+        &self,
+        render_pass: &mut RenderPass<'_>,
+        bind_group: &BindGroup,
+        viewport: Viewport,
+    ) {
+        render_pass.set_viewport(
+            viewport.x as f32,
+            viewport.y as f32,
+            viewport.width as f32,
+            viewport.height as f32,
+            0.0,
+            1.0,
+        );
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        let instances = if self.params.split == 0 { 1 } else { 2 };
+        render_pass.draw(0..3, 0..instances);
+    }
+}
+
+const BLIT_SHADER: &str = "
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+struct Params {
+    brightness: f32,
+    contrast: f32,
+    gamma: f32,
+    split: u32,
+    flip_y: u32,
+};
+
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+@group(0) @binding(3)
+var second: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) instance: u32,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance: u32,
+) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return VertexOutput(position, uv, instance);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = select(in.uv, vec2<f32>(in.uv.x, 1.0 - in.uv.y), params.flip_y == 1u);
+    // Each instance of a split screen keeps its own side.
+    var side = 0u;
+    if params.split == 1u {
+        side = u32(uv.x >= 0.5);
+    } else if params.split == 2u {
+        side = u32(uv.y >= 0.5);
+    }
+    if side != in.instance {
+        discard;
+    }
+    let color = select(
+        textureSample(second, source_sampler, uv),
+        textureSample(source, source_sampler, uv),
+        in.instance == 0u,
+    );
+    let rgb = (color.rgb - 0.5) * params.contrast + 0.5 + params.brightness;
+    return vec4<f32>(pow(max(rgb, vec3<f32>(0.0)), vec3<f32>(1.0 / params.gamma)), color.a);
+}
+";
+
+#[cfg(test)]
+mod tests {
+    use std::mem::offset_of;
+
+    use super::*;
+    use crate::reflect;
+
+    #[test]
+    fn params_match_wgsl() {
+        let member =
+            |name: &str, offset: usize, size: usize| (name.to_owned(), offset as u32, size as u32);
+        let expected = [
+            member(
+                "brightness",
+                offset_of!(Params, adjustment.brightness),
+                size_of::<f32>(),
+            ),
+            member(
+                "contrast",
+                offset_of!(Params, adjustment.contrast),
+                size_of::<f32>(),
+            ),
+            member(
+                "gamma",
+                offset_of!(Params, adjustment.gamma),
+                size_of::<f32>(),
+            ),
+            member("split", offset_of!(Params, split), size_of::<u32>()),
+            member("flip_y", offset_of!(Params, flip_y), size_of::<u32>()),
+            member("Params", 0, size_of::<Params>()),
+        ];
+        let (mut actual, size) = reflect::struct_layout(BLIT_SHADER, "Params");
+        actual.push(("Params".to_owned(), 0, size));
+        reflect::assert_layout(&expected, &actual);
+    }
+}
@@ -0,0 +1,474 @@
+use std::{
+    fmt,
+    path::Path,
+    sync::mpsc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::{ExtendedColorType, ImageResult};
+
+use wgpu::{
+    BufferDescriptor, BufferUsages, COPY_BYTES_PER_ROW_ALIGNMENT, CommandEncoderDescriptor, Device,
+    Extent3d, MapMode, Origin3d, PollType, Queue, TexelCopyBufferInfo, TexelCopyBufferLayout,
+    TexelCopyTextureInfo, Texture, TextureAspect, TextureFormat, TextureUsages,
+};
+
+/// How the colors of captured frames are encoded, see `--capture-encoding`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Match what the window shows: 8-bit and 10-bit textures are shown
+    /// as stored, while float textures hold linear colors the display
+    /// encodes to sRGB.
+    #[default]
+    Auto,
+    /// Treat the shader's output as linear and encode it to sRGB.
+    Srgb,
+    /// Keep the values the shader wrote, undoing the encoding of sRGB
+    /// textures.
+    Linear,
+}
+
+/// Layout of the texels of a texture that can be read back.
+#[derive(Debug, Clone, Copy)]
+enum Texels {
+    Unorm8 { swizzle: bool },
+    Rgb10a2,
+    Rgba16Float,
+}
+
+impl Texels {
+    /// Layout of the texels of `format`, if it can be read back.
+    fn of(format: TextureFormat) -> Result<Self, String> {
+        match format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+                Ok(Self::Unorm8 { swizzle: false })
+            }
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+                Ok(Self::Unorm8 { swizzle: true })
+            }
+            TextureFormat::Rgb10a2Unorm => Ok(Self::Rgb10a2),
+            TextureFormat::Rgba16Float => Ok(Self::Rgba16Float),
+            format => Err(format!(
+                "unsupported texture format for readback: {format:?}"
+            )),
+        }
+    }
+}
+
+/// A frame read back from the GPU as tightly packed RGBA8 rows.
+#[derive(Debug)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Frame {
+    /// Turns the frame upside down, see `--flip-y`.
+    pub fn flip_rows(&mut self) {
+        let row = self.width as usize * 4;
+        let rows = self.height as usize;
+        for y in 0..rows / 2 {
+            let (top, bottom) = self.rgba.split_at_mut((rows - 1 - y) * row);
+            top[y * row..(y + 1) * row].swap_with_slice(&mut bottom[..row]);
+        }
+    }
+}
+
+/// Copies `texture` into a mappable buffer and reads it back to the CPU as
+/// 8-bit RGBA, encoded as `encoding` asks.
+///
+/// The texture must have been created with [`TextureUsages::COPY_SRC`] and use
+/// an 8-bit RGBA or BGRA, `Rgb10a2Unorm` or `Rgba16Float` format.
+#[tracing::instrument(skip(device, queue, texture))]
+pub fn read_texture(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+    encoding: Encoding,
+) -> Result<Frame, Box<dyn std::error::Error>> {
+    let format = texture.format();
+    let texels = Texels::of(format)?;
+    let data = read_texels(device, queue, texture)?;
+    Ok(Frame {
+        width: texture.width(),
+        height: texture.height(),
+        rgba: to_rgba8(format, texels, encoding, data),
+    })
+}
+
+/// Converts `data`, tightly packed `texels` of a texture in `format`, to
+/// 8-bit RGBA encoded as `encoding` asks, see [`read_texture`].
+fn to_rgba8(format: TextureFormat, texels: Texels, encoding: Encoding, data: Vec<u8>) -> Vec<u8> {
+    let transfer: Option<fn(f32) -> f32> = match (encoding, format.is_srgb(), texels) {
+        (Encoding::Auto, _, Texels::Rgba16Float) => Some(srgb_encode),
+        (Encoding::Auto, _, _) | (Encoding::Srgb, true, _) | (Encoding::Linear, false, _) => None,
+        (Encoding::Srgb, false, _) => Some(srgb_encode),
+        (Encoding::Linear, true, _) => Some(srgb_decode),
+    };
+
+    let mut rgba = match (texels, transfer) {
+        (Texels::Unorm8 { .. }, None) => data,
+        _ => {
+            let bytes_per_pixel = format
+                .block_copy_size(None)
+                .expect("readback formats are uncompressed color formats");
+            let mut rgba = Vec::with_capacity(data.len() / bytes_per_pixel as usize * 4);
+            for texel in data.chunks_exact(bytes_per_pixel as usize) {
+                let [r, g, b, a] = decode_texel(texels, texel);
+                let [r, g, b] = [r, g, b].map(|c| transfer.map_or(c, |f| f(c)));
+                rgba.extend([r, g, b, a].map(quantize));
+            }
+            rgba
+        }
+    };
+
+    if let Texels::Unorm8 { swizzle: true } = texels {
+        for pixel in rgba.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    rgba
+}
+
+/// Copies `texture` into a mappable buffer and reads back its texels as
+/// stored, in tightly packed rows.
+///
+/// The texture must have been created with [`TextureUsages::COPY_SRC`] and
+/// use an uncompressed color format.
+pub fn read_texels(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !texture.usage().contains(TextureUsages::COPY_SRC) {
+        return Err("texture was not created with COPY_SRC usage".into());
+    }
+    let (width, height) = (texture.width(), texture.height());
+    let bytes_per_pixel = texture
+        .format()
+        .block_copy_size(None)
+        .ok_or_else(|| format!("can't read back {:?} textures", texture.format()))?;
+    let unpadded_bytes_per_row = width * bytes_per_pixel;
+    let padded_bytes_per_row =
+        unpadded_bytes_per_row.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+    tracing::trace!(width, height, padded_bytes_per_row, "Reading back texture");
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("readback buffer"),
+        size: u64::from(padded_bytes_per_row) * u64::from(height),
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("readback encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    let (tx, rx) = mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| {
+        let _ = tx.send(result);
+    });
+    device.poll(PollType::wait_indefinitely())?;
+    rx.recv()??;
+
+    let texels = unpad_rows(
+        &slice.get_mapped_range(),
+        padded_bytes_per_row,
+        unpadded_bytes_per_row,
+    );
+    buffer.unmap();
+    Ok(texels)
+}
+
+/// The first `unpadded_bytes_per_row` bytes of each `padded_bytes_per_row`
+/// long row of `data`, dropping the padding copies need.
+fn unpad_rows(data: &[u8], padded_bytes_per_row: u32, unpadded_bytes_per_row: u32) -> Vec<u8> {
+    data.chunks_exact(padded_bytes_per_row as usize)
+        .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+        .copied()
+        .collect()
+}
+
+/// Color channels of `texel` as stored, between 0 and 1 for normalized
+/// formats, in the texture's channel order.
+fn decode_texel(texels: Texels, texel: &[u8]) -> [f32; 4] {
+    match texels {
+        Texels::Unorm8 { .. } => std::array::from_fn(|i| f32::from(texel[i]) / 255.0),
+        Texels::Rgb10a2 => {
+            let bits = u32::from_le_bytes(texel.try_into().expect("texels are 4 bytes"));
+            let channel = |shift: u32, max: u32| ((bits >> shift) & max) as f32 / max as f32;
+            [
+                channel(0, 0x3ff),
+                channel(10, 0x3ff),
+                channel(20, 0x3ff),
+                channel(30, 0x3),
+            ]
+        }
+        Texels::Rgba16Float => std::array::from_fn(|i| {
+            f16_to_f32(u16::from_le_bytes([texel[2 * i], texel[2 * i + 1]]))
+        }),
+    }
+}
+
+/// Widens a half-precision float.
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = i32::from((half >> 10) & 0x1f);
+    let mantissa = f32::from(half & 0x3ff);
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Encodes a linear color channel with the sRGB transfer function.
+fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decodes an sRGB-encoded color channel to linear.
+fn srgb_decode(encoded: f32) -> f32 {
+    if encoded <= 0.040_45 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Rounds a channel between 0 and 1 to 8 bits, clamping values outside it.
+fn quantize(channel: f32) -> u8 {
+    (channel.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// The system clipboard, opened on the first copy and kept open from then
+/// on. On X11 and Wayland the process that copied an image serves it to
+/// whoever pastes it, so closing the clipboard right after copying loses
+/// the image unless a clipboard manager took it over.
+#[derive(Default)]
+pub struct Clipboard {
+    inner: Option<arboard::Clipboard>,
+}
+
+impl fmt::Debug for Clipboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clipboard")
+            .field("open", &self.inner.is_some())
+            .finish()
+    }
+}
+
+impl Clipboard {
+    /// Places `frame` on the system clipboard as an image.
+    #[tracing::instrument(skip_all)]
+    pub fn copy(&mut self, frame: &Frame) -> Result<(), arboard::Error> {
+        let clipboard = match self.inner.take() {
+            Some(clipboard) => clipboard,
+            None => arboard::Clipboard::new()?,
+        };
+        self.inner.insert(clipboard).set_image(arboard::ImageData {
+            width: frame.width as usize,
+            height: frame.height as usize,
+            bytes: frame.rgba.as_slice().into(),
+        })
+    }
+}
+
+/// Writes `frame` to `path` as a PNG.
+#[tracing::instrument(skip(frame))]
+pub fn save_png(frame: &Frame, path: &Path) -> ImageResult<()> {
+    image::save_buffer(
+        path,
+        &frame.rgba,
+        frame.width,
+        frame.height,
+        ExtendedColorType::Rgba8,
+    )
+}
+
+/// Formats `time` in UTC as `2024-01-31_23-59-59.999`, which sorts in order
+/// and is a valid file name everywhere.
+pub fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days(seconds / 86_400);
+    let seconds = seconds % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Year, month from 1 and day from 1 of the date `days` days after
+/// 1970-01-01, from Howard Hinnant's `civil_from_days`.
+pub fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn convert(format: TextureFormat, encoding: Encoding, data: Vec<u8>) -> Vec<u8> {
+        to_rgba8(format, Texels::of(format).unwrap(), encoding, data)
+    }
+
+    #[test]
+    fn bgra_is_swizzled_to_rgba() {
+        let bgra = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(
+            convert(TextureFormat::Bgra8Unorm, Encoding::Auto, bgra.clone()),
+            [3, 2, 1, 4, 7, 6, 5, 8]
+        );
+        assert_eq!(
+            convert(TextureFormat::Rgba8Unorm, Encoding::Auto, bgra.clone()),
+            bgra
+        );
+        // Converted texels are swizzled too.
+        assert_eq!(
+            convert(
+                TextureFormat::Bgra8UnormSrgb,
+                Encoding::Linear,
+                vec![255, 0, 188, 255]
+            ),
+            [128, 0, 255, 255]
+        );
+    }
+
+    #[test]
+    fn rows_lose_their_padding() {
+        let data = [1, 2, 3, 4, 5, 6, 0, 0, 7, 8, 9, 10, 11, 12, 0, 0];
+        assert_eq!(
+            unpad_rows(&data, 8, 6),
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+        );
+        assert_eq!(unpad_rows(&data, 8, 8), data);
+    }
+
+    #[test]
+    fn rgba16float_is_encoded_to_srgb() {
+        let halves: [u16; 8] = [
+            0x3c00, 0x3800, 0x0000, 0x3800, 0xc000, 0x7c00, 0x0001, 0x3c00,
+        ];
+        let data = halves.iter().flat_map(|half| half.to_le_bytes()).collect();
+        // Alpha stays linear, and values outside 0 to 1 are clamped.
+        assert_eq!(
+            convert(TextureFormat::Rgba16Float, Encoding::Auto, data),
+            [255, 188, 0, 128, 0, 255, 0, 255]
+        );
+    }
+
+    #[test]
+    fn f16_decodes_special_values() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn rgb10a2_is_unpacked() {
+        let bits: u32 = 1023 | (512 << 20) | (3 << 30);
+        assert_eq!(
+            convert(
+                TextureFormat::Rgb10a2Unorm,
+                Encoding::Auto,
+                bits.to_le_bytes().to_vec()
+            ),
+            [255, 0, 128, 255]
+        );
+        let bits: u32 = 1 << 30;
+        assert_eq!(
+            convert(
+                TextureFormat::Rgb10a2Unorm,
+                Encoding::Srgb,
+                bits.to_le_bytes().to_vec()
+            ),
+            [0, 0, 0, 85]
+        );
+    }
+
+    #[test]
+    fn srgb_round_trips() {
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert_eq!(srgb_decode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_decode(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_encode(0.002) - 0.002 * 12.92).abs() < 1e-6);
+        assert!((srgb_encode(0.5) - 0.735_357).abs() < 1e-5);
+        for value in [0.001, 0.04, 0.2, 0.5, 0.9] {
+            assert!((srgb_decode(srgb_encode(value)) - value).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn unsupported_formats_are_rejected() {
+        assert!(Texels::of(TextureFormat::R8Unorm).is_err());
+        assert!(Texels::of(TextureFormat::Rgba32Float).is_err());
+    }
+
+    #[test]
+    fn days_are_civil_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+
+    #[test]
+    fn timestamps_sort_as_names() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_251_199_999);
+        assert_eq!(timestamp(time), "2024-02-29_23-59-59.999");
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01_00-00-00.000");
+    }
+}
@@ -0,0 +1,850 @@
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{Parser, ValueEnum};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+use crate::{
+    blit::{Adjustment, SplitScreen},
+    capture::Encoding,
+    pipeline::Geometry,
+    watcher::WatchConfig,
+};
+
+/// Live-reloading WGSL fragment shader viewer.
+#[derive(Debug, Clone, Parser)]
+#[command(version, args_override_self = true)]
+pub struct Args {
+    /// WGSL fragment shader to run. Defaults to the passes of
+    /// `shadertoy.toml` if it exists, or `shader.wgsl`.
+    #[arg(value_name = "SHADER", conflicts_with = "manifest", value_parser = parse_shader)]
+    pub shader: Option<PathBuf>,
+
+    /// Config file setting default options, see the README. Defaults to
+    /// `shadertoy/config.toml` in the user's config directory if it exists.
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Ignore the config file and use the built-in defaults.
+    #[arg(long, conflicts_with = "config")]
+    pub no_config: bool,
+
+    /// Render graph manifest describing the shader passes. Defaults to
+    /// `shadertoy.toml` if it exists, or a single pass running the shader.
+    #[arg(long, value_name = "PATH")]
+    pub manifest: Option<PathBuf>,
+
+    /// WGSL file prepended to every shader, for shared constants, functions
+    /// and structs. Watched for changes like the shaders themselves.
+    #[arg(long, value_name = "PATH")]
+    pub prelude: Option<PathBuf>,
+
+    /// Write the final WGSL of the shader, after the prelude was added, to
+    /// this file every time it's compiled. With several passes, the pass
+    /// name is added to the file name.
+    #[arg(long, value_name = "PATH")]
+    pub dump_wgsl: Option<PathBuf>,
+
+    /// Also write naga's intermediate representation of the dumped WGSL to
+    /// a `.naga.txt` file next to it.
+    #[arg(long, requires = "dump_wgsl")]
+    pub dump_naga_ir: bool,
+
+    /// Write a PNG of the first frame after every reload that compiles to
+    /// this directory, named by the time it was taken, for a visual history
+    /// of a session.
+    #[arg(long, value_name = "DIR")]
+    pub snapshot_dir: Option<PathBuf>,
+
+    /// Save the contents of the buffer passes and the clock to this file on
+    /// exit and when `F10` is pressed, to continue later with `--load-state`.
+    #[arg(long, value_name = "PATH")]
+    pub save_state: Option<PathBuf>,
+
+    /// Start from buffer pass contents saved with `--save-state`. The passes
+    /// must render at the resolution they were saved at.
+    #[arg(long, value_name = "PATH")]
+    pub load_state: Option<PathBuf>,
+
+    /// Color encoding of copied frames, snapshots and GIFs: `auto` matches
+    /// what the window shows, `srgb` encodes the shader's output as linear
+    /// colors, and `linear` keeps the values the shader wrote.
+    #[arg(long, value_name = "ENCODING", default_value = "auto")]
+    pub capture_encoding: CaptureEncoding,
+
+    /// Glob pattern of other files to watch, e.g. `shaders/**/*.wgsl`. A
+    /// change to any matching file recompiles every pass. Can be repeated.
+    #[arg(long = "watch-include-glob", value_name = "PATTERN")]
+    pub watch_globs: Vec<String>,
+
+    /// Only redraw when the window, input or shaders change, unless the
+    /// shader uses time, the frame counter or a feedback buffer.
+    #[arg(long)]
+    pub idle: bool,
+
+    /// What the window shows until the shader is first read: the magenta
+    /// fallback shader, black, or a fully transparent color.
+    #[arg(long, value_name = "COLOR", default_value = "magenta")]
+    pub initial: Initial,
+
+    /// Geometry the passes draw to cover their targets: one oversized
+    /// `triangle`, or a `quad` of two triangles for custom vertex stages.
+    /// Fragment positions are the same either way.
+    #[arg(long, value_name = "GEOMETRY", default_value = "triangle")]
+    pub fullscreen_geometry: FullscreenGeometry,
+
+    /// Render this many frames in each present mode the window supports
+    /// (`Fifo`, `Mailbox` and `Immediate`), print their frame times and exit.
+    #[arg(long, value_name = "FRAMES", conflicts_with = "idle", value_parser = clap::value_parser!(u32).range(1..))]
+    pub bench_present_modes: Option<u32>,
+
+    /// Present with `Mailbox` and a one-frame queue, pacing redraws to the
+    /// monitor's refresh rate, to cut input latency without tearing. Falls
+    /// back to `Fifo` where `Mailbox` is unsupported.
+    #[arg(long, conflicts_with = "bench_present_modes")]
+    pub low_latency: bool,
+
+    /// How frames are presented: `fifo` waits for vsync, `mailbox` replaces
+    /// queued frames without tearing, `immediate` shows them right away and
+    /// may tear. Falls back to `fifo` where the mode is unsupported.
+    #[arg(
+        long,
+        value_name = "MODE",
+        env = "SHADERTOY_PRESENT_MODE",
+        conflicts_with_all = ["bench_present_modes", "low_latency"]
+    )]
+    pub present_mode: Option<PresentMode>,
+
+    /// Render at most this many frames per second, waiting out the rest of
+    /// each frame's time instead of rendering as fast as the GPU can. `0`
+    /// renders without a limit.
+    #[arg(long, value_name = "FPS", env = "SHADERTOY_MAX_FPS", value_parser = parse_max_fps)]
+    pub max_fps: Option<f64>,
+
+    /// Without `--idle`, drop to `--throttle-fps` once the shader doesn't
+    /// animate and no input or reload happened for this long (e.g. `5s`).
+    #[arg(long, value_name = "DURATION", default_value = "2s", value_parser = parse_duration)]
+    pub throttle_after: Duration,
+
+    /// Frame rate of a static output, see `--throttle-after`.
+    #[arg(long, value_name = "FPS", default_value_t = 5.0, value_parser = parse_fps)]
+    pub throttle_fps: f64,
+
+    /// Keep rendering at the full rate when the output is static.
+    #[arg(long)]
+    pub no_throttle: bool,
+
+    /// Exit after rendering this many frames and print their frame times,
+    /// for profiling. Combine with `--deterministic` for reproducible runs.
+    #[arg(long, value_name = "FRAMES", conflicts_with_all = ["idle", "bench_present_modes"], value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_frames: Option<u32>,
+
+    /// Safe mode: wait at most this long for each frame, e.g. `2s`, and
+    /// switch to the fallback shader until the next reload if the GPU takes
+    /// longer, instead of letting a runaway shader freeze the desktop.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub frame_timeout: Option<Duration>,
+
+    /// Print shader reloads, rendered frames and device loss to stdout as
+    /// JSON lines, for applications embedding the viewer.
+    #[arg(long)]
+    pub events: bool,
+
+    /// Listen on a Unix domain socket at this path for JSON requests that
+    /// set the shader source or uniforms, save screenshots and query the
+    /// status, for editor plugins.
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+
+    /// Input of `iChannel0` in the pass drawn to the window: a built-in
+    /// texture (`noise-rgba-256`, `noise-rgba-64` or `noise-gray-256`), the
+    /// output of a pass, or `image` for the pass's own previous frame.
+    #[arg(long, value_name = "NAME")]
+    pub channel0: Option<String>,
+
+    /// Input of `iChannel1`, see `--channel0`.
+    #[arg(long, value_name = "NAME")]
+    pub channel1: Option<String>,
+
+    /// Input of `iChannel2`, see `--channel0`.
+    #[arg(long, value_name = "NAME")]
+    pub channel2: Option<String>,
+
+    /// Input of `iChannel3`, see `--channel0`.
+    #[arg(long, value_name = "NAME")]
+    pub channel3: Option<String>,
+
+    /// Animate `iChannel0` of the pass drawn to the window with the images
+    /// in this directory, played in the order of the numbers in their names
+    /// and looped.
+    #[arg(long, value_name = "DIR", conflicts_with = "channel0")]
+    pub channel0_flipbook: Option<PathBuf>,
+
+    /// Frames per second of `--channel0-flipbook`, 12 by default.
+    #[arg(long, value_name = "FPS", requires = "channel0_flipbook", value_parser = parse_fps)]
+    pub channel0_fps: Option<f64>,
+
+    /// Flipbook of `iChannel1`, see `--channel0-flipbook`.
+    #[arg(long, value_name = "DIR", conflicts_with = "channel1")]
+    pub channel1_flipbook: Option<PathBuf>,
+
+    /// Frames per second of `--channel1-flipbook`.
+    #[arg(long, value_name = "FPS", requires = "channel1_flipbook", value_parser = parse_fps)]
+    pub channel1_fps: Option<f64>,
+
+    /// Flipbook of `iChannel2`, see `--channel0-flipbook`.
+    #[arg(long, value_name = "DIR", conflicts_with = "channel2")]
+    pub channel2_flipbook: Option<PathBuf>,
+
+    /// Frames per second of `--channel2-flipbook`.
+    #[arg(long, value_name = "FPS", requires = "channel2_flipbook", value_parser = parse_fps)]
+    pub channel2_fps: Option<f64>,
+
+    /// Flipbook of `iChannel3`, see `--channel0-flipbook`.
+    #[arg(long, value_name = "DIR", conflicts_with = "channel3")]
+    pub channel3_flipbook: Option<PathBuf>,
+
+    /// Frames per second of `--channel3-flipbook`.
+    #[arg(long, value_name = "FPS", requires = "channel3_flipbook", value_parser = parse_fps)]
+    pub channel3_fps: Option<f64>,
+
+    /// Read the shaders once at startup instead of watching them for
+    /// changes, e.g. for a kiosk or a read-only filesystem.
+    #[arg(long, conflicts_with = "watch_globs")]
+    pub no_watch: bool,
+
+    /// How often watched files are checked for changes (e.g. `200ms`, `1s`).
+    /// Shaders, the prelude and the manifest are only checked this often
+    /// where the platform can't report their changes.
+    #[arg(long, value_name = "DURATION", default_value = "500ms", value_parser = parse_duration)]
+    pub watch_interval: Duration,
+
+    /// How long a changed shader file has to stay unmodified before it's
+    /// read, to avoid reading a write in progress. `0ms` disables it.
+    #[arg(long, value_name = "DURATION", default_value = "0ms", value_parser = parse_duration)]
+    pub watch_debounce: Duration,
+
+    /// Largest shader or prelude file in bytes that's read, so pointing the
+    /// viewer at a large file by mistake doesn't fill the memory. Larger
+    /// files are skipped until they change.
+    #[arg(long, value_name = "BYTES", default_value_t = 4 * 1024 * 1024)]
+    pub max_shader_size: u64,
+
+    /// Compile every `.wgsl` file in a directory without opening a window,
+    /// and exit with a nonzero status if any of them fails.
+    #[arg(long, value_name = "DIR")]
+    pub validate: Option<PathBuf>,
+
+    /// Write the shader's passes and channels to a Shadertoy JSON file
+    /// without opening a window.
+    #[arg(long, value_name = "PATH")]
+    pub export: Option<PathBuf>,
+
+    /// Render the shader without opening a window and write it to a looping
+    /// GIF, advancing time by a fixed step of `1 / fps` seconds per frame.
+    /// Uses the `--size` or `--internal` resolution, or 480x270.
+    #[arg(long, value_name = "PATH", group = "recording")]
+    pub gif: Option<PathBuf>,
+
+    /// Render `--duration` seconds of the shader at `--fps` without opening
+    /// a window, to numbered PNGs in a directory, or to a video through
+    /// `ffmpeg` if the path ends in `.mp4`, `.mkv`, `.mov` or `.webm`.
+    #[arg(long, value_name = "PATH", group = "recording")]
+    pub sequence: Option<PathBuf>,
+
+    /// Render a single frame at `--time` without opening a window and write
+    /// it to a PNG. Uses the `--size` or `--internal` resolution, or 480x270.
+    #[arg(long, value_name = "PATH", conflicts_with = "recording")]
+    pub render: Option<PathBuf>,
+
+    /// Shader time in seconds of the `--render` frame.
+    #[arg(long, value_name = "SECONDS", default_value_t = 0.0, requires = "render", value_parser = parse_time)]
+    pub time: f64,
+
+    /// Length of the `--gif` or `--sequence` recording in seconds.
+    #[arg(long, value_name = "SECONDS", default_value_t = 4.0, requires = "recording", value_parser = parse_seconds)]
+    pub duration: f64,
+
+    /// Render this many samples spread over each `--gif` or `--sequence`
+    /// frame's duration and average them, for motion blur.
+    #[arg(long, value_name = "SAMPLES", requires = "recording", value_parser = clap::value_parser!(u32).range(1..))]
+    pub motion_blur_samples: Option<u32>,
+
+    /// Also write the time, frame index, resolution and uniform values of
+    /// every `--gif` frame to this JSON file, for tools that reconstruct
+    /// the state a frame was rendered with.
+    #[arg(long, value_name = "PATH", requires = "gif")]
+    pub gif_metadata: Option<PathBuf>,
+
+    /// Color accuracy of `--gif` from 1 to 10. Higher values quantize the
+    /// frames to 256 colors more accurately but take longer.
+    #[arg(long, value_name = "QUALITY", default_value_t = 7, requires = "gif", value_parser = clap::value_parser!(u8).range(1..=10))]
+    pub gif_quality: u8,
+
+    /// Whether `--gif` frames share one palette, which avoids colors
+    /// flickering between frames, or each gets its own, which fits each
+    /// frame's colors better.
+    #[arg(
+        long,
+        value_name = "PALETTE",
+        default_value = "per-frame",
+        requires = "gif"
+    )]
+    pub gif_palette: GifPalette,
+
+    /// PNG image to use as the window icon instead of the built-in one.
+    #[arg(long, value_name = "PATH")]
+    pub icon: Option<PathBuf>,
+
+    /// Lock the shader output to an aspect ratio (e.g. `16:9`), letterboxing
+    /// the rest of the window.
+    #[arg(long, value_name = "W:H", value_parser = parse_aspect)]
+    pub aspect: Option<f32>,
+
+    /// Color of the letterbox bars as `#rrggbb`.
+    #[arg(long, value_name = "COLOR", default_value = "#000000", value_parser = parse_color)]
+    pub letterbox_color: wgpu::Color,
+
+    /// Render the shader at a fixed resolution (e.g. `1280x720`) and scale it
+    /// to fit the window.
+    #[arg(long, value_name = "WxH", value_parser = parse_size)]
+    pub internal: Option<PhysicalSize<u32>>,
+
+    /// Second shader to draw next to the first one, for comparing two
+    /// variants. Both render at the full resolution, and each fills its
+    /// half of the window.
+    #[arg(long, value_name = "PATH", conflicts_with = "accumulate")]
+    pub compare: Option<PathBuf>,
+
+    /// How `--compare` splits the window: `vertical` puts the shaders side
+    /// by side, `horizontal` one above the other.
+    #[arg(
+        long,
+        value_name = "SPLIT",
+        default_value = "vertical",
+        requires = "compare"
+    )]
+    pub split: Split,
+
+    /// Flip the shader output upside down, for shaders written for the
+    /// opposite Y convention or capture setups that expect it. The mouse
+    /// uniform is flipped to match.
+    #[arg(long)]
+    pub flip_y: bool,
+
+    /// Filter used when scaling the shader output to the window.
+    #[arg(long, value_name = "FILTER", default_value = "linear")]
+    pub upscale: Upscale,
+
+    /// Format of the window's swapchain, instead of the one the surface
+    /// prefers. Falls back to the preferred format if it's unsupported.
+    #[arg(long, value_name = "FORMAT")]
+    pub format: Option<SurfaceFormat>,
+
+    /// How the window is composited with what's behind it. Anything but
+    /// `opaque` makes the window transparent where the shader writes alpha
+    /// below 1. Falls back to the surface's default if it's unsupported.
+    #[arg(long, value_name = "MODE")]
+    pub alpha_mode: Option<AlphaMode>,
+
+    /// Graphics API to render with, instead of the best one available.
+    #[arg(long, value_name = "BACKEND", env = "SHADERTOY_BACKEND")]
+    pub backend: Option<GraphicsBackend>,
+
+    /// Prefer the integrated (`low`) or the discrete (`high`) GPU when
+    /// there are several.
+    #[arg(long, value_name = "PREFERENCE", env = "SHADERTOY_POWER")]
+    pub power: Option<PowerPreference>,
+
+    /// Render on the first GPU whose name contains this text, ignoring
+    /// case. Fails listing the available GPUs if none does.
+    #[arg(long, value_name = "NAME", env = "SHADERTOY_ADAPTER")]
+    pub adapter: Option<String>,
+
+    /// Advance time by a fixed step of `1 / fps` seconds per frame instead of
+    /// following the wall clock, so every run renders identical frames.
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Continue time and the frame counter where they were when a shader is
+    /// reloaded, instead of restarting them from zero.
+    #[arg(long)]
+    pub keep_time: bool,
+
+    /// Frame rate of the fixed time step used by `--deterministic` and
+    /// `--gif`.
+    #[arg(long, value_name = "FPS", default_value_t = 60.0, value_parser = parse_fps)]
+    pub fps: f64,
+
+    /// Average this many frames into a running mean instead of showing each
+    /// frame, for path tracers that add one noisy sample per frame. The mean
+    /// restarts when the shader, resolution, mouse or time change.
+    #[arg(long, value_name = "SAMPLES", value_parser = clap::value_parser!(u32).range(1..))]
+    pub accumulate: Option<u32>,
+
+    /// Apply a brightness, contrast and gamma correction to the shader output
+    /// on its way to the window, adjustable with `F7`/`F8`. Implied by
+    /// `--brightness`, `--contrast` and `--gamma`.
+    #[arg(long)]
+    pub adjust: bool,
+
+    /// Offset added to every color channel by the output correction.
+    #[arg(long, value_name = "OFFSET", allow_negative_numbers = true)]
+    pub brightness: Option<f32>,
+
+    /// Factor scaling every color channel around mid-gray in the output
+    /// correction.
+    #[arg(long, value_name = "FACTOR", value_parser = parse_positive)]
+    pub contrast: Option<f32>,
+
+    /// Gamma of the output correction. Values above 1 brighten the midtones.
+    #[arg(long, value_name = "GAMMA", value_parser = parse_positive)]
+    pub gamma: Option<f32>,
+
+    /// Seed for the random uniform, for reproducible runs. Drawn from OS
+    /// entropy if omitted.
+    #[arg(long, value_name = "N")]
+    pub seed: Option<u64>,
+
+    /// Size in bytes of a read-write storage buffer bound at
+    /// `@group(0) @binding(3)` that persists across frames.
+    #[arg(long, value_name = "BYTES")]
+    pub storage_size: Option<u64>,
+
+    /// Ease the mouse uniform's position toward the cursor, covering this
+    /// fraction of the distance each frame (e.g. `0.2`). The click position
+    /// is never smoothed. Follows the cursor exactly if omitted.
+    #[arg(long, value_name = "FACTOR", value_parser = parse_smoothing)]
+    pub mouse_smoothing: Option<f32>,
+
+    /// Amount the scroll uniform changes per mouse wheel notch. Negative
+    /// values invert the direction.
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        default_value_t = 1.0,
+        allow_negative_numbers = true
+    )]
+    pub scroll_speed: f32,
+
+    /// Initial window position in physical pixels as `X,Y`. Centers the
+    /// window if the position isn't on any monitor.
+    #[arg(long, value_name = "X,Y", env = "SHADERTOY_POS", value_parser = parse_position)]
+    pub pos: Option<PhysicalPosition<i32>>,
+
+    /// Show the frame rate and frame time in the window title, averaged
+    /// over this many frames (120 if no number is given) and refreshed once
+    /// a second. The title turns plain again while rendering stalls.
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        env = "SHADERTOY_TITLE_FPS",
+        num_args = 0..=1,
+        default_missing_value = "120"
+    )]
+    pub title_fps: Option<NonZeroUsize>,
+
+    /// Initial size of the window's contents in physical pixels (e.g.
+    /// `1280x720`).
+    #[arg(long, value_name = "WxH", value_parser = parse_size)]
+    pub size: Option<PhysicalSize<u32>>,
+
+    /// Title of the window.
+    #[arg(long, value_name = "TITLE", default_value = "Shadertoy")]
+    pub title: String,
+}
+
+impl Args {
+    /// How shader files are watched, or `None` with `--no-watch`.
+    pub fn watch_config(&self) -> Option<WatchConfig> {
+        (!self.no_watch).then_some(WatchConfig {
+            interval: self.watch_interval,
+            debounce: self.watch_debounce,
+        })
+    }
+
+    /// Initial output correction, or `None` if it's off.
+    pub fn adjustment(&self) -> Option<Adjustment> {
+        let enabled = self.adjust
+            || self.brightness.is_some()
+            || self.contrast.is_some()
+            || self.gamma.is_some();
+        let default = Adjustment::default();
+        enabled.then(|| {
+            Adjustment::new(
+                self.brightness.unwrap_or(default.brightness),
+                self.contrast.unwrap_or(default.contrast),
+                self.gamma.unwrap_or(default.gamma),
+            )
+        })
+    }
+
+    /// Channel inputs given on the command line, by channel index.
+    pub fn channels(&self) -> impl Iterator<Item = (usize, &str)> {
+        [
+            &self.channel0,
+            &self.channel1,
+            &self.channel2,
+            &self.channel3,
+        ]
+        .into_iter()
+        .enumerate()
+        .filter_map(|(channel, name)| Some((channel, name.as_deref()?)))
+    }
+
+    /// Flipbook directories and frame rates given on the command line, by
+    /// channel index.
+    pub fn flipbooks(&self) -> impl Iterator<Item = (usize, &Path, Option<f64>)> {
+        [
+            (&self.channel0_flipbook, self.channel0_fps),
+            (&self.channel1_flipbook, self.channel1_fps),
+            (&self.channel2_flipbook, self.channel2_fps),
+            (&self.channel3_flipbook, self.channel3_fps),
+        ]
+        .into_iter()
+        .enumerate()
+        .filter_map(|(channel, (dir, fps))| Some((channel, dir.as_deref()?, fps)))
+    }
+}
+
+/// Parses a duration in milliseconds or seconds, e.g. `200ms` or `1.5s`.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (value, scale) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 1e-3)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1.0)
+    } else {
+        return Err(format!(
+            "expected a duration like `200ms` or `1s`, got `{s}`"
+        ));
+    };
+    let value: f64 = value.trim().parse().map_err(|err| format!("{err}"))?;
+    Duration::try_from_secs_f64(value * scale).map_err(|err| format!("{err}"))
+}
+
+fn parse_shader(s: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(s);
+    if path.is_dir() {
+        return Err(format!(
+            "`{s}` is a directory, expected a WGSL file like `{}`",
+            path.join("shader.wgsl").display()
+        ));
+    }
+    Ok(path)
+}
+
+fn parse_fps(s: &str) -> Result<f64, String> {
+    let fps: f64 = s.trim().parse().map_err(|err| format!("{err}"))?;
+    if !(fps > 0.0 && fps.is_finite()) {
+        return Err("frame rate must be positive".to_owned());
+    }
+    Ok(fps)
+}
+
+fn parse_max_fps(s: &str) -> Result<f64, String> {
+    let fps: f64 = s.trim().parse().map_err(|err| format!("{err}"))?;
+    if !(fps >= 0.0 && fps.is_finite()) {
+        return Err("frame rate must not be negative".to_owned());
+    }
+    Ok(fps)
+}
+
+fn parse_smoothing(s: &str) -> Result<f32, String> {
+    let factor: f32 = s.trim().parse().map_err(|err| format!("{err}"))?;
+    if !(factor > 0.0 && factor <= 1.0) {
+        return Err("smoothing factor must be in (0, 1]".to_owned());
+    }
+    Ok(factor)
+}
+
+fn parse_positive(s: &str) -> Result<f32, String> {
+    let value: f32 = s.trim().parse().map_err(|err| format!("{err}"))?;
+    if !(value > 0.0 && value.is_finite()) {
+        return Err("value must be positive".to_owned());
+    }
+    Ok(value)
+}
+
+fn parse_seconds(s: &str) -> Result<f64, String> {
+    let seconds: f64 = s.trim().parse().map_err(|err| format!("{err}"))?;
+    if !(seconds > 0.0 && seconds.is_finite()) {
+        return Err("duration must be positive".to_owned());
+    }
+    Ok(seconds)
+}
+
+fn parse_time(s: &str) -> Result<f64, String> {
+    let seconds: f64 = s.trim().parse().map_err(|err| format!("{err}"))?;
+    if !(seconds >= 0.0 && seconds.is_finite()) {
+        return Err("time must not be negative".to_owned());
+    }
+    Ok(seconds)
+}
+
+fn parse_position(s: &str) -> Result<PhysicalPosition<i32>, String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected `X,Y`, got `{s}`"))?;
+    let x = x.trim().parse().map_err(|err| format!("{err}"))?;
+    let y = y.trim().parse().map_err(|err| format!("{err}"))?;
+    Ok(PhysicalPosition::new(x, y))
+}
+
+/// Swapchain formats `--format` can pick, in channel order and encoding.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SurfaceFormat {
+    #[value(name = "bgra8unorm")]
+    Bgra8Unorm,
+    #[value(name = "bgra8unorm-srgb")]
+    Bgra8UnormSrgb,
+    #[value(name = "rgba8unorm")]
+    Rgba8Unorm,
+    #[value(name = "rgba8unorm-srgb")]
+    Rgba8UnormSrgb,
+    #[value(name = "rgb10a2unorm")]
+    Rgb10a2Unorm,
+    #[value(name = "rgba16float")]
+    Rgba16Float,
+}
+
+impl From<SurfaceFormat> for wgpu::TextureFormat {
+    fn from(format: SurfaceFormat) -> Self {
+        match format {
+            SurfaceFormat::Bgra8Unorm => Self::Bgra8Unorm,
+            SurfaceFormat::Bgra8UnormSrgb => Self::Bgra8UnormSrgb,
+            SurfaceFormat::Rgba8Unorm => Self::Rgba8Unorm,
+            SurfaceFormat::Rgba8UnormSrgb => Self::Rgba8UnormSrgb,
+            SurfaceFormat::Rgb10a2Unorm => Self::Rgb10a2Unorm,
+            SurfaceFormat::Rgba16Float => Self::Rgba16Float,
+        }
+    }
+}
+
+/// Present modes `--present-mode` can pick.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PresentMode {
+    Fifo,
+    Mailbox,
+    Immediate,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => Self::Fifo,
+            PresentMode::Mailbox => Self::Mailbox,
+            PresentMode::Immediate => Self::Immediate,
+        }
+    }
+}
+
+/// Compositing of the window, see `--alpha-mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AlphaMode {
+    Opaque,
+    /// Colors are already multiplied by alpha.
+    #[value(name = "premultiplied")]
+    PreMultiplied,
+    /// The compositor multiplies colors by alpha.
+    #[value(name = "postmultiplied")]
+    PostMultiplied,
+    /// Left to the windowing system, e.g. set through native APIs.
+    Inherit,
+}
+
+impl From<AlphaMode> for wgpu::CompositeAlphaMode {
+    fn from(mode: AlphaMode) -> Self {
+        match mode {
+            AlphaMode::Opaque => Self::Opaque,
+            AlphaMode::PreMultiplied => Self::PreMultiplied,
+            AlphaMode::PostMultiplied => Self::PostMultiplied,
+            AlphaMode::Inherit => Self::Inherit,
+        }
+    }
+}
+
+/// Graphics APIs `--backend` can pick.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum GraphicsBackend {
+    Vulkan,
+    Dx12,
+    Metal,
+    /// OpenGL, or WebGL on the web.
+    Gl,
+}
+
+impl From<GraphicsBackend> for wgpu::Backends {
+    fn from(backend: GraphicsBackend) -> Self {
+        match backend {
+            GraphicsBackend::Vulkan => Self::VULKAN,
+            GraphicsBackend::Dx12 => Self::DX12,
+            GraphicsBackend::Metal => Self::METAL,
+            GraphicsBackend::Gl => Self::GL,
+        }
+    }
+}
+
+/// GPU to prefer when there are several, see `--power`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PowerPreference {
+    /// Usually the integrated GPU.
+    Low,
+    /// Usually the discrete GPU.
+    High,
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(power: PowerPreference) -> Self {
+        match power {
+            PowerPreference::Low => Self::LowPower,
+            PowerPreference::High => Self::HighPerformance,
+        }
+    }
+}
+
+/// Geometry covering the target of every pass, see
+/// `--fullscreen-geometry`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FullscreenGeometry {
+    Triangle,
+    Quad,
+}
+
+impl From<FullscreenGeometry> for Geometry {
+    fn from(geometry: FullscreenGeometry) -> Self {
+        match geometry {
+            FullscreenGeometry::Triangle => Self::Triangle,
+            FullscreenGeometry::Quad => Self::Quad,
+        }
+    }
+}
+
+/// Appearance of a pass before its shader is read, see `--initial`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Initial {
+    Black,
+    Magenta,
+    Transparent,
+}
+
+impl From<Initial> for wgpu::Color {
+    fn from(initial: Initial) -> Self {
+        match initial {
+            Initial::Black => Self::BLACK,
+            Initial::Magenta => Self {
+                r: 1.0,
+                g: 0.0,
+                b: 1.0,
+                a: 1.0,
+            },
+            Initial::Transparent => Self::TRANSPARENT,
+        }
+    }
+}
+
+/// Palette of the frames of a GIF, see `--gif-palette`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum GifPalette {
+    /// One palette for all frames.
+    Global,
+    /// A palette for each frame.
+    PerFrame,
+}
+
+/// Color encoding of captured frames, see `--capture-encoding`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CaptureEncoding {
+    Auto,
+    Srgb,
+    Linear,
+}
+
+impl From<CaptureEncoding> for Encoding {
+    fn from(encoding: CaptureEncoding) -> Self {
+        match encoding {
+            CaptureEncoding::Auto => Self::Auto,
+            CaptureEncoding::Srgb => Self::Srgb,
+            CaptureEncoding::Linear => Self::Linear,
+        }
+    }
+}
+
+/// Direction of the split screen, see `--split`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Split {
+    Vertical,
+    Horizontal,
+}
+
+impl From<Split> for SplitScreen {
+    fn from(split: Split) -> Self {
+        match split {
+            Split::Vertical => Self::Vertical,
+            Split::Horizontal => Self::Horizontal,
+        }
+    }
+}
+
+/// Filter used by the blit pass.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Upscale {
+    /// Smooth bilinear filtering.
+    Linear,
+    /// Hard pixel edges, for a pixel-art look.
+    Nearest,
+}
+
+impl From<Upscale> for wgpu::FilterMode {
+    fn from(upscale: Upscale) -> Self {
+        match upscale {
+            Upscale::Linear => Self::Linear,
+            Upscale::Nearest => Self::Nearest,
+        }
+    }
+}
+
+fn parse_size(s: &str) -> Result<PhysicalSize<u32>, String> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| format!("expected `WxH`, got `{s}`"))?;
+    let width: u32 = width.trim().parse().map_err(|err| format!("{err}"))?;
+    let height: u32 = height.trim().parse().map_err(|err| format!("{err}"))?;
+    if width == 0 || height == 0 {
+        return Err("size must be non-zero".to_owned());
+    }
+    Ok(PhysicalSize::new(width, height))
+}
+
+fn parse_aspect(s: &str) -> Result<f32, String> {
+    let (width, height) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected `W:H`, got `{s}`"))?;
+    let width: f32 = width.trim().parse().map_err(|err| format!("{err}"))?;
+    let height: f32 = height.trim().parse().map_err(|err| format!("{err}"))?;
+    if !(width > 0.0 && height > 0.0) {
+        return Err("aspect ratio components must be positive".to_owned());
+    }
+    Ok(width / height)
+}
+
+fn parse_color(s: &str) -> Result<wgpu::Color, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(format!("expected `#rrggbb`, got `{s}`"));
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .map(|c| f64::from(c) / 255.0)
+            .map_err(|err| format!("{err}"))
+    };
+    Ok(wgpu::Color {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+        a: 1.0,
+    })
+}
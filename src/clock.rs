@@ -0,0 +1,193 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Step used when paused in wall-clock mode before any frame was timed.
+const DEFAULT_STEP: Duration = Duration::from_nanos(16_666_667);
+/// Longest delta time in wall-clock mode, so shaders integrating over it
+/// don't leap after the window was minimized or frames stalled.
+const MAX_DELTA: Duration = Duration::from_millis(100);
+
+/// Drives the time, frame and delta time uniforms.
+///
+/// By default time follows the wall clock. With a fixed step, every frame
+/// advances time by exactly the step regardless of how long it took to
+/// render, so runs are reproducible frame for frame.
+///
+/// While paused, time stands still until the clock is stepped a frame
+/// forward or back.
+#[derive(Debug)]
+pub struct Clock {
+    fixed_step: Option<Duration>,
+    /// When time was zero, ignoring pauses.
+    start: Instant,
+    paused: bool,
+    /// Frame step requested while paused: `1` forward, `-1` back.
+    pending_step: i8,
+    /// Whether the next tick is the first since a reset.
+    restarted: bool,
+    frame: u32,
+    time: Duration,
+    delta: Duration,
+    /// Time a step moves by while paused.
+    frame_step: Duration,
+}
+
+/// Time the clock can't be moved to, since it lies further back than an
+/// [`Instant`] reaches on this system, e.g. beyond the uptime on Windows.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeOutOfRange(pub Duration);
+
+impl fmt::Display for TimeOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "time {:.3}s is out of range", self.0.as_secs_f64())
+    }
+}
+
+impl std::error::Error for TimeOutOfRange {}
+
+/// When the clock would have started to be at `time` now.
+pub fn start_for(time: Duration) -> Result<Instant, TimeOutOfRange> {
+    Instant::now().checked_sub(time).ok_or(TimeOutOfRange(time))
+}
+
+impl Clock {
+    /// Creates a wall-clock `Clock`, or one advancing by `1 / fps` per frame
+    /// if `fixed_fps` is given.
+    pub fn new(fixed_fps: Option<f64>) -> Self {
+        let fixed_step = fixed_fps.map(|fps| Duration::from_secs_f64(fps.recip()));
+        Self {
+            fixed_step,
+            start: Instant::now(),
+            paused: false,
+            pending_step: 0,
+            restarted: true,
+            frame: 0,
+            time: Duration::ZERO,
+            delta: Duration::ZERO,
+            frame_step: fixed_step.unwrap_or(DEFAULT_STEP),
+        }
+    }
+
+    /// Restarts time and the frame counter from zero.
+    pub fn reset(&mut self) {
+        self.restarted = true;
+        self.pending_step = 0;
+    }
+
+    /// Continues from `frame` at `time`, as if the clock had been running
+    /// up to it, see `--load-state`. The clock is left as is if `time` is out
+    /// of range.
+    pub fn resume_from(&mut self, frame: u32, time: Duration) -> Result<(), TimeOutOfRange> {
+        self.start = start_for(time)?;
+        self.restarted = false;
+        self.pending_step = 0;
+        self.frame = frame;
+        self.time = time;
+        self.delta = Duration::ZERO;
+        Ok(())
+    }
+
+    /// Jumps to `time` and keeps running from there. With a fixed step, the
+    /// frame counter moves to the frame at `time`, which the time follows.
+    pub fn seek(&mut self, time: Duration) -> Result<(), TimeOutOfRange> {
+        let frame = match self.fixed_step {
+            Some(step) => (time.as_secs_f64() / step.as_secs_f64()).round() as u32,
+            None => self.frame,
+        };
+        self.resume_from(frame, time)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes time. Resuming continues from the paused time
+    /// rather than jumping ahead by the time spent paused.
+    pub fn set_paused(&mut self, paused: bool) {
+        if self.paused && !paused {
+            // Stepping forward while paused is the only way to reach a time
+            // that wasn't checked, in which case time jumps on resuming.
+            match start_for(self.time) {
+                Ok(start) => self.start = start,
+                Err(err) => tracing::warn!("Can't resume from the paused time: {err}"),
+            }
+        }
+        self.paused = paused;
+        self.pending_step = 0;
+    }
+
+    /// Requests a step of one frame forward, or back if `forward` is false,
+    /// on the next tick. Pauses the clock if it's running.
+    pub fn step(&mut self, forward: bool) {
+        self.set_paused(true);
+        self.pending_step = if forward { 1 } else { -1 };
+    }
+
+    /// Advances to the next frame. The first tick after a reset is frame 0
+    /// at time 0. Returns whether the frame changed, which is always the case
+    /// unless the clock is paused and wasn't stepped.
+    pub fn tick(&mut self) -> bool {
+        if std::mem::take(&mut self.restarted) {
+            self.start = Instant::now();
+            self.frame = 0;
+            self.time = Duration::ZERO;
+            self.delta = Duration::ZERO;
+            return true;
+        }
+
+        let time = if self.paused {
+            match std::mem::take(&mut self.pending_step) {
+                0 => {
+                    self.delta = Duration::ZERO;
+                    return false;
+                }
+                1 => {
+                    self.frame += 1;
+                    self.time + self.frame_step
+                }
+                _ => {
+                    self.frame = self.frame.saturating_sub(1);
+                    self.time.saturating_sub(self.frame_step)
+                }
+            }
+        } else {
+            self.frame += 1;
+            match self.fixed_step {
+                Some(step) => step * self.frame,
+                None => self.start.elapsed(),
+            }
+        };
+        self.delta = time.abs_diff(self.time);
+        if self.fixed_step.is_none() {
+            self.delta = self.delta.min(MAX_DELTA);
+        }
+        self.time = time;
+        if self.fixed_step.is_none() && !self.paused && !self.delta.is_zero() {
+            self.frame_step = self.delta;
+        }
+        true
+    }
+
+    /// Elapsed time at the current frame.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Time since the previous frame, at most [`MAX_DELTA`] unless the
+    /// step is fixed.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    /// Time every frame advances by, if it's fixed.
+    pub fn fixed_step(&self) -> Option<Duration> {
+        self.fixed_step
+    }
+
+    /// Index of the current frame.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+}
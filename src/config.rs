@@ -0,0 +1,150 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use clap::{Command, CommandFactory, FromArgMatches, Parser};
+use toml::{Table, Value};
+
+use crate::cli::Args;
+
+/// Options that choose the config file, which it can't set itself.
+const RESERVED: &[&str] = &["config", "no-config", "help", "version"];
+
+/// Parses the command line on top of the defaults in the config file, see
+/// `--config`, and the `[options]` of the manifest.
+///
+/// Options given on the command line win over environment variables, which
+/// win over the manifest, which wins over the config file, which wins over
+/// the built-in defaults.
+pub fn parse_args() -> Result<Args, Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    if args.no_config {
+        return Ok(args);
+    }
+    let mut command = Args::command();
+    let config = match &args.config {
+        Some(path) => Some(path.clone()),
+        None => default_path().filter(|path| path.is_file()),
+    };
+    if let Some(path) = &config {
+        let table = fs::read_to_string(path)
+            .map_err(Into::into)
+            .and_then(|source| Ok(toml::from_str(&source)?));
+        command = table
+            .and_then(|table| with_defaults(command, &table))
+            .map_err(|err| format!("`{}`: {err}", path.display()))?;
+    }
+    // A manifest that can't be read is reported when the graph is loaded,
+    // so only the options are skipped here.
+    let manifest = crate::manifest_path(&args)
+        .filter(|path| path.is_file())
+        .and_then(|path| {
+            match manifest_options(&path).and_then(|table| with_defaults(command.clone(), &table)) {
+                Ok(with_options) => Some((path, with_options)),
+                Err(err) => {
+                    tracing::warn!("Ignoring the options of `{}`: {err}", path.display());
+                    None
+                }
+            }
+        });
+    if config.is_none() && manifest.is_none() {
+        return Ok(args);
+    }
+
+    let mut matches = command.try_get_matches().unwrap_or_else(|err| {
+        if let Some(path) = &config {
+            tracing::error!(
+                "Invalid options with the defaults from `{}`",
+                path.display()
+            );
+        }
+        err.exit()
+    });
+    if let Some(path) = &config {
+        tracing::debug!("Read defaults from `{}`", path.display());
+    }
+    // The values of the manifest's options are only checked by parsing
+    // them, so they're dropped if the command line doesn't parse with them.
+    if let Some((path, with_options)) = manifest {
+        match with_options.try_get_matches() {
+            Ok(with_options) => {
+                tracing::debug!("Read defaults from `{}`", path.display());
+                matches = with_options;
+            }
+            Err(err) => {
+                // Only the first line, without clap's `error: ` and hint.
+                let err = err.to_string();
+                let err = err.lines().next().unwrap_or_default();
+                tracing::warn!(
+                    "Ignoring the options of `{}`: {}",
+                    path.display(),
+                    err.trim_start_matches("error: ")
+                );
+            }
+        }
+    }
+    Ok(Args::from_arg_matches(&matches)?)
+}
+
+/// The `[options]` table of the manifest at `path`, or an empty table if it
+/// has none.
+fn manifest_options(path: &Path) -> Result<Table, Box<dyn std::error::Error>> {
+    let mut manifest: Table = toml::from_str(&fs::read_to_string(path)?)?;
+    match manifest.remove("options") {
+        Some(Value::Table(options)) => Ok(options),
+        Some(_) => Err("`options` must be a table".into()),
+        None => Ok(Table::new()),
+    }
+}
+
+/// `shadertoy/config.toml` in the user's config directory.
+fn default_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    }?;
+    Some(dir.join("shadertoy").join("config.toml"))
+}
+
+/// Replaces the defaults of `command`'s options with the values of `table`.
+/// Unlike options on the command line, defaults don't count towards options
+/// that conflict with or require others.
+fn with_defaults(
+    mut command: Command,
+    table: &Table,
+) -> Result<Command, Box<dyn std::error::Error>> {
+    for (key, value) in table {
+        let name = key.replace('_', "-");
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(name.as_str()))
+            .filter(|_| !RESERVED.contains(&name.as_str()))
+        else {
+            return Err(format!("unknown option `{key}`").into());
+        };
+        let takes_value = arg.get_action().takes_values();
+        let values = match value {
+            Value::Array(values) if takes_value => values.as_slice(),
+            value => std::slice::from_ref(value),
+        };
+        let values = values
+            .iter()
+            .map(|value| match value {
+                Value::Boolean(enabled) if !takes_value => Ok(enabled.to_string()),
+                Value::String(value) if takes_value => Ok(value.clone()),
+                Value::Integer(value) if takes_value => Ok(value.to_string()),
+                Value::Float(value) if takes_value => Ok(value.to_string()),
+                _ if takes_value => Err(format!("`{key}` must be a string or number")),
+                _ => Err(format!("`{key}` must be `true` or `false`")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let id = arg.get_id().clone();
+        command = command.mut_arg(id, |arg| arg.default_values(values));
+    }
+    Ok(command)
+}
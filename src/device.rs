@@ -0,0 +1,227 @@
+use std::fmt;
+
+use wgpu::{
+    Adapter, Backend, Backends, Device, DeviceDescriptor, DeviceType, Features, Instance,
+    InstanceDescriptor, Limits, Queue, RequestAdapterOptions, RequestDeviceError, Surface,
+};
+
+use crate::cli::{Args, PowerPreference};
+
+/// Shader features enabled wherever the adapter supports them, so shaders
+/// using them compile on GPUs that can run them.
+const SHADER_FEATURES: Features = Features::SHADER_F16
+    .union(Features::SHADER_INT64)
+    .union(Features::SHADER_PRIMITIVE_INDEX)
+    .union(Features::DUAL_SOURCE_BLENDING)
+    .union(Features::SUBGROUP);
+
+/// Requests a device with the features the adapter supports among `f64` and
+/// [`SHADER_FEATURES`], and the preferred limits. If the adapter can't
+/// provide those limits, retries with its own, and only then with
+/// [`Limits::downlevel_defaults`].
+#[tracing::instrument(skip_all)]
+pub async fn request_device(adapter: &Adapter) -> Result<(Device, Queue), RequestDeviceError> {
+    let wanted = Features::SHADER_F64 | SHADER_FEATURES;
+    let features = adapter.features().intersection(wanted);
+    tracing::debug!(
+        "The adapter doesn't support {:?}",
+        wanted.difference(features)
+    );
+    let descriptor = |required_limits| DeviceDescriptor {
+        label: Some("device"),
+        required_features: features,
+        required_limits,
+        ..Default::default()
+    };
+
+    let limits = Limits {
+        min_uniform_buffer_offset_alignment: 64,
+        ..Default::default()
+    };
+    match adapter.request_device(&descriptor(limits.clone())).await {
+        Ok(device) => return Ok(device),
+        Err(err) => tracing::warn!("Failed to create device with the preferred limits: {err}"),
+    }
+
+    // The adapter supports its own limits by definition, and they keep
+    // textures as large as it allows.
+    let adapter_limits = adapter.limits();
+    match adapter
+        .request_device(&descriptor(adapter_limits.clone()))
+        .await
+    {
+        Ok(device) => {
+            limits.check_limits_with_fail_fn(&adapter_limits, false, |name, wanted, reduced| {
+                tracing::info!(
+                    "Using the adapter's limit `{name}` of {reduced} instead of {wanted}"
+                );
+            });
+            return Ok(device);
+        }
+        Err(err) => tracing::warn!("Failed to create device with the adapter's limits: {err}"),
+    }
+
+    let fallback_limits = Limits::downlevel_defaults();
+    limits.check_limits_with_fail_fn(&fallback_limits, false, |name, wanted, reduced| {
+        tracing::warn!("Reducing limit `{name}` from {wanted} to {reduced}");
+    });
+    adapter
+        .request_device(&descriptor(fallback_limits))
+        .await
+        .inspect(|_| tracing::info!("Device created with reduced limits"))
+}
+
+/// Creates an instance of the backend chosen with `--backend`, or of every
+/// backend the platform has.
+pub fn instance(args: &Args) -> Instance {
+    let mut descriptor = InstanceDescriptor::default();
+    if let Some(backend) = args.backend {
+        descriptor.backends = backend.into();
+    }
+    Instance::new(&descriptor)
+}
+
+/// Picks the adapter to render with, see `--adapter` and `--power`. With a
+/// `surface`, only adapters that can present to it are considered.
+#[tracing::instrument(skip_all)]
+pub async fn adapter(
+    instance: &Instance,
+    args: &Args,
+    surface: Option<&Surface<'_>>,
+) -> Result<Adapter, Box<dyn std::error::Error>> {
+    let adapter = match &args.adapter {
+        Some(name) => {
+            let adapters = instance.enumerate_adapters(Backends::all()).await;
+            let needle = name.to_lowercase();
+            let mut matching: Vec<_> = adapters
+                .iter()
+                .filter(|adapter| adapter.get_info().name.to_lowercase().contains(&needle))
+                .filter(|adapter| {
+                    surface.is_none_or(|surface| adapter.is_surface_supported(surface))
+                })
+                .collect();
+            if let Some(power) = args.power {
+                matching.sort_by_key(|adapter| rank(adapter.get_info().device_type, power));
+            }
+            let Some(adapter) = matching.first() else {
+                let available = adapters
+                    .iter()
+                    .map(|adapter| {
+                        let info = adapter.get_info();
+                        format!("`{}` ({:?})", info.name, info.backend)
+                    })
+                    .collect::<Vec<_>>();
+                let available = if available.is_empty() {
+                    "none".to_owned()
+                } else {
+                    available.join(", ")
+                };
+                let presentable = if surface.is_some() {
+                    " that can render to the window"
+                } else {
+                    ""
+                };
+                return Err(format!(
+                    "No GPU{presentable} matches `--adapter {name}`, available: {available}"
+                )
+                .into());
+            };
+            (*adapter).clone()
+        }
+        None => instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: args.power.map(Into::into).unwrap_or_default(),
+                force_fallback_adapter: false,
+                compatible_surface: surface,
+            })
+            .await
+            .map_err(|err| format!("No GPU found: {err}"))?,
+    };
+    let info = adapter.get_info();
+    tracing::info!(
+        "Adapter: {} ({:?}, {:?})",
+        info.name,
+        info.backend,
+        info.device_type
+    );
+    Ok(adapter)
+}
+
+/// Order of device types for `power`, lowest first.
+fn rank(device_type: DeviceType, power: PowerPreference) -> u8 {
+    let discrete = device_type == DeviceType::DiscreteGpu;
+    let integrated = device_type == DeviceType::IntegratedGpu;
+    match power {
+        PowerPreference::High if discrete => 0,
+        PowerPreference::Low if integrated => 0,
+        _ if discrete || integrated => 1,
+        _ => 2,
+    }
+}
+
+/// Creates a device without a window, for running shaders offscreen.
+#[tracing::instrument(skip_all)]
+pub async fn headless(args: &Args) -> Result<(Adapter, Device, Queue), Box<dyn std::error::Error>> {
+    let adapter = adapter(&instance(args), args, None).await?;
+    let (device, queue) = request_device(&adapter).await?;
+    Ok((adapter, device, queue))
+}
+
+/// The selected adapter and what the device created on it supports, for
+/// showing in the HUD or deciding which features to use.
+#[derive(Debug, Clone)]
+pub struct GpuInfo {
+    pub name: String,
+    pub backend: Backend,
+    pub device_type: DeviceType,
+    /// Driver name and version, empty if the backend doesn't report them.
+    pub driver: String,
+    pub features: Features,
+    pub max_texture_dimension_2d: u32,
+    pub max_texture_dimension_3d: u32,
+    pub max_color_attachments: u32,
+    pub max_storage_buffer_binding_size: u32,
+}
+
+impl GpuInfo {
+    pub fn new(adapter: &Adapter, device: &Device) -> Self {
+        let info = adapter.get_info();
+        let limits = device.limits();
+        Self {
+            name: info.name,
+            backend: info.backend,
+            device_type: info.device_type,
+            driver: format!("{} {}", info.driver, info.driver_info)
+                .trim()
+                .to_owned(),
+            features: device.features(),
+            max_texture_dimension_2d: limits.max_texture_dimension_2d,
+            max_texture_dimension_3d: limits.max_texture_dimension_3d,
+            max_color_attachments: limits.max_color_attachments,
+            max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+        }
+    }
+}
+
+impl fmt::Display for GpuInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:?}, {:?}",
+            self.name, self.backend, self.device_type
+        )?;
+        if !self.driver.is_empty() {
+            write!(f, ", {}", self.driver)?;
+        }
+        write!(
+            f,
+            "), max 2D texture {}, max 3D texture {}, {} color attachments, \
+             {} byte storage buffers, features {:?}",
+            self.max_texture_dimension_2d,
+            self.max_texture_dimension_3d,
+            self.max_color_attachments,
+            self.max_storage_buffer_binding_size,
+            self.features
+        )
+    }
+}
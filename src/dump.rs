@@ -0,0 +1,60 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use wgpu::naga::front::wgsl;
+
+/// Where the final WGSL of a pass is written before it's compiled, see
+/// `--dump-wgsl`.
+#[derive(Debug, Clone)]
+pub struct Dump {
+    pub path: PathBuf,
+    /// Whether to also write naga's IR of the source next to it.
+    pub naga_ir: bool,
+}
+
+impl Dump {
+    /// The dump of `pass` in a graph with other passes: `out.wgsl` becomes
+    /// `out.<pass>.wgsl`, so passes don't overwrite each other.
+    pub fn for_pass(&self, pass: &str) -> Self {
+        let mut name = self.path.file_stem().unwrap_or_default().to_owned();
+        name.push(format!(".{pass}"));
+        if let Some(extension) = self.path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        Self {
+            path: self.path.with_file_name(name),
+            naga_ir: self.naga_ir,
+        }
+    }
+
+    /// Writes `source`, and its IR if requested, logging failures.
+    pub fn write(&self, source: &str) {
+        write(&self.path, source);
+        if self.naga_ir {
+            let ir = match wgsl::parse_str(source) {
+                Ok(module) => format!("{module:#?}"),
+                Err(err) => err.emit_to_string(source),
+            };
+            write(&self.path.with_extension("naga.txt"), &ir);
+        }
+    }
+}
+
+/// Writes the source of a shader that failed to compile to the temporary
+/// directory, so the exact code the device rejected can be inspected.
+pub fn write_failed(label: &str, source: &str) {
+    write(
+        &env::temp_dir().join(format!("shadertoy-{label}.wgsl")),
+        source,
+    );
+}
+
+fn write(path: &Path, contents: &str) {
+    match fs::write(path, contents) {
+        Ok(()) => tracing::info!("Wrote `{}`", path.display()),
+        Err(err) => tracing::warn!("Failed to write `{}`: {err}", path.display()),
+    }
+}
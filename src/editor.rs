@@ -0,0 +1,306 @@
+use std::{fs, io, path::PathBuf};
+
+use winit::{
+    event::{ElementState, KeyEvent},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
+};
+
+use crate::overlay::{Color, GLYPH_SIZE, Overlay};
+
+const SCALE: u32 = 2;
+const PADDING: f32 = 8.0;
+/// Columns reserved for line numbers, including a separating space.
+const GUTTER_COLUMNS: usize = 5;
+const TAB: &str = "    ";
+const KEYWORDS: &[&str] = &[
+    "alias", "break", "case", "const", "continue", "default", "discard", "else", "enable", "false",
+    "fn", "for", "if", "let", "loop", "override", "return", "struct", "switch", "true", "var",
+    "while",
+];
+
+const BACKGROUND: Color = [0.0, 0.0, 0.0, 0.8];
+const TEXT: Color = [0.9, 0.9, 0.9, 1.0];
+const GUTTER: Color = [0.45, 0.45, 0.45, 1.0];
+const KEYWORD: Color = [0.8, 0.5, 0.9, 1.0];
+const NUMBER: Color = [0.7, 0.85, 0.5, 1.0];
+const ATTRIBUTE: Color = [0.95, 0.75, 0.4, 1.0];
+const COMMENT: Color = [0.45, 0.6, 0.45, 1.0];
+const CURSOR: Color = [1.0, 1.0, 1.0, 0.8];
+const STATUS: Color = [0.2, 0.2, 0.2, 0.9];
+
+/// What the app should do after a key was handled by the editor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Compile the editor's text without saving it.
+    Apply,
+    Close,
+}
+
+/// Minimal text editor for the shader drawn to the screen, rendered with
+/// the overlay.
+///
+/// Saving writes the file, which the watcher then picks up like any other
+/// edit, so external editors keep working alongside it. Tabs are expanded
+/// to spaces on load.
+#[derive(Debug)]
+pub struct Editor {
+    pub visible: bool,
+    path: PathBuf,
+    lines: Vec<String>,
+    /// Line and character column of the cursor.
+    cursor: (usize, usize),
+    /// First visible line.
+    scroll: usize,
+    /// Whether the text was edited since it was last loaded or saved.
+    dirty: bool,
+    /// Lines that fit on the panel, as of the last draw.
+    page: usize,
+}
+
+impl Editor {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            visible: false,
+            path,
+            lines: vec![String::new()],
+            cursor: (0, 0),
+            scroll: 0,
+            dirty: false,
+            page: 1,
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Replaces the text with `source`, keeping the cursor where possible.
+    pub fn load(&mut self, source: &str) {
+        self.lines = source
+            .split('\n')
+            .map(|line| line.replace('\t', TAB))
+            .collect();
+        self.dirty = false;
+        self.clamp_cursor();
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Writes the text back to the shader file.
+    #[tracing::instrument(skip(self), fields(path = %self.path.display()))]
+    pub fn save(&mut self) -> io::Result<()> {
+        fs::write(&self.path, self.text())?;
+        self.dirty = false;
+        tracing::info!("Shader saved");
+        Ok(())
+    }
+
+    /// Handles a key press. Returns what the app should do in response, if
+    /// anything beyond editing the text.
+    pub fn key(&mut self, event: &KeyEvent, modifiers: ModifiersState) -> Option<Action> {
+        if event.state != ElementState::Pressed {
+            return None;
+        }
+        let PhysicalKey::Code(code) = event.physical_key else {
+            return None;
+        };
+        let (line, column) = self.cursor;
+        match code {
+            KeyCode::Escape => return Some(Action::Close),
+            KeyCode::Enter | KeyCode::NumpadEnter if modifiers.control_key() => {
+                return Some(Action::Apply);
+            }
+            KeyCode::KeyS if modifiers.control_key() => {
+                if let Err(err) = self.save() {
+                    tracing::error!("Failed to save `{}`: {err}", self.path.display());
+                }
+            }
+            KeyCode::ArrowLeft if column > 0 => self.cursor.1 -= 1,
+            KeyCode::ArrowLeft if line > 0 => self.cursor = (line - 1, self.line_len(line - 1)),
+            KeyCode::ArrowRight if column < self.line_len(line) => self.cursor.1 += 1,
+            KeyCode::ArrowRight if line + 1 < self.lines.len() => self.cursor = (line + 1, 0),
+            KeyCode::ArrowUp => self.move_lines(-1),
+            KeyCode::ArrowDown => self.move_lines(1),
+            KeyCode::PageUp => self.move_lines(-(self.page as isize)),
+            KeyCode::PageDown => self.move_lines(self.page as isize),
+            KeyCode::Home => self.cursor.1 = 0,
+            KeyCode::End => self.cursor.1 = self.line_len(line),
+            KeyCode::Backspace if column > 0 => {
+                let index = self.byte_index(line, column - 1);
+                self.lines[line].remove(index);
+                self.cursor.1 -= 1;
+                self.dirty = true;
+            }
+            KeyCode::Backspace if line > 0 => {
+                let removed = self.lines.remove(line);
+                self.cursor = (line - 1, self.line_len(line - 1));
+                self.lines[line - 1].push_str(&removed);
+                self.dirty = true;
+            }
+            KeyCode::Delete if column < self.line_len(line) => {
+                let index = self.byte_index(line, column);
+                self.lines[line].remove(index);
+                self.dirty = true;
+            }
+            KeyCode::Delete if line + 1 < self.lines.len() => {
+                let removed = self.lines.remove(line + 1);
+                self.lines[line].push_str(&removed);
+                self.dirty = true;
+            }
+            KeyCode::Enter | KeyCode::NumpadEnter => {
+                let index = self.byte_index(line, column);
+                let rest = self.lines[line].split_off(index);
+                let indent: String = self.lines[line]
+                    .chars()
+                    .take_while(|&ch| ch == ' ')
+                    .collect();
+                self.cursor = (line + 1, indent.len());
+                self.lines.insert(line + 1, indent + &rest);
+                self.dirty = true;
+            }
+            KeyCode::Tab => self.insert(TAB),
+            _ if modifiers.control_key() || modifiers.alt_key() => {}
+            _ => {
+                if let Some(text) = &event.text {
+                    let text: String = text.chars().filter(|ch| !ch.is_control()).collect();
+                    self.insert(&text);
+                }
+            }
+        }
+        None
+    }
+
+    /// Queues the editor on a panel covering a `screen`-sized surface,
+    /// scrolling to keep the cursor in view.
+    pub fn draw(&mut self, overlay: &mut Overlay, screen: [f32; 2]) {
+        let glyph = (GLYPH_SIZE * SCALE) as f32;
+        let [width, height] = screen;
+        overlay.rect(0.0, 0.0, width, height, BACKGROUND);
+
+        // The last row shows the status line.
+        let rows = ((height - 2.0 * PADDING) / glyph) as usize;
+        let rows = rows.saturating_sub(1).max(1);
+        self.page = rows;
+        let line = self.cursor.0;
+        if line < self.scroll {
+            self.scroll = line;
+        } else if line >= self.scroll + rows {
+            self.scroll = line + 1 - rows;
+        }
+
+        let text_x = PADDING + GUTTER_COLUMNS as f32 * glyph;
+        for (row, (number, text)) in self
+            .lines
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(rows)
+            .enumerate()
+        {
+            let y = PADDING + row as f32 * glyph;
+            let gutter = format!("{:>width$}", number + 1, width = GUTTER_COLUMNS - 1);
+            overlay.text(PADDING, y, SCALE, GUTTER, &gutter);
+            for (column, token, color) in highlight(text) {
+                overlay.text(text_x + column as f32 * glyph, y, SCALE, color, token);
+            }
+        }
+
+        let (line, column) = self.cursor;
+        let cursor_y = PADDING + (line - self.scroll) as f32 * glyph;
+        overlay.rect(text_x + column as f32 * glyph, cursor_y, 2.0, glyph, CURSOR);
+
+        let status_y = height - glyph - PADDING;
+        overlay.rect(
+            0.0,
+            status_y - PADDING / 2.0,
+            width,
+            glyph + PADDING * 1.5,
+            STATUS,
+        );
+        let status = format!(
+            "{}{}  {}:{}  Ctrl+Enter apply  Ctrl+S save  Esc close",
+            self.path.display(),
+            if self.dirty { " [modified]" } else { "" },
+            line + 1,
+            column + 1,
+        );
+        overlay.text(PADDING, status_y, SCALE, TEXT, &status);
+    }
+
+    fn insert(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let (line, column) = self.cursor;
+        let index = self.byte_index(line, column);
+        self.lines[line].insert_str(index, text);
+        self.cursor.1 += text.chars().count();
+        self.dirty = true;
+    }
+
+    fn move_lines(&mut self, by: isize) {
+        let last = self.lines.len() - 1;
+        self.cursor.0 = self.cursor.0.saturating_add_signed(by).min(last);
+        self.clamp_cursor();
+    }
+
+    fn clamp_cursor(&mut self) {
+        let line = self.cursor.0.min(self.lines.len() - 1);
+        self.cursor = (line, self.cursor.1.min(self.line_len(line)));
+    }
+
+    fn line_len(&self, line: usize) -> usize {
+        self.lines[line].chars().count()
+    }
+
+    /// Byte offset of the character at `column` in `line`.
+    fn byte_index(&self, line: usize, column: usize) -> usize {
+        let text = &self.lines[line];
+        text.char_indices()
+            .nth(column)
+            .map_or(text.len(), |(index, _)| index)
+    }
+}
+
+/// Splits `line` into `(column, token, color)` spans for simple syntax
+/// hints: keywords, numbers, `@attributes` and `//` comments.
+fn highlight(line: &str) -> Vec<(usize, &str, Color)> {
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let mut spans = Vec::new();
+    let mut column = 0;
+    let mut rest = line;
+    while let Some(ch) = rest.chars().next() {
+        if rest.starts_with("//") {
+            spans.push((column, rest, COMMENT));
+            break;
+        }
+        let (len, color) = if ch == '@' {
+            (1 + word_len(&rest[1..], is_word), ATTRIBUTE)
+        } else if ch.is_ascii_digit() {
+            (word_len(rest, |ch| is_word(ch) || ch == '.'), NUMBER)
+        } else if is_word(ch) {
+            let len = word_len(rest, is_word);
+            let color = if KEYWORDS.contains(&&rest[..len]) {
+                KEYWORD
+            } else {
+                TEXT
+            };
+            (len, color)
+        } else {
+            (ch.len_utf8(), TEXT)
+        };
+        let (token, tail) = rest.split_at(len);
+        spans.push((column, token, color));
+        column += token.chars().count();
+        rest = tail;
+    }
+    spans
+}
+
+/// Length in bytes of the prefix of `text` made of characters matching
+/// `is_word`.
+fn word_len(text: &str, is_word: impl Fn(char) -> bool) -> usize {
+    text.find(|ch| !is_word(ch)).unwrap_or(text.len())
+}
@@ -0,0 +1,73 @@
+use std::{
+    sync::{Arc, Mutex, mpsc},
+    thread,
+};
+
+use serde::Serialize;
+
+use crate::graph::Reloaded;
+
+/// Something that happened in the renderer, for hosts that keep their own
+/// UI in sync with it, see `--events` and
+/// [`Renderer::subscribe`](crate::Renderer::subscribe).
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A pass was recompiled, because its shader changed on disk or was
+    /// replaced from the editor or history.
+    Reloaded(Reloaded),
+    /// A frame was presented.
+    Rendered {
+        /// Frames rendered since the shader was loaded.
+        frame: u32,
+        /// Shader time of the frame in seconds.
+        time: f32,
+        /// Milliseconds from the start of the frame to its present.
+        duration_ms: f64,
+    },
+    /// The GPU device was lost, after which the renderer is rebuilt.
+    DeviceLost { reason: String, message: String },
+    /// The renderer was rebuilt on a new device after it was lost.
+    DeviceRecovered,
+}
+
+/// Delivers [`Event`]s to every subscriber. Clones share subscribers, so
+/// events can be sent from callbacks on other threads.
+#[derive(Debug, Clone, Default)]
+pub struct Events {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<Event>>>>,
+}
+
+impl Events {
+    /// Returns a receiver of every event sent from now on.
+    pub fn subscribe(&self) -> mpsc::Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers
+            .lock()
+            .expect("no subscriber panics while holding the lock")
+            .push(tx);
+        rx
+    }
+
+    /// Sends `event` to every subscriber, forgetting those that hung up.
+    pub fn send(&self, event: Event) {
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .expect("no subscriber panics while holding the lock");
+        subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+/// Prints every event received on `rx` to stdout as one JSON line, from a
+/// thread of its own, see `--events`.
+pub fn print(rx: mpsc::Receiver<Event>) {
+    thread::spawn(move || {
+        for event in rx {
+            match serde_json::to_string(&event) {
+                Ok(line) => println!("{line}"),
+                Err(err) => tracing::error!("Failed to serialize {event:?}: {err}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,213 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use serde_json::{Value, json};
+
+use crate::{
+    graph::{self, Manifest},
+    sampler::{Filter, SamplerOptions, Wrap},
+};
+
+const BUFFER_NAMES: [&str; 4] = ["Buffer A", "Buffer B", "Buffer C", "Buffer D"];
+/// Shadertoy's fixed ids of the outputs of Buffer A to D.
+const BUFFER_IDS: [&str; 4] = ["4dXGR8", "XsXGR8", "4sXGR8", "XdfGR8"];
+/// Shadertoy's fixed id of the output of the Image pass.
+const IMAGE_ID: &str = "4dfGRr";
+
+/// Writes `manifest` to `path` in the JSON format Shadertoy imports and
+/// exports shaders in.
+///
+/// Offscreen passes become Buffer A to D in the order they run, the prelude
+/// becomes the Common tab, and channel inputs keep their samplers.
+/// Features without an equivalent are reported as warnings. Shader code is
+/// copied as is and has to be ported to GLSL by hand.
+#[tracing::instrument(skip(manifest))]
+pub fn export(manifest: &Manifest, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let order = manifest
+        .schedule()
+        .map_err(|err| format!("Invalid render graph: {err}"))?;
+    let (screen, buffers) = order.split_last().expect("the screen pass runs last");
+    if buffers.len() > BUFFER_IDS.len() {
+        return Err(format!(
+            "Shadertoy supports {} buffer passes, the graph has {}",
+            BUFFER_IDS.len(),
+            buffers.len()
+        )
+        .into());
+    }
+    for &index in &order {
+        let pass = &manifest.passes[index];
+        if pass.blend.is_some() {
+            tracing::warn!(
+                "Pass `{}` blends over its previous frame, which Shadertoy can't; read the pass \
+                 as its own input and blend in the shader instead",
+                pass.name
+            );
+        }
+    }
+    for &index in buffers {
+        let pass = &manifest.passes[index];
+        if pass.outputs.len() > 1 {
+            tracing::warn!(
+                "Pass `{}` has several outputs, which Shadertoy can't; only `{}` is exported",
+                pass.name,
+                pass.output()
+            );
+        }
+        if pass.size.is_some() || pass.scale.is_some() {
+            tracing::warn!(
+                "Pass `{}` has its own resolution, which Shadertoy can't; it's exported at the \
+                 screen resolution",
+                pass.name
+            );
+        }
+    }
+    // Buffer slot of each offscreen output.
+    let slots: HashMap<_, _> = buffers
+        .iter()
+        .enumerate()
+        .map(|(slot, &index)| (manifest.passes[index].output(), slot))
+        .collect();
+
+    let mut renderpass = Vec::new();
+    if let Some(prelude) = &manifest.prelude {
+        renderpass.push(json!({
+            "inputs": [],
+            "outputs": [],
+            "code": fs::read_to_string(prelude)?,
+            "name": "Common",
+            "description": "",
+            "type": "common",
+        }));
+    }
+    for (slot, &index) in buffers.iter().enumerate() {
+        let mut pass = export_pass(manifest, index, &slots)?;
+        pass["name"] = BUFFER_NAMES[slot].into();
+        pass["type"] = "buffer".into();
+        pass["outputs"] = json!([{ "id": BUFFER_IDS[slot], "channel": 0 }]);
+        renderpass.push(pass);
+    }
+    let mut image = export_pass(manifest, *screen, &slots)?;
+    image["name"] = "Image".into();
+    image["type"] = "image".into();
+    image["outputs"] = json!([{ "id": IMAGE_ID, "channel": 0 }]);
+    renderpass.push(image);
+
+    if manifest.storage_size.is_some() {
+        tracing::warn!("Shadertoy has no storage buffers, the storage buffer isn't exported");
+    }
+    let name = path
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    let shader = json!({
+        "Shader": {
+            "ver": "0.1",
+            "info": {
+                "id": "",
+                "name": name,
+                "description": "",
+                "tags": [],
+            },
+            "renderpass": renderpass,
+        }
+    });
+    fs::write(path, serde_json::to_string_pretty(&shader)?)?;
+    tracing::info!(
+        "Exported {} passes to `{}`. The shaders are WGSL and need to be ported to GLSL \
+         before they run on Shadertoy",
+        order.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Code and inputs of the pass at `index`, reading offscreen outputs from
+/// the buffers in `slots`.
+fn export_pass(
+    manifest: &Manifest,
+    index: usize,
+    slots: &HashMap<&str, usize>,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let pass = &manifest.passes[index];
+    let mut inputs = Vec::new();
+    for (channel, source) in &pass.inputs {
+        let channel = graph::channel_index(channel).expect("validated by schedule");
+        let sampler = pass
+            .samplers
+            .get(&format!("iChannel{channel}"))
+            .copied()
+            .unwrap_or_default();
+        let (input_id, src, ctype) = if let Some(&slot) = slots.get(source.as_str()) {
+            let src = format!("/media/previz/buffer{slot:02}.png");
+            (BUFFER_IDS[slot].to_owned(), src, "buffer")
+        } else if manifest.volumes.contains_key(source) {
+            tracing::warn!(
+                "Pass `{}` reads volume `{source}`, which Shadertoy can't load; iChannel{channel} \
+                 isn't exported",
+                pass.name
+            );
+            continue;
+        } else if manifest.flipbooks.contains_key(source) {
+            tracing::warn!(
+                "Pass `{}` reads flipbook `{source}`, which Shadertoy can't load; \
+                 iChannel{channel} isn't exported",
+                pass.name
+            );
+            continue;
+        } else if manifest
+            .passes
+            .iter()
+            .any(|other| other.outputs.contains(source))
+        {
+            tracing::warn!(
+                "Pass `{}` reads `{source}`, an extra output of a pass that isn't exported; \
+                 iChannel{channel} isn't exported",
+                pass.name
+            );
+            continue;
+        } else {
+            tracing::warn!(
+                "Pass `{}` reads the built-in texture `{source}`; pick the matching Shadertoy \
+                 texture for iChannel{channel} after importing",
+                pass.name
+            );
+            (String::new(), source.clone(), "texture")
+        };
+        inputs.push(json!({
+            "id": input_id,
+            "src": src,
+            "ctype": ctype,
+            "channel": channel,
+            "sampler": export_sampler(&pass.name, channel, sampler),
+            "published": 1,
+        }));
+    }
+    Ok(json!({
+        "inputs": inputs,
+        "code": fs::read_to_string(&pass.shader)?,
+        "description": "",
+    }))
+}
+
+fn export_sampler(pass: &str, channel: usize, options: SamplerOptions) -> Value {
+    let filter = match options.filter {
+        Filter::Linear => "linear",
+        Filter::Nearest => "nearest",
+    };
+    let wrap = match options.wrap {
+        Wrap::Repeat => "repeat",
+        Wrap::Clamp => "clamp",
+        Wrap::Mirror => {
+            tracing::warn!(
+                "Pass `{pass}` mirrors iChannel{channel}, which Shadertoy can't; exported as repeat"
+            );
+            "repeat"
+        }
+    };
+    json!({
+        "filter": filter,
+        "wrap": wrap,
+        "vflip": "false",
+        "srgb": "false",
+        "internal": "byte",
+    })
+}
@@ -0,0 +1,187 @@
+use std::{fs, path::PathBuf};
+
+use image::{ImageFormat, ImageReader};
+use serde::Deserialize;
+use wgpu::{
+    Device, Extent3d, Origin3d, Queue, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+
+/// Frame rate of flipbooks that don't set one.
+pub const DEFAULT_FPS: f64 = 12.0;
+
+/// Animated channel declared in the manifest, cycling through the numbered
+/// images of a directory.
+///
+/// ```toml
+/// [flipbook.explosion]
+/// dir = "explosion"
+/// fps = 24
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlipbookManifest {
+    /// Directory of the frames, played in the order of the numbers in their
+    /// file names.
+    pub dir: PathBuf,
+    #[serde(default = "default_fps")]
+    pub fps: f64,
+    /// Whether to start over after the last frame instead of holding it.
+    #[serde(default = "default_loop", rename = "loop")]
+    pub looping: bool,
+}
+
+fn default_fps() -> f64 {
+    DEFAULT_FPS
+}
+
+fn default_loop() -> bool {
+    true
+}
+
+/// Frames of a flipbook, one of which is uploaded to its texture at a time.
+#[derive(Debug)]
+pub struct Flipbook {
+    texture: Texture,
+    /// RGBA texels of each frame.
+    frames: Vec<Vec<u8>>,
+    fps: f64,
+    looping: bool,
+    /// Index of the frame in `texture`.
+    current: usize,
+}
+
+impl Flipbook {
+    /// Reads the frames of the flipbook `name` and uploads the first one to
+    /// a new texture.
+    #[tracing::instrument(skip(device, queue, flipbook))]
+    pub fn load(
+        device: &Device,
+        queue: &Queue,
+        name: &str,
+        flipbook: &FlipbookManifest,
+    ) -> Result<Self, String> {
+        let error = |err: String| format!("flipbook `{name}`: {err}");
+        if !(flipbook.fps.is_finite() && flipbook.fps > 0.0) {
+            return Err(error(format!(
+                "fps {} isn't a positive number",
+                flipbook.fps
+            )));
+        }
+        let dir = &flipbook.dir;
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .map_err(|err| error(format!("can't read `{}`: {err}", dir.display())))?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_file() && ImageFormat::from_path(path).is_ok())
+            .collect();
+        if paths.is_empty() {
+            return Err(error(format!("`{}` has no images", dir.display())));
+        }
+        paths.sort_by_cached_key(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let number: String = name.chars().filter(char::is_ascii_digit).collect();
+            (number.parse::<u64>().ok(), name.into_owned())
+        });
+
+        let max = device.limits().max_texture_dimension_2d;
+        let mut size = None;
+        let mut frames = Vec::with_capacity(paths.len());
+        for path in &paths {
+            let image = ImageReader::open(path)
+                .map_err(|err| err.to_string())
+                .and_then(|reader| reader.decode().map_err(|err| err.to_string()))
+                .map_err(|err| error(format!("can't read `{}`: {err}", path.display())))?;
+            let frame_size = (image.width(), image.height());
+            match size {
+                None if frame_size.0.max(frame_size.1) > max => {
+                    return Err(error(format!(
+                        "`{}` is {}x{}, the device supports textures up to {max}x{max}",
+                        path.display(),
+                        frame_size.0,
+                        frame_size.1
+                    )));
+                }
+                None => size = Some(frame_size),
+                Some((width, height)) if frame_size != (width, height) => {
+                    return Err(error(format!(
+                        "`{}` is {}x{}, expected {width}x{height} like the first frame",
+                        path.display(),
+                        frame_size.0,
+                        frame_size.1
+                    )));
+                }
+                Some(_) => {}
+            }
+            frames.push(image.into_rgba8().into_raw());
+        }
+
+        let (width, height) = size.expect("there is at least one frame");
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(name),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let flipbook = Self {
+            texture,
+            frames,
+            fps: flipbook.fps,
+            looping: flipbook.looping,
+            current: 0,
+        };
+        flipbook.upload(queue);
+        tracing::debug!(
+            "Loaded {} frames of {width}x{height} from `{}`",
+            flipbook.frames.len(),
+            dir.display()
+        );
+        Ok(flipbook)
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Shows the frame due `time` seconds into the animation. Returns
+    /// whether it changed.
+    pub fn advance(&mut self, queue: &Queue, time: f64) -> bool {
+        let frame = (time.max(0.0) * self.fps) as usize;
+        let frame = if self.looping {
+            frame % self.frames.len()
+        } else {
+            frame.min(self.frames.len() - 1)
+        };
+        if frame == self.current {
+            return false;
+        }
+        self.current = frame;
+        self.upload(queue);
+        true
+    }
+
+    fn upload(&self, queue: &Queue) {
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &self.frames[self.current],
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(self.texture.width() * 4),
+                rows_per_image: None,
+            },
+            self.texture.size(),
+        );
+    }
+}
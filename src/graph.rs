@@ -0,0 +1,1968 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use serde::{Deserialize, Serialize};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferBindingType, BufferDescriptor,
+    BufferUsages, ColorTargetState, CommandEncoder, Device, Extent3d, FilterMode, Limits, LoadOp,
+    Operations, Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, Sampler,
+    SamplerBindingType, ShaderModule, ShaderStages, StoreOp, TexelCopyBufferLayout, Texture,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureView, TextureViewDimension,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    blit::{Blit, Viewport},
+    capture,
+    dump::Dump,
+    flipbook::{Flipbook, FlipbookManifest},
+    image_file::{self, ImageManifest},
+    pipeline::{self, Blend, Geometry, Modules},
+    preprocess, reflect,
+    sampler::{self, SamplerOptions},
+    state::SavedTarget,
+    textures,
+    volume::{self, VolumeManifest},
+    watcher::{self, WatchConfig, WatchGuard},
+};
+
+/// Manifest looked up in the working directory when `--manifest` isn't given.
+pub const MANIFEST_FILE: &str = "shadertoy.toml";
+/// Number of `iChannel` inputs each pass can bind.
+pub const CHANNEL_COUNT: usize = 4;
+/// Contents of the buffer bound after the channels of a pass, the `own`
+/// resolution and those of its `channels`.
+pub type Resolutions = [[f32; 4]; 1 + CHANNEL_COUNT];
+/// Format of the offscreen textures passes render into.
+pub const TARGET_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+/// Output name of the pass drawn to the window.
+const SCREEN: &str = "screen";
+
+/// Declarative description of the passes making up a shader, read from a
+/// `shadertoy.toml` manifest.
+///
+/// ```toml
+/// [[pass]]
+/// name = "buffer_a"
+/// shader = "buffer_a.wgsl"
+/// inputs = { iChannel0 = "buffer_a" } # previous frame of this pass
+///
+/// [[pass]]
+/// name = "image"
+/// shader = "image.wgsl"
+/// inputs = { iChannel0 = "buffer_a" }
+/// output = "screen"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Where to write the final source of each pass, see `--dump-wgsl`.
+    #[serde(skip)]
+    pub dump: Option<Dump>,
+    /// Color shown until a pass's shader is first read instead of the
+    /// fallback shader, see `--initial`.
+    #[serde(skip)]
+    pub initial: Option<wgpu::Color>,
+    /// Source run by passes whose shader file doesn't exist at startup,
+    /// until the file is created.
+    #[serde(skip)]
+    pub default_source: Option<&'static str>,
+    /// Largest shader file that's read, see `--max-shader-size`.
+    #[serde(skip)]
+    pub max_file_size: Option<u64>,
+    /// Geometry the passes draw, see `--fullscreen-geometry`.
+    #[serde(skip)]
+    pub geometry: Geometry,
+    /// Size in bytes of the storage buffer, see `--storage-size`.
+    pub storage_size: Option<u64>,
+    /// File prepended to every shader, see `--prelude`.
+    pub prelude: Option<PathBuf>,
+    /// Glob patterns of other files whose changes recompile every pass, see
+    /// `--watch-include-glob`.
+    #[serde(default)]
+    pub watch: Vec<String>,
+    #[serde(rename = "pass")]
+    pub passes: Vec<PassManifest>,
+    /// 3D textures passes can read by name.
+    #[serde(default, rename = "volume")]
+    pub volumes: BTreeMap<String, VolumeManifest>,
+    /// Animated textures passes can read by name.
+    #[serde(default, rename = "flipbook")]
+    pub flipbooks: BTreeMap<String, FlipbookManifest>,
+    /// Image files passes can read by name.
+    #[serde(default, rename = "image")]
+    pub images: BTreeMap<String, ImageManifest>,
+    /// Defaults of command-line options, read before the manifest is
+    /// loaded, see `--config`.
+    #[serde(default)]
+    pub options: toml::Table,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PassManifest {
+    pub name: String,
+    /// Path to the fragment shader, relative to the manifest.
+    pub shader: PathBuf,
+    /// Maps `iChannel0`..`iChannel3` to the output of another pass or a
+    /// built-in texture.
+    #[serde(default)]
+    pub inputs: BTreeMap<String, String>,
+    /// Name of the texture the pass renders into, defaulting to the pass
+    /// name. Exactly one pass must output to `screen`.
+    pub output: Option<String>,
+    /// Names of the textures the pass renders into at `@location(0)`,
+    /// `@location(1)` and so on, instead of a single `output`. Passes with
+    /// several outputs can't render to `screen`.
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    /// Overrides the filtering and wrapping of the samplers of
+    /// `iChannel0`..`iChannel3`.
+    #[serde(default)]
+    pub samplers: BTreeMap<String, SamplerOptions>,
+    /// Blends the output over the previous frame of the pass's targets
+    /// instead of replacing it.
+    pub blend: Option<Blend>,
+    /// Fixed `[width, height]` of the pass's targets, instead of following
+    /// the screen resolution.
+    pub size: Option<[u32; 2]>,
+    /// Resolution of the pass's targets as a fraction of the screen
+    /// resolution, e.g. `0.5` for a half-resolution blur.
+    pub scale: Option<f32>,
+}
+
+impl Manifest {
+    /// The pass drawn to the window, if any.
+    pub fn screen_pass_mut(&mut self) -> Option<&mut PassManifest> {
+        self.passes.iter_mut().find(|pass| pass.output() == SCREEN)
+    }
+
+    /// Reads the manifest at `path`, resolving shader paths relative to it.
+    #[tracing::instrument]
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::parse(&fs::read_to_string(path)?, path)
+    }
+
+    /// Parses `source`, the contents of the manifest at `path`, resolving
+    /// shader paths relative to it.
+    pub fn parse(source: &str, path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut manifest: Self = toml::from_str(source)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for pass in &mut manifest.passes {
+            pass.shader = dir.join(&pass.shader);
+        }
+        if let Some(prelude) = &mut manifest.prelude {
+            *prelude = dir.join(&*prelude);
+        }
+        for pattern in &mut manifest.watch {
+            *pattern = dir.join(&*pattern).to_string_lossy().into_owned();
+        }
+        for volume in manifest.volumes.values_mut() {
+            if let Some(data) = &mut volume.data {
+                *data = dir.join(&*data);
+            }
+            for slice in &mut volume.slices {
+                *slice = dir.join(&*slice);
+            }
+        }
+        for flipbook in manifest.flipbooks.values_mut() {
+            flipbook.dir = dir.join(&flipbook.dir);
+        }
+        for image in manifest.images.values_mut() {
+            image.path = dir.join(&image.path);
+        }
+        tracing::info!(
+            "Loaded manifest `{}` with {} passes",
+            path.display(),
+            manifest.passes.len()
+        );
+        Ok(manifest)
+    }
+
+    /// A graph with a single pass rendering `shader` to the screen. Images
+    /// named `channel0.png` to `channel3.png`, or `.jpg`, next to the shader
+    /// are read as its channels.
+    pub fn single(shader: PathBuf) -> Self {
+        let dir = shader.parent().unwrap_or(Path::new(""));
+        let mut inputs = BTreeMap::new();
+        let mut images = BTreeMap::new();
+        for channel in 0..CHANNEL_COUNT {
+            let Some(path) = ["png", "jpg", "jpeg"]
+                .iter()
+                .map(|extension| dir.join(format!("channel{channel}.{extension}")))
+                .find(|path| path.is_file())
+            else {
+                continue;
+            };
+            tracing::debug!("Reading `{}` as `iChannel{channel}`", path.display());
+            let name = format!("channel{channel}-image");
+            inputs.insert(format!("iChannel{channel}"), name.clone());
+            images.insert(
+                name,
+                ImageManifest {
+                    path,
+                    srgb: false,
+                    mipmaps: true,
+                },
+            );
+        }
+        Self {
+            dump: None,
+            initial: None,
+            default_source: None,
+            max_file_size: None,
+            geometry: Geometry::default(),
+            storage_size: None,
+            prelude: None,
+            watch: Vec::new(),
+            options: toml::Table::new(),
+            passes: vec![PassManifest {
+                name: "image".to_owned(),
+                shader,
+                inputs,
+                output: Some(SCREEN.to_owned()),
+                outputs: Vec::new(),
+                samplers: BTreeMap::new(),
+                blend: None,
+                size: None,
+                scale: None,
+            }],
+            volumes: BTreeMap::new(),
+            flipbooks: BTreeMap::new(),
+            images,
+        }
+    }
+
+    /// Validates the graph and returns the pass indices in dependency order.
+    ///
+    /// A pass reading its own output sees the previous frame; any other
+    /// cycle is an error.
+    pub fn schedule(&self) -> Result<Vec<usize>, String> {
+        for name in self.volumes.keys() {
+            if textures::is_builtin(name) {
+                return Err(format!(
+                    "volume `{name}` has the name of a built-in texture"
+                ));
+            }
+        }
+        for name in self.flipbooks.keys() {
+            if textures::is_builtin(name) || self.volumes.contains_key(name) {
+                return Err(format!(
+                    "flipbook `{name}` has the name of a built-in texture or a volume"
+                ));
+            }
+        }
+        for name in self.images.keys() {
+            if textures::is_builtin(name)
+                || self.volumes.contains_key(name)
+                || self.flipbooks.contains_key(name)
+            {
+                return Err(format!(
+                    "image `{name}` has the name of a built-in texture, a volume or a flipbook"
+                ));
+            }
+        }
+        let mut writers = HashMap::new();
+        for (index, pass) in self.passes.iter().enumerate() {
+            if self.passes[..index].iter().any(|p| p.name == pass.name) {
+                return Err(format!("duplicate pass name `{}`", pass.name));
+            }
+            if pass.output.is_some() && !pass.outputs.is_empty() {
+                return Err(format!(
+                    "pass `{}` sets both `output` and `outputs`",
+                    pass.name
+                ));
+            }
+            let outputs = pass.outputs();
+            if outputs.len() > 1 && outputs.contains(&SCREEN) {
+                return Err(format!(
+                    "pass `{}` can't render to `{SCREEN}` alongside other outputs",
+                    pass.name
+                ));
+            }
+            if pass.size.is_some() || pass.scale.is_some() {
+                if outputs.contains(&SCREEN) {
+                    return Err(format!(
+                        "pass `{}` renders to `{SCREEN}`, which can't have its own size",
+                        pass.name
+                    ));
+                }
+                if pass.size.is_some() && pass.scale.is_some() {
+                    return Err(format!("pass `{}` sets both `size` and `scale`", pass.name));
+                }
+                if pass.size.is_some_and(|size| size.contains(&0)) {
+                    return Err(format!("pass `{}` has an empty `size`", pass.name));
+                }
+                if pass
+                    .scale
+                    .is_some_and(|scale| !(scale.is_finite() && scale > 0.0))
+                {
+                    return Err(format!(
+                        "pass `{}` has a `scale` that isn't a positive number",
+                        pass.name
+                    ));
+                }
+            }
+            for output in outputs {
+                if textures::is_builtin(output) {
+                    return Err(format!(
+                        "pass `{}` outputs to `{output}`, the name of a built-in texture",
+                        pass.name
+                    ));
+                }
+                if self.volumes.contains_key(output)
+                    || self.flipbooks.contains_key(output)
+                    || self.images.contains_key(output)
+                {
+                    return Err(format!(
+                        "pass `{}` outputs to `{output}`, the name of a volume, flipbook or image",
+                        pass.name
+                    ));
+                }
+                if writers.insert(output, index).is_some() {
+                    return Err(format!("multiple passes output to `{output}`"));
+                }
+            }
+        }
+        if !writers.contains_key(SCREEN) {
+            return Err(format!("no pass outputs to `{SCREEN}`"));
+        }
+
+        let mut dependencies = Vec::with_capacity(self.passes.len());
+        for (index, pass) in self.passes.iter().enumerate() {
+            let mut deps = Vec::new();
+            for channel in pass.inputs.keys().chain(pass.samplers.keys()) {
+                channel_index(channel).ok_or_else(|| {
+                    format!(
+                        "pass `{}` has unknown channel `{channel}`, expected `iChannel0`..`iChannel{}`",
+                        pass.name,
+                        CHANNEL_COUNT - 1
+                    )
+                })?;
+            }
+            for source in pass.inputs.values() {
+                if textures::is_builtin(source)
+                    || self.volumes.contains_key(source)
+                    || self.flipbooks.contains_key(source)
+                    || self.images.contains_key(source)
+                {
+                    continue;
+                }
+                let writer = match writers.get(source.as_str()) {
+                    Some(&writer) => writer,
+                    // The pass drawn to the screen reads its own previous
+                    // frame by its name.
+                    None if *source == pass.name && pass.output() == SCREEN => index,
+                    None => {
+                        return Err(format!(
+                            "pass `{}` reads unknown output `{source}`, expected a pass output, a volume, a flipbook, an image or one of {:?}",
+                            pass.name,
+                            textures::BUILTIN_TEXTURES
+                        ));
+                    }
+                };
+                if source == SCREEN {
+                    return Err(format!("pass `{}` can't read the screen", pass.name));
+                }
+                if writer != index {
+                    deps.push(writer);
+                }
+            }
+            dependencies.push(deps);
+        }
+
+        let mut order = Vec::with_capacity(self.passes.len());
+        let mut state = vec![Visit::New; self.passes.len()];
+        for index in 0..self.passes.len() {
+            self.visit(index, &dependencies, &mut state, &mut order)?;
+        }
+        // The screen pass runs last even if nothing depends on it.
+        let screen = writers[SCREEN];
+        order.retain(|&index| index != screen);
+        order.push(screen);
+        Ok(order)
+    }
+
+    /// Checks that the targets of every pass fit within `limits`.
+    pub fn check_limits(&self, limits: &Limits) -> Result<(), String> {
+        // Passes render all their outputs at once, up to the number of
+        // attachments the device can write per pixel.
+        let bytes_per_target = TARGET_FORMAT
+            .target_pixel_byte_cost()
+            .expect("targets are renderable");
+        let max_outputs = limits
+            .max_color_attachments
+            .min(limits.max_color_attachment_bytes_per_sample / bytes_per_target)
+            as usize;
+        let max_size = limits.max_texture_dimension_2d;
+        for pass in &self.passes {
+            if let TargetSize::Fixed(fixed) = pass.target_size()
+                && fixed.width.max(fixed.height) > max_size
+            {
+                return Err(format!(
+                    "Pass `{}` is {}x{}, the device supports textures up to {max_size}x{max_size}",
+                    pass.name, fixed.width, fixed.height
+                ));
+            }
+            let outputs = pass.outputs().len();
+            if outputs > max_outputs {
+                return Err(format!(
+                    "Pass `{}` has {outputs} outputs, the device can render to at most {max_outputs}",
+                    pass.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn visit(
+        &self,
+        index: usize,
+        dependencies: &[Vec<usize>],
+        state: &mut [Visit],
+        order: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        match state[index] {
+            Visit::Done => return Ok(()),
+            Visit::InProgress => {
+                return Err(format!(
+                    "pass `{}` depends on itself through other passes",
+                    self.passes[index].name
+                ));
+            }
+            Visit::New => {}
+        }
+        state[index] = Visit::InProgress;
+        for &dependency in &dependencies[index] {
+            self.visit(dependency, dependencies, state, order)?;
+        }
+        state[index] = Visit::Done;
+        order.push(index);
+        Ok(())
+    }
+}
+
+impl PassManifest {
+    /// Name of the texture the pass renders into, at `@location(0)` if it
+    /// has several.
+    pub fn output(&self) -> &str {
+        self.output
+            .as_deref()
+            .or(self.outputs.first().map(String::as_str))
+            .unwrap_or(&self.name)
+    }
+
+    /// Names of the textures the pass renders into, by location.
+    pub fn outputs(&self) -> Vec<&str> {
+        match self.outputs.as_slice() {
+            [] => vec![self.output()],
+            outputs => outputs.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// Resolution of the pass's targets.
+    fn target_size(&self) -> TargetSize {
+        match (self.size, self.scale) {
+            (Some([width, height]), _) => TargetSize::Fixed(PhysicalSize::new(width, height)),
+            (None, scale) => TargetSize::Scaled(scale.unwrap_or(1.0)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Visit {
+    New,
+    InProgress,
+    Done,
+}
+
+/// Parses `iChannelN` into `N`.
+pub fn channel_index(name: &str) -> Option<usize> {
+    name.strip_prefix("iChannel")?
+        .parse()
+        .ok()
+        .filter(|&index| index < CHANNEL_COUNT)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Input {
+    /// Output of a pass.
+    Target {
+        target: usize,
+        /// Whether the input is read before its pass runs this frame, and so
+        /// sees the previous frame's contents.
+        previous: bool,
+    },
+    /// Index into the built-in textures and volumes used by the graph.
+    Texture(usize),
+}
+
+/// Outcome of recompiling a pass.
+#[derive(Debug, Clone, Serialize)]
+pub struct Reloaded {
+    pub pass: String,
+    pub shader: PathBuf,
+    /// Why the shader failed to compile, in which case the pass runs the
+    /// fallback shader.
+    pub error: Option<String>,
+}
+
+/// Double-buffered offscreen output of a pass.
+#[derive(Debug)]
+struct Target {
+    name: String,
+    size: TargetSize,
+    textures: [Texture; 2],
+}
+
+/// Resolution of a target, see [`PassManifest::size`] and
+/// [`PassManifest::scale`].
+#[derive(Debug, Clone, Copy)]
+enum TargetSize {
+    /// Fraction of the screen resolution.
+    Scaled(f32),
+    Fixed(PhysicalSize<u32>),
+}
+
+impl TargetSize {
+    /// Size of the target when the screen is `screen`, at most `max` pixels
+    /// on each side.
+    fn resolve(self, screen: PhysicalSize<u32>, max: u32) -> PhysicalSize<u32> {
+        match self {
+            Self::Scaled(scale) => {
+                let side = |side: u32| ((side as f32 * scale).round() as u32).clamp(1, max);
+                PhysicalSize::new(side(screen.width), side(screen.height))
+            }
+            Self::Fixed(size) => size,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Pass {
+    name: String,
+    shader: PathBuf,
+    source_rx: mpsc::Receiver<String>,
+    /// Last source read from `shader`, without the prelude.
+    source: Option<String>,
+    pipeline: RenderPipeline,
+    /// Whether `source` compiled. If it didn't, `pipeline` keeps running the
+    /// last version that did.
+    compiled: bool,
+    /// Whether `pipeline` runs the fallback shader, because no version of
+    /// `source` compiled yet.
+    fallback: bool,
+    /// Why `source` last failed to compile.
+    error: Option<String>,
+    /// `(group, binding)` of the resources the shader uses.
+    bindings: Vec<(u32, u32)>,
+    inputs: [Option<Input>; CHANNEL_COUNT],
+    samplers: [Sampler; CHANNEL_COUNT],
+    /// Layout of the channels, which differ between passes in whether they
+    /// are 2D textures or volumes.
+    channel_layout: BindGroupLayout,
+    /// Offscreen target of each output location, or none for the screen.
+    outputs: Vec<usize>,
+    /// Channel bind groups for each frame parity.
+    bind_groups: Vec<BindGroup>,
+    dump: Option<Dump>,
+    blend: Option<Blend>,
+}
+
+impl Pass {
+    fn outcome(&self) -> Reloaded {
+        Reloaded {
+            pass: self.name.clone(),
+            shader: self.shader.clone(),
+            error: self.error.clone(),
+        }
+    }
+}
+
+/// Passes from a [`Manifest`], ready to render in dependency order.
+#[derive(Debug)]
+pub struct RenderGraph {
+    passes: Vec<Pass>,
+    targets: Vec<Target>,
+    placeholder: TextureView,
+    textures: Vec<Texture>,
+    /// Animated textures among `textures`.
+    flipbooks: Vec<Flipbook>,
+    /// Image files among `textures`, reloaded when they change.
+    images: Vec<Image>,
+    /// Notified when the files of `images` change.
+    images_rx: Option<mpsc::Receiver<()>>,
+    /// Screen resolution, which targets without a fixed size follow.
+    size: PhysicalSize<u32>,
+    prelude_rx: Option<mpsc::Receiver<String>>,
+    prelude: Option<String>,
+    /// Notified when files matching the manifest's `watch` globs change.
+    watch_rx: Option<mpsc::Receiver<()>>,
+    /// Stops the threads watching the files of the graph when it's dropped,
+    /// e.g. replaced by a new version of the manifest.
+    _watch_guard: WatchGuard,
+    screen_format: TextureFormat,
+    /// Used for passes whose shader is missing or fails to compile.
+    fallback_shader: ShaderModule,
+    vertex_shader: ShaderModule,
+    geometry: Geometry,
+    /// Set if the pass drawn to the screen reads its own previous frame.
+    screen_copy: Option<ScreenCopy>,
+    /// Which texture of each target pair is written this frame.
+    parity: usize,
+}
+
+/// Image file a pass reads, see [`ImageManifest`].
+#[derive(Debug)]
+struct Image {
+    /// Index into the graph's textures.
+    texture: usize,
+    name: String,
+    manifest: ImageManifest,
+}
+
+/// Draws the target of a screen pass reading its own previous frame to the
+/// screen, since the screen itself can't be read back.
+#[derive(Debug)]
+struct ScreenCopy {
+    target: usize,
+    blit: Blit,
+    /// Bind group sampling each texture of the target pair.
+    bind_groups: Vec<BindGroup>,
+}
+
+impl RenderGraph {
+    #[tracing::instrument(skip_all)]
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        manifest: &Manifest,
+        screen_format: TextureFormat,
+        uniform_layout: &BindGroupLayout,
+        watch: Option<WatchConfig>,
+        size: PhysicalSize<u32>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let fallback_shader = pipeline::create_fallback_shader(device);
+        let vertex_shader = pipeline::create_vertex_shader(device, manifest.geometry);
+        let initial_shader = manifest
+            .initial
+            .map(|color| pipeline::create_solid_shader(device, color));
+        let order = manifest
+            .schedule()
+            .map_err(|err| format!("Invalid render graph: {err}"))?;
+        tracing::debug!(
+            order = ?order.iter().map(|&i| &manifest.passes[i].name).collect::<Vec<_>>(),
+            "Scheduled render graph"
+        );
+
+        let placeholder =
+            create_target_texture(device, "placeholder channel", PhysicalSize::new(1, 1))
+                .create_view(&Default::default());
+
+        // Without a watch config, every file is read once up front.
+        let watch_guard = WatchGuard::default();
+        let watch_file = |path: PathBuf| match watch {
+            Some(config) => {
+                watcher::spawn_watcher_thread(path, config, manifest.max_file_size, &watch_guard)
+            }
+            None => Ok(watcher::read_once(&path, manifest.max_file_size)),
+        };
+        let prelude_rx = manifest
+            .prelude
+            .as_ref()
+            .map(|path| watch_file(path.to_owned()))
+            .transpose()?;
+        let prelude = prelude_rx.as_ref().and_then(|rx| rx.try_recv().ok());
+        let watch_rx = watch
+            .filter(|_| !manifest.watch.is_empty())
+            .map(|config| {
+                watcher::spawn_glob_watcher_thread(manifest.watch.clone(), config, &watch_guard)
+            })
+            .transpose()?;
+
+        let limits = device.limits();
+        manifest.check_limits(&limits)?;
+        let max_size = limits.max_texture_dimension_2d;
+        let mut targets = Vec::new();
+        let mut screen_feedback = None;
+        for &index in &order {
+            let pass = &manifest.passes[index];
+            let target_size = pass.target_size();
+            for output in pass.outputs() {
+                if output != SCREEN {
+                    let size = target_size.resolve(size, max_size);
+                    targets.push(Target {
+                        name: output.to_owned(),
+                        size: target_size,
+                        textures: [0, 1].map(|_| create_target_texture(device, output, size)),
+                    });
+                }
+            }
+            // A screen pass reading its own previous frame renders into a
+            // target of its own first. It runs last, so every other target
+            // already exists.
+            if pass.output() == SCREEN
+                && pass.inputs.values().any(|source| *source == pass.name)
+                && !targets.iter().any(|target| target.name == pass.name)
+            {
+                let target_size = TargetSize::Scaled(1.0);
+                let size = target_size.resolve(size, max_size);
+                screen_feedback = Some(targets.len());
+                targets.push(Target {
+                    name: pass.name.clone(),
+                    size: target_size,
+                    textures: [0, 1].map(|_| create_target_texture(device, &pass.name, size)),
+                });
+            }
+        }
+
+        // Built-in textures, volumes, flipbooks and images are only loaded
+        // once a pass reads them, and passes share samplers with the same
+        // options.
+        let mut textures = Vec::new();
+        let mut flipbooks = Vec::new();
+        let mut images = Vec::new();
+        let mut dimensions = Vec::new();
+        let mut samplers = HashMap::new();
+        let mut texture_names = Vec::new();
+        let mut passes = Vec::with_capacity(order.len());
+        for (position, &index) in order.iter().enumerate() {
+            let pass = &manifest.passes[index];
+            let target_index = |name: &str| targets.iter().position(|t| t.name == name);
+
+            let mut inputs = [None; CHANNEL_COUNT];
+            let mut channel_dimensions = [TextureViewDimension::D2; CHANNEL_COUNT];
+            for (channel, source) in &pass.inputs {
+                let channel = channel_index(channel).expect("validated by schedule");
+                if let Some(target) = target_index(source) {
+                    // Only the screen feedback target has no pass writing
+                    // it by name, and it's read by the pass that writes it.
+                    let writer = order
+                        .iter()
+                        .position(|&i| manifest.passes[i].outputs().contains(&source.as_str()))
+                        .unwrap_or(position);
+                    inputs[channel] = Some(Input::Target {
+                        target,
+                        previous: writer >= position,
+                    });
+                    continue;
+                }
+                let texture = match texture_names.iter().position(|name| name == source) {
+                    Some(texture) => texture,
+                    None => {
+                        let (texture, dimension) =
+                            if let Some(volume) = manifest.volumes.get(source) {
+                                (
+                                    volume::create_volume(device, queue, source, volume)?,
+                                    TextureViewDimension::D3,
+                                )
+                            } else if let Some(flipbook) = manifest.flipbooks.get(source) {
+                                let flipbook = Flipbook::load(device, queue, source, flipbook)?;
+                                let texture = flipbook.texture().clone();
+                                flipbooks.push(flipbook);
+                                (texture, TextureViewDimension::D2)
+                            } else if let Some(image) = manifest.images.get(source) {
+                                images.push(Image {
+                                    texture: textures.len(),
+                                    name: source.clone(),
+                                    manifest: image.clone(),
+                                });
+                                (
+                                    image_file::create_image(device, queue, source, image)?,
+                                    TextureViewDimension::D2,
+                                )
+                            } else {
+                                (
+                                    textures::create_builtin(device, queue, source)
+                                        .expect("validated by schedule"),
+                                    TextureViewDimension::D2,
+                                )
+                            };
+                        textures.push(texture);
+                        dimensions.push(dimension);
+                        texture_names.push(source.clone());
+                        textures.len() - 1
+                    }
+                };
+                inputs[channel] = Some(Input::Texture(texture));
+                channel_dimensions[channel] = dimensions[texture];
+            }
+
+            let samplers = std::array::from_fn(|channel| {
+                let options = pass
+                    .samplers
+                    .get(&format!("iChannel{channel}"))
+                    .copied()
+                    .unwrap_or_default();
+                samplers
+                    .entry(options)
+                    .or_insert_with(|| sampler::create_sampler(device, "channel sampler", options))
+                    .clone()
+            });
+
+            let channel_layout = create_channel_layout(device, &pass.name, channel_dimensions);
+            let dump = manifest.dump.as_ref().map(|dump| match order.len() {
+                1 => dump.clone(),
+                _ => dump.for_pass(&pass.name),
+            });
+
+            let source_rx = watch_file(pass.shader.clone())?;
+            let source = source_rx.try_recv().ok().or_else(|| {
+                let source = manifest.default_source.filter(|_| !pass.shader.exists())?;
+                tracing::info!(
+                    "`{}` doesn't exist, running the built-in shader until it's created",
+                    pass.shader.display()
+                );
+                Some(source.to_owned())
+            });
+            let fragment = source
+                .as_deref()
+                .map(|source| preprocess::preprocess(prelude.as_deref(), source));
+            let outputs: Vec<_> = match screen_feedback {
+                Some(target) if pass.output() == SCREEN => vec![target],
+                _ => pass
+                    .outputs()
+                    .into_iter()
+                    .filter_map(target_index)
+                    .collect(),
+            };
+            let (pipeline, error) = pipeline::create_pipeline(
+                device,
+                &pass.name,
+                &color_targets(&outputs, screen_format, pass.blend),
+                Modules {
+                    vertex: &vertex_shader,
+                    fallback: match (&fragment, &initial_shader) {
+                        (None, Some(initial_shader)) => initial_shader,
+                        _ => &fallback_shader,
+                    },
+                },
+                fragment.as_ref(),
+                &[uniform_layout, &channel_layout],
+                dump.as_ref(),
+            );
+            let compiled = fragment.is_some() && error.is_none();
+            let bindings = fragment
+                .and_then(|fragment| reflect::used_bindings(&fragment.source))
+                .unwrap_or_default();
+
+            passes.push(Pass {
+                name: pass.name.clone(),
+                shader: pass.shader.clone(),
+                source_rx,
+                source,
+                pipeline,
+                compiled,
+                fallback: !compiled,
+                error,
+                bindings,
+                inputs,
+                samplers,
+                channel_layout,
+                outputs,
+                bind_groups: Vec::new(),
+                dump,
+                blend: pass.blend,
+            });
+        }
+
+        let images_rx = watch
+            .filter(|_| !images.is_empty())
+            .map(|config| {
+                let paths = images
+                    .iter()
+                    .map(|image| glob::Pattern::escape(&image.manifest.path.to_string_lossy()))
+                    .collect();
+                watcher::spawn_glob_watcher_thread(paths, config, &watch_guard)
+            })
+            .transpose()?;
+        let mut graph = Self {
+            passes,
+            targets,
+            placeholder,
+            textures,
+            flipbooks,
+            images,
+            images_rx,
+            size,
+            prelude_rx,
+            prelude,
+            watch_rx,
+            _watch_guard: watch_guard,
+            screen_format,
+            fallback_shader,
+            vertex_shader,
+            geometry: manifest.geometry,
+            screen_copy: screen_feedback.map(|target| ScreenCopy {
+                target,
+                blit: Blit::new(device, screen_format, FilterMode::Nearest),
+                bind_groups: Vec::new(),
+            }),
+            parity: 0,
+        };
+        graph.create_bind_groups(device);
+        Ok(graph)
+    }
+
+    fn create_bind_groups(&mut self, device: &Device) {
+        let resolutions: Vec<_> = self
+            .passes
+            .iter()
+            .map(|pass| self.resolutions(pass))
+            .collect();
+        for (pass, resolutions) in self.passes.iter_mut().zip(resolutions) {
+            let resolution_buffer = device.create_buffer(&BufferDescriptor {
+                label: Some(&format!("{} resolution buffer", pass.name)),
+                size: size_of_val(&resolutions) as u64,
+                usage: BufferUsages::UNIFORM,
+                mapped_at_creation: true,
+            });
+            resolution_buffer
+                .slice(..)
+                .get_mapped_range_mut()
+                .copy_from_slice(bytemuck::cast_slice(&resolutions));
+            resolution_buffer.unmap();
+
+            pass.bind_groups = (0..2)
+                .map(|parity| {
+                    let views = pass.inputs.map(|input| match input {
+                        Some(Input::Target { target, previous }) => self.targets[target].textures
+                            [parity ^ usize::from(previous)]
+                        .create_view(&Default::default()),
+                        Some(Input::Texture(texture)) => {
+                            self.textures[texture].create_view(&Default::default())
+                        }
+                        None => self.placeholder.clone(),
+                    });
+                    let views = views.iter().map(BindingResource::TextureView);
+                    let samplers = pass.samplers.iter().map(BindingResource::Sampler);
+                    let resolution =
+                        BindingResource::Buffer(resolution_buffer.as_entire_buffer_binding());
+                    let entries: Vec<_> = views
+                        .chain(samplers)
+                        .chain([resolution])
+                        .zip(0..)
+                        .map(|(resource, binding)| BindGroupEntry { binding, resource })
+                        .collect();
+                    device.create_bind_group(&BindGroupDescriptor {
+                        label: Some(&format!("{} channel bind group", pass.name)),
+                        layout: &pass.channel_layout,
+                        entries: &entries,
+                    })
+                })
+                .collect();
+        }
+        if let Some(copy) = &mut self.screen_copy {
+            copy.bind_groups = self.targets[copy.target]
+                .textures
+                .iter()
+                .map(|texture| {
+                    copy.blit
+                        .bind_group(device, &texture.create_view(&Default::default()))
+                })
+                .collect();
+        }
+    }
+
+    /// Resolution of `pass` followed by that of each of its channels, as
+    /// `[width, height, depth, 0]`. Unconnected channels are all zeros.
+    fn resolutions(&self, pass: &Pass) -> Resolutions {
+        let extent = |texture: &Texture| {
+            let size = texture.size();
+            [
+                size.width as f32,
+                size.height as f32,
+                size.depth_or_array_layers as f32,
+                0.0,
+            ]
+        };
+        let own = match pass.outputs.first() {
+            Some(&target) => extent(&self.targets[target].textures[0]),
+            None => [self.size.width as f32, self.size.height as f32, 1.0, 0.0],
+        };
+        let mut resolutions = [own; 1 + CHANNEL_COUNT];
+        for (resolution, input) in resolutions[1..].iter_mut().zip(pass.inputs) {
+            *resolution = match input {
+                Some(Input::Target { target, .. }) => extent(&self.targets[target].textures[0]),
+                Some(Input::Texture(texture)) => extent(&self.textures[texture]),
+                None => [0.0; 4],
+            };
+        }
+        resolutions
+    }
+
+    /// Recompiles every pass whose shader changed on disk, or all of them if
+    /// the prelude or a watched file changed. Returns the passes that were
+    /// reloaded.
+    #[tracing::instrument(skip_all)]
+    pub fn reload(&mut self, device: &Device, uniform_layout: &BindGroupLayout) -> Vec<Reloaded> {
+        let prelude_changed = match self.prelude_rx.as_ref().and_then(|rx| rx.try_iter().last()) {
+            Some(prelude) => {
+                tracing::info!("Prelude changed, recompiling all passes");
+                self.prelude = Some(prelude);
+                true
+            }
+            None => false,
+        };
+        let watched_changed = self
+            .watch_rx
+            .as_ref()
+            .is_some_and(|rx| rx.try_iter().count() > 0);
+        if watched_changed {
+            tracing::info!("Watched files changed, recompiling all passes");
+        }
+        let recompile_all = prelude_changed || watched_changed;
+
+        let mut reloaded = Vec::new();
+        for index in 0..self.passes.len() {
+            let pass = &mut self.passes[index];
+            if let Some(source) = pass.source_rx.try_iter().last() {
+                pass.source = Some(source);
+            } else if !recompile_all {
+                continue;
+            }
+            if let Some(pass) = self.compile(index, device, uniform_layout) {
+                tracing::info!("Shader `{}` reloaded", pass.shader.display());
+                reloaded.push(pass);
+            }
+        }
+        reloaded
+    }
+
+    /// Replaces every pass with the fallback shader until its shader is
+    /// reloaded or the prelude changes.
+    #[tracing::instrument(skip_all)]
+    pub fn use_fallback(&mut self, device: &Device, uniform_layout: &BindGroupLayout) {
+        for pass in &mut self.passes {
+            (pass.pipeline, _) = pipeline::create_pipeline(
+                device,
+                &pass.name,
+                &color_targets(&pass.outputs, self.screen_format, pass.blend),
+                Modules {
+                    vertex: &self.vertex_shader,
+                    fallback: &self.fallback_shader,
+                },
+                None,
+                &[uniform_layout, &pass.channel_layout],
+                None,
+            );
+            pass.compiled = false;
+            pass.fallback = true;
+            pass.bindings.clear();
+        }
+    }
+
+    /// Source of the pass drawn to the screen, if it compiled.
+    pub fn screen_compiled_source(&self) -> Option<&str> {
+        let pass = self.passes.last().expect("the screen pass runs last");
+        pass.source.as_deref().filter(|_| pass.compiled)
+    }
+
+    /// Name, shader file and whether the shader compiled, of every pass in
+    /// the order they run.
+    pub fn pass_status(&self) -> impl Iterator<Item = (&str, &Path, bool)> {
+        self.passes
+            .iter()
+            .map(|pass| (pass.name.as_str(), pass.shader.as_path(), pass.compiled))
+    }
+
+    /// Shader file and last loaded source of the pass drawn to the screen.
+    pub fn screen_source(&self) -> (&Path, Option<&str>) {
+        let pass = self.passes.last().expect("the screen pass runs last");
+        (&pass.shader, pass.source.as_deref())
+    }
+
+    /// Replaces the source of the pass drawn to the screen without going
+    /// through its file, and recompiles it.
+    pub fn set_screen_source(
+        &mut self,
+        device: &Device,
+        uniform_layout: &BindGroupLayout,
+        source: String,
+    ) -> Reloaded {
+        let index = self.passes.len() - 1;
+        self.passes[index].source = Some(source);
+        self.compile(index, device, uniform_layout)
+            .expect("the source was just set")
+    }
+
+    /// Rebuilds the pipeline of the pass at `index` from its last source.
+    /// Returns the outcome, or none if the pass has no source to compile.
+    fn compile(
+        &mut self,
+        index: usize,
+        device: &Device,
+        uniform_layout: &BindGroupLayout,
+    ) -> Option<Reloaded> {
+        let pass = &mut self.passes[index];
+        let source = pass.source.as_ref()?;
+        let fragment = preprocess::preprocess(self.prelude.as_deref(), source);
+        let (pipeline, error) = pipeline::create_pipeline(
+            device,
+            &pass.name,
+            &color_targets(&pass.outputs, self.screen_format, pass.blend),
+            Modules {
+                vertex: &self.vertex_shader,
+                fallback: &self.fallback_shader,
+            },
+            Some(&fragment),
+            &[uniform_layout, &pass.channel_layout],
+            pass.dump.as_ref(),
+        );
+        // A version that fails to compile leaves the last one that did
+        // running, with the error shown over it.
+        if error.is_none() || pass.fallback {
+            pass.pipeline = pipeline;
+            pass.fallback = error.is_some();
+            pass.bindings = reflect::used_bindings(&fragment.source).unwrap_or_default();
+        }
+        pass.compiled = error.is_none();
+        pass.error = error;
+        Some(pass.outcome())
+    }
+
+    /// Outcome of the last compile of every pass that has a source.
+    pub fn outcomes(&self) -> impl Iterator<Item = Reloaded> {
+        self.passes
+            .iter()
+            .filter(|pass| pass.source.is_some())
+            .map(Pass::outcome)
+    }
+
+    /// Name and compile error of every pass whose source failed to compile.
+    pub fn errors(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.passes.iter().filter_map(|pass| {
+            pass.error
+                .as_deref()
+                .map(|error| (pass.name.as_str(), error))
+        })
+    }
+
+    /// Whether any pass uses the resource at `binding` of `group`.
+    pub fn uses_binding(&self, group: u32, binding: u32) -> bool {
+        self.passes
+            .iter()
+            .any(|pass| pass.bindings.contains(&(group, binding)))
+    }
+
+    /// Whether any pass reads a previous frame, and so changes every frame.
+    pub fn has_feedback(&self) -> bool {
+        self.passes
+            .iter()
+            .flat_map(|pass| pass.inputs.iter().flatten())
+            .any(|input| matches!(input, Input::Target { previous: true, .. }))
+    }
+
+    /// Reloads the images after one of their files changed. An image that
+    /// fails to load keeps its previous contents. Returns whether any image
+    /// was reloaded.
+    #[tracing::instrument(skip_all)]
+    pub fn reload_images(&mut self, device: &Device, queue: &Queue) -> bool {
+        let Some(rx) = &self.images_rx else {
+            return false;
+        };
+        if rx.try_iter().last().is_none() {
+            return false;
+        }
+        let mut reloaded = false;
+        for image in &self.images {
+            match image_file::create_image(device, queue, &image.name, &image.manifest) {
+                Ok(texture) => {
+                    tracing::info!("Reloaded `{}`", image.manifest.path.display());
+                    self.textures[image.texture] = texture;
+                    reloaded = true;
+                }
+                Err(err) => tracing::warn!("Keeping the previous image: {err}"),
+            }
+        }
+        if reloaded {
+            self.create_bind_groups(device);
+        }
+        reloaded
+    }
+
+    /// Whether any pass reads a flipbook, and so changes over time.
+    pub fn has_flipbooks(&self) -> bool {
+        !self.flipbooks.is_empty()
+    }
+
+    /// Shows the frame of each flipbook due `time` seconds into the shader.
+    /// Returns whether any of them changed.
+    pub fn advance_flipbooks(&mut self, queue: &Queue, time: f64) -> bool {
+        self.flipbooks.iter_mut().fold(false, |changed, flipbook| {
+            flipbook.advance(queue, time) | changed
+        })
+    }
+
+    /// Recreates the offscreen targets that follow the screen resolution at
+    /// `size`, clearing their contents.
+    #[tracing::instrument(skip(self, device))]
+    pub fn resize(&mut self, device: &Device, size: PhysicalSize<u32>) {
+        self.size = size;
+        let max_size = device.limits().max_texture_dimension_2d;
+        for target in &mut self.targets {
+            if let TargetSize::Scaled(_) = target.size {
+                let size = target.size.resolve(size, max_size);
+                target.textures = [0, 1].map(|_| create_target_texture(device, &target.name, size));
+            }
+        }
+        self.create_bind_groups(device);
+    }
+
+    /// Copies the contents of `previous`'s offscreen targets into the
+    /// targets of the same name and size, so buffers survive the graph being
+    /// rebuilt around them. Returns the names of the targets copied.
+    pub fn copy_targets_from(
+        &mut self,
+        previous: &RenderGraph,
+        encoder: &mut CommandEncoder,
+    ) -> Vec<String> {
+        // Both frames are copied as they are, so the next frame reads the
+        // same texture as previous's would have.
+        self.parity = previous.parity;
+        let mut copied = Vec::new();
+        for target in &self.targets {
+            let Some(old) = previous
+                .targets
+                .iter()
+                .find(|old| old.name == target.name)
+                .filter(|old| old.textures[0].size() == target.textures[0].size())
+            else {
+                continue;
+            };
+            for (from, to) in old.textures.iter().zip(&target.textures) {
+                encoder.copy_texture_to_texture(
+                    from.as_image_copy(),
+                    to.as_image_copy(),
+                    from.size(),
+                );
+            }
+            copied.push(target.name.clone());
+        }
+        copied
+    }
+
+    /// Reads back what each offscreen target holds after the last frame, see
+    /// `--save-state`.
+    pub fn save_targets(
+        &self,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Vec<SavedTarget>, Box<dyn std::error::Error>> {
+        self.targets
+            .iter()
+            .map(|target| {
+                let texture = &target.textures[self.parity ^ 1];
+                Ok(SavedTarget {
+                    name: target.name.clone(),
+                    format: format!("{:?}", texture.format()),
+                    width: texture.width(),
+                    height: texture.height(),
+                    data: capture::read_texels(device, queue, texture)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Uploads saved contents into the offscreen targets of the same name,
+    /// as both their previous and current frame, see `--load-state`.
+    ///
+    /// Nothing is uploaded unless every saved target matches the size and
+    /// format of the graph's.
+    pub fn restore_targets(&self, queue: &Queue, saved: &[SavedTarget]) -> Result<(), String> {
+        let bytes_per_pixel = TARGET_FORMAT
+            .block_copy_size(None)
+            .expect("targets have an uncompressed format");
+        let mut uploads = Vec::with_capacity(saved.len());
+        for saved in saved {
+            let Some(target) = self.targets.iter().find(|target| target.name == saved.name) else {
+                return Err(format!("the manifest has no pass writing `{}`", saved.name));
+            };
+            let texture = &target.textures[0];
+            let format = format!("{:?}", texture.format());
+            if saved.format != format {
+                return Err(format!(
+                    "`{}` was saved as {}, expected {format}",
+                    saved.name, saved.format
+                ));
+            }
+            if (saved.width, saved.height) != (texture.width(), texture.height()) {
+                return Err(format!(
+                    "`{}` was saved at {}x{}, it's {}x{} now; set its `size` or `--internal` \
+                     to the saved resolution",
+                    saved.name,
+                    saved.width,
+                    saved.height,
+                    texture.width(),
+                    texture.height()
+                ));
+            }
+            if saved.data.len() != (saved.width * saved.height * bytes_per_pixel) as usize {
+                return Err(format!("the contents of `{}` are truncated", saved.name));
+            }
+            uploads.push((target, saved));
+        }
+        for target in &self.targets {
+            if !saved.iter().any(|saved| saved.name == target.name) {
+                tracing::warn!("The state has no contents for `{}`", target.name);
+            }
+        }
+        for (target, saved) in uploads {
+            for texture in &target.textures {
+                queue.write_texture(
+                    texture.as_image_copy(),
+                    &saved.data,
+                    TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(saved.width * bytes_per_pixel),
+                        rows_per_image: None,
+                    },
+                    texture.size(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes every pass, the last one drawing into `screen`.
+    #[tracing::instrument(skip_all)]
+    pub fn render(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        uniform_bind_group: &BindGroup,
+        screen: &TextureView,
+    ) {
+        for pass in &self.passes {
+            // Blended passes draw over their previous frame, which the
+            // other texture of each target pair holds.
+            if pass.blend.is_some() {
+                for &target in &pass.outputs {
+                    let [first, second] = &self.targets[target].textures;
+                    let (previous, current) = match self.parity {
+                        0 => (second, first),
+                        _ => (first, second),
+                    };
+                    encoder.copy_texture_to_texture(
+                        previous.as_image_copy(),
+                        current.as_image_copy(),
+                        previous.size(),
+                    );
+                }
+            }
+            let views: Vec<_> = pass
+                .outputs
+                .iter()
+                .map(|&target| {
+                    self.targets[target].textures[self.parity].create_view(&Default::default())
+                })
+                .collect();
+            let attachment = |view| {
+                Some(RenderPassColorAttachment {
+                    view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: match pass.blend {
+                            Some(_) => LoadOp::Load,
+                            None => LoadOp::default(),
+                        },
+                        store: StoreOp::Store,
+                    },
+                })
+            };
+            let color_attachments: Vec<_> = match views.as_slice() {
+                [] => vec![attachment(screen)],
+                views => views.iter().map(attachment).collect(),
+            };
+            // The viewport covers the attachments, so each pass renders at
+            // the size of its own targets.
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some(&pass.name),
+                color_attachments: &color_attachments,
+                ..Default::default()
+            });
+
+            render_pass.set_pipeline(&pass.pipeline);
+            render_pass.set_bind_group(0, uniform_bind_group, &[]);
+            render_pass.set_bind_group(1, &pass.bind_groups[self.parity], &[]);
+            render_pass.draw(0..self.geometry.vertex_count(), 0..1);
+        }
+        if let Some(copy) = &self.screen_copy {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("screen copy"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: screen,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                })],
+                ..Default::default()
+            });
+            copy.blit.draw(
+                &mut render_pass,
+                &copy.bind_groups[self.parity],
+                Viewport::letterbox(self.size.width, self.size.height, None),
+            );
+        }
+        self.parity ^= 1;
+    }
+}
+
+/// Attachments of a pass rendering into `outputs`, or to the screen if
+/// there are none.
+fn color_targets(
+    outputs: &[usize],
+    screen_format: TextureFormat,
+    blend: Option<Blend>,
+) -> Vec<Option<ColorTargetState>> {
+    match outputs.len() {
+        0 => vec![pipeline::color_target(screen_format, blend)],
+        count => vec![pipeline::color_target(TARGET_FORMAT, blend); count],
+    }
+}
+
+/// Layout of the channel textures of a pass, followed by their samplers and
+/// the resolutions of the pass and its channels.
+fn create_channel_layout(
+    device: &Device,
+    pass: &str,
+    dimensions: [TextureViewDimension; CHANNEL_COUNT],
+) -> BindGroupLayout {
+    let textures = dimensions.map(|view_dimension| BindingType::Texture {
+        sample_type: TextureSampleType::Float { filterable: true },
+        view_dimension,
+        multisampled: false,
+    });
+    let samplers = [BindingType::Sampler(SamplerBindingType::Filtering); CHANNEL_COUNT];
+    let resolution = BindingType::Buffer {
+        ty: BufferBindingType::Uniform,
+        has_dynamic_offset: false,
+        min_binding_size: None,
+    };
+    let entries: Vec<_> = textures
+        .into_iter()
+        .chain(samplers)
+        .chain([resolution])
+        .zip(0..)
+        .map(|(ty, binding)| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty,
+            count: None,
+        })
+        .collect();
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some(&format!("{pass} channel bind group layout")),
+        entries: &entries,
+    })
+}
+
+fn create_target_texture(device: &Device, name: &str, size: PhysicalSize<u32>) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: Some(name),
+        size: Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TARGET_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT
+            | TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_SRC
+            | TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Manifest {
+        Manifest::parse(source, Path::new("shaders/shadertoy.toml")).expect("the manifest parses")
+    }
+
+    /// Pass names in the order they're scheduled.
+    fn order(source: &str) -> Vec<String> {
+        let manifest = parse(source);
+        let order = manifest.schedule().expect("the graph is valid");
+        order
+            .into_iter()
+            .map(|index| manifest.passes[index].name.clone())
+            .collect()
+    }
+
+    fn schedule_error(source: &str) -> String {
+        parse(source).schedule().expect_err("the graph is invalid")
+    }
+
+    #[test]
+    fn parse_resolves_paths_relative_to_the_manifest() {
+        let manifest = parse(
+            r#"
+            prelude = "common.wgsl"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            output = "screen"
+            [image.logo]
+            path = "logo.png"
+            "#,
+        );
+        assert_eq!(manifest.passes[0].shader, Path::new("shaders/image.wgsl"));
+        assert_eq!(
+            manifest.prelude.as_deref(),
+            Some(Path::new("shaders/common.wgsl"))
+        );
+        assert_eq!(manifest.images["logo"].path, Path::new("shaders/logo.png"));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_fields() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            ouptut = "screen"
+        "#;
+        let err = Manifest::parse(source, Path::new("shadertoy.toml")).unwrap_err();
+        assert!(err.to_string().contains("ouptut"), "{err}");
+    }
+
+    #[test]
+    fn schedule_orders_dependencies_first() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            inputs = { iChannel0 = "b", iChannel1 = "noise-rgba-256" }
+            output = "screen"
+            [[pass]]
+            name = "b"
+            shader = "b.wgsl"
+            inputs = { iChannel0 = "a" }
+            [[pass]]
+            name = "a"
+            shader = "a.wgsl"
+            inputs = { iChannel0 = "a" }
+        "#;
+        assert_eq!(order(source), ["a", "b", "image"]);
+    }
+
+    #[test]
+    fn schedule_runs_the_screen_pass_last() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            output = "screen"
+            [[pass]]
+            name = "unused"
+            shader = "unused.wgsl"
+        "#;
+        assert_eq!(order(source), ["unused", "image"]);
+    }
+
+    #[test]
+    fn schedule_lets_the_screen_pass_read_its_previous_frame() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            inputs = { iChannel0 = "image" }
+            output = "screen"
+        "#;
+        assert_eq!(order(source), ["image"]);
+    }
+
+    #[test]
+    fn schedule_rejects_reading_the_screen() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            inputs = { iChannel0 = "screen" }
+            output = "screen"
+        "#;
+        assert_eq!(schedule_error(source), "pass `image` can't read the screen");
+    }
+
+    #[test]
+    fn schedule_rejects_cycles() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            inputs = { iChannel0 = "a" }
+            output = "screen"
+            [[pass]]
+            name = "a"
+            shader = "a.wgsl"
+            inputs = { iChannel0 = "b" }
+            [[pass]]
+            name = "b"
+            shader = "b.wgsl"
+            inputs = { iChannel0 = "a" }
+        "#;
+        assert_eq!(
+            schedule_error(source),
+            "pass `a` depends on itself through other passes"
+        );
+    }
+
+    #[test]
+    fn schedule_rejects_duplicate_pass_names() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            output = "screen"
+            [[pass]]
+            name = "image"
+            shader = "other.wgsl"
+            output = "other"
+        "#;
+        assert_eq!(schedule_error(source), "duplicate pass name `image`");
+    }
+
+    #[test]
+    fn schedule_rejects_unknown_channels() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            inputs = { iChannel4 = "noise-rgba-256" }
+            output = "screen"
+        "#;
+        assert_eq!(
+            schedule_error(source),
+            "pass `image` has unknown channel `iChannel4`, expected `iChannel0`..`iChannel3`"
+        );
+    }
+
+    #[test]
+    fn schedule_rejects_unknown_outputs() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            inputs = { iChannel0 = "missing" }
+            output = "screen"
+        "#;
+        let err = schedule_error(source);
+        assert!(
+            err.starts_with("pass `image` reads unknown output `missing`"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn schedule_requires_one_screen_pass() {
+        let source = r#"
+            [[pass]]
+            name = "a"
+            shader = "a.wgsl"
+        "#;
+        assert_eq!(schedule_error(source), "no pass outputs to `screen`");
+
+        let source = r#"
+            [[pass]]
+            name = "a"
+            shader = "a.wgsl"
+            output = "screen"
+            [[pass]]
+            name = "b"
+            shader = "b.wgsl"
+            output = "screen"
+        "#;
+        assert_eq!(schedule_error(source), "multiple passes output to `screen`");
+    }
+
+    #[test]
+    fn outputs_default_to_the_pass_name() {
+        let manifest = parse(
+            r#"
+            [[pass]]
+            name = "a"
+            shader = "a.wgsl"
+            [[pass]]
+            name = "b"
+            shader = "b.wgsl"
+            outputs = ["albedo", "normal"]
+            "#,
+        );
+        assert_eq!(manifest.passes[0].output(), "a");
+        assert_eq!(manifest.passes[0].outputs(), ["a"]);
+        assert_eq!(manifest.passes[1].output(), "albedo");
+        assert_eq!(manifest.passes[1].outputs(), ["albedo", "normal"]);
+    }
+
+    #[test]
+    fn schedule_rejects_conflicting_output_names() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            output = "screen"
+            outputs = ["a"]
+        "#;
+        assert_eq!(
+            schedule_error(source),
+            "pass `image` sets both `output` and `outputs`"
+        );
+
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            output = "noise-rgba-64"
+        "#;
+        assert_eq!(
+            schedule_error(source),
+            "pass `image` outputs to `noise-rgba-64`, the name of a built-in texture"
+        );
+    }
+
+    #[test]
+    fn target_size_resolves_against_the_screen() {
+        let screen = PhysicalSize::new(1920, 1080);
+        let fixed = PhysicalSize::new(256, 128);
+        assert_eq!(TargetSize::Fixed(fixed).resolve(screen, 8192), fixed);
+        assert_eq!(TargetSize::Scaled(1.0).resolve(screen, 8192), screen);
+        assert_eq!(
+            TargetSize::Scaled(0.5).resolve(screen, 8192),
+            PhysicalSize::new(960, 540)
+        );
+        // Sides round to the nearest pixel and keep at least one.
+        assert_eq!(
+            TargetSize::Scaled(0.3).resolve(PhysicalSize::new(5, 1), 8192),
+            PhysicalSize::new(2, 1)
+        );
+    }
+
+    #[test]
+    fn target_size_clamps_to_the_texture_limit() {
+        let screen = PhysicalSize::new(1920, 1080);
+        assert_eq!(
+            TargetSize::Scaled(4.0).resolve(screen, 4096),
+            PhysicalSize::new(4096, 4096)
+        );
+        assert_eq!(
+            TargetSize::Scaled(2.0).resolve(screen, 2048),
+            PhysicalSize::new(2048, 2048)
+        );
+    }
+
+    #[test]
+    fn target_size_follows_the_manifest() {
+        let manifest = parse(
+            r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            output = "screen"
+            [[pass]]
+            name = "fixed"
+            shader = "fixed.wgsl"
+            size = [256, 128]
+            [[pass]]
+            name = "half"
+            shader = "half.wgsl"
+            scale = 0.5
+            "#,
+        );
+        let screen = PhysicalSize::new(800, 600);
+        let sizes: Vec<_> = manifest
+            .passes
+            .iter()
+            .map(|pass| pass.target_size().resolve(screen, 8192))
+            .collect();
+        assert_eq!(
+            sizes,
+            [
+                screen,
+                PhysicalSize::new(256, 128),
+                PhysicalSize::new(400, 300)
+            ]
+        );
+    }
+
+    #[test]
+    fn schedule_rejects_invalid_target_sizes() {
+        let pass = |size: &str| {
+            format!(
+                r#"
+                [[pass]]
+                name = "image"
+                shader = "image.wgsl"
+                output = "screen"
+                [[pass]]
+                name = "a"
+                shader = "a.wgsl"
+                {size}
+                "#
+            )
+        };
+        for scale in ["0.0", "-0.5", "nan", "inf"] {
+            assert_eq!(
+                schedule_error(&pass(&format!("scale = {scale}"))),
+                "pass `a` has a `scale` that isn't a positive number",
+                "scale = {scale}"
+            );
+        }
+        assert_eq!(
+            schedule_error(&pass("size = [0, 128]")),
+            "pass `a` has an empty `size`"
+        );
+        assert_eq!(
+            schedule_error(&pass("size = [256, 128]\nscale = 0.5")),
+            "pass `a` sets both `size` and `scale`"
+        );
+
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            output = "screen"
+            scale = 0.5
+        "#;
+        assert_eq!(
+            schedule_error(source),
+            "pass `image` renders to `screen`, which can't have its own size"
+        );
+    }
+
+    /// A screen pass reading the pass `a`, which renders to `outputs`.
+    fn with_outputs(outputs: &[&str]) -> String {
+        format!(
+            r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            inputs = {{ iChannel0 = "{}" }}
+            output = "screen"
+            [[pass]]
+            name = "a"
+            shader = "a.wgsl"
+            outputs = {outputs:?}
+            "#,
+            outputs[0]
+        )
+    }
+
+    #[test]
+    fn check_limits_caps_the_outputs_of_a_pass() {
+        // 32 bytes per sample fit four `rgba16float` targets.
+        let limits = Limits::default();
+        assert_eq!(limits.max_color_attachment_bytes_per_sample, 32);
+
+        let four = parse(&with_outputs(&["a0", "a1", "a2", "a3"]));
+        assert_eq!(four.schedule().map(|order| order.len()), Ok(2));
+        assert_eq!(four.check_limits(&limits), Ok(()));
+
+        let five = parse(&with_outputs(&["a0", "a1", "a2", "a3", "a4"]));
+        assert_eq!(five.schedule().map(|order| order.len()), Ok(2));
+        assert_eq!(
+            five.check_limits(&limits),
+            Err("Pass `a` has 5 outputs, the device can render to at most 4".to_owned())
+        );
+
+        let attachments = Limits {
+            max_color_attachments: 2,
+            ..Limits::default()
+        };
+        assert_eq!(
+            four.check_limits(&attachments),
+            Err("Pass `a` has 4 outputs, the device can render to at most 2".to_owned())
+        );
+    }
+
+    #[test]
+    fn check_limits_caps_fixed_sizes() {
+        let manifest = parse(
+            r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            output = "screen"
+            [[pass]]
+            name = "a"
+            shader = "a.wgsl"
+            size = [4096, 16]
+            "#,
+        );
+        let limits = Limits {
+            max_texture_dimension_2d: 2048,
+            ..Limits::default()
+        };
+        assert_eq!(
+            manifest.check_limits(&limits),
+            Err("Pass `a` is 4096x16, the device supports textures up to 2048x2048".to_owned())
+        );
+        assert_eq!(manifest.check_limits(&Limits::default()), Ok(()));
+    }
+
+    #[test]
+    fn schedule_rejects_colliding_outputs() {
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            inputs = { iChannel0 = "normal" }
+            output = "screen"
+            [[pass]]
+            name = "a"
+            shader = "a.wgsl"
+            outputs = ["albedo", "normal"]
+            [[pass]]
+            name = "b"
+            shader = "b.wgsl"
+            outputs = ["normal", "depth"]
+        "#;
+        assert_eq!(schedule_error(source), "multiple passes output to `normal`");
+
+        // A pass without `output` writes to its name.
+        let source = r#"
+            [[pass]]
+            name = "image"
+            shader = "image.wgsl"
+            output = "screen"
+            [[pass]]
+            name = "albedo"
+            shader = "albedo.wgsl"
+            [[pass]]
+            name = "a"
+            shader = "a.wgsl"
+            outputs = ["albedo", "normal"]
+        "#;
+        assert_eq!(schedule_error(source), "multiple passes output to `albedo`");
+
+        assert_eq!(
+            schedule_error(&with_outputs(&["a0", "a0"])),
+            "multiple passes output to `a0`"
+        );
+        assert_eq!(
+            schedule_error(&with_outputs(&["a0", "screen"])),
+            "pass `a` can't render to `screen` alongside other outputs"
+        );
+    }
+
+    #[test]
+    fn channel_index_accepts_the_channel_range() {
+        assert_eq!(channel_index("iChannel0"), Some(0));
+        assert_eq!(channel_index("iChannel3"), Some(3));
+        assert_eq!(channel_index("iChannel4"), None);
+        assert_eq!(channel_index("iChannel"), None);
+        assert_eq!(channel_index("iChannel-1"), None);
+        assert_eq!(channel_index("channel0"), None);
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::VecDeque;
+
+/// Number of sources kept, after which the oldest is dropped.
+const CAPACITY: usize = 32;
+
+/// Recently compiled versions of a shader, oldest first, which can be
+/// stepped through without touching the file.
+#[derive(Debug, Default)]
+pub struct History {
+    sources: VecDeque<String>,
+    /// Index of the version shown while browsing, or `None` when showing
+    /// the latest one.
+    position: Option<usize>,
+}
+
+impl History {
+    /// Records a newly compiled `source` and stops browsing.
+    pub fn push(&mut self, source: &str) {
+        self.position = None;
+        if self.sources.back().is_some_and(|last| last == source) {
+            return;
+        }
+        if self.sources.len() == CAPACITY {
+            self.sources.pop_front();
+        }
+        self.sources.push_back(source.to_owned());
+    }
+
+    /// Steps to the previous version, if there is one.
+    pub fn back(&mut self) -> Option<&str> {
+        let current = self.position.or(self.sources.len().checked_sub(1))?;
+        let previous = current.checked_sub(1)?;
+        self.position = Some(previous);
+        Some(&self.sources[previous])
+    }
+
+    /// Steps to the next version while browsing.
+    pub fn forward(&mut self) -> Option<&str> {
+        let next = self.position? + 1;
+        self.position = Some(next).filter(|&next| next + 1 < self.sources.len());
+        Some(&self.sources[next])
+    }
+
+    /// One-based index of the version shown and the number of versions.
+    pub fn status(&self) -> (usize, usize) {
+        let len = self.sources.len();
+        (self.position.map_or(len, |position| position + 1), len)
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use wgpu::Device;
+
+use crate::overlay::{Color, GLYPH_SIZE, Overlay, Theme};
+
+/// How often the GPU memory usage is re-queried.
+const MEMORY_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+const SCALE: u32 = 2;
+const PADDING: f32 = 6.0;
+const MARGIN: f32 = 10.0;
+/// Color of the line naming the pass a compile error is from.
+const ERROR_HEADER: Color = [1.0, 0.4, 0.4, 1.0];
+
+/// Heads-up display with renderer statistics, drawn in the top right corner.
+#[derive(Debug, Default)]
+pub struct Hud {
+    pub visible: bool,
+    /// Bytes allocated by the device, if the backend reports it.
+    gpu_memory: Option<u64>,
+    last_memory_query: Option<Instant>,
+}
+
+impl Hud {
+    /// Re-queries the device's allocator, at most once per
+    /// [`MEMORY_QUERY_INTERVAL`].
+    #[tracing::instrument(skip_all)]
+    pub fn update_gpu_memory(&mut self, device: &Device) {
+        let now = Instant::now();
+        if self
+            .last_memory_query
+            .is_some_and(|last| now - last < MEMORY_QUERY_INTERVAL)
+        {
+            return;
+        }
+        self.last_memory_query = Some(now);
+        self.gpu_memory = device
+            .generate_allocator_report()
+            .map(|report| report.total_allocated_bytes);
+        tracing::trace!(gpu_memory = ?self.gpu_memory, "Queried GPU memory");
+    }
+
+    /// Human-readable GPU memory usage, or `n/a` if the backend doesn't
+    /// report it.
+    pub fn gpu_memory(&self) -> String {
+        self.gpu_memory.map_or_else(
+            || "n/a".to_owned(),
+            |bytes| format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0)),
+        )
+    }
+
+    /// Queues `lines` on a translucent panel in the top right corner of a
+    /// surface `screen_width` pixels wide.
+    pub fn draw(&self, overlay: &mut Overlay, theme: Theme, screen_width: f32, lines: &[String]) {
+        let text = lines.join("\n");
+        let [width, height] = Overlay::text_size(SCALE, &text);
+        let x = screen_width - width - MARGIN - PADDING;
+        let y = MARGIN + PADDING;
+        overlay.rect(
+            x - PADDING,
+            y - PADDING,
+            width + 2.0 * PADDING,
+            height + 2.0 * PADDING,
+            theme.background(),
+        );
+        overlay.text(x, y, SCALE, theme.text(), &text);
+    }
+}
+
+/// Queues a panel along the bottom of a `screen`-sized surface with the
+/// compile error of each pass in `errors`. Long lines are wrapped, and the
+/// panel covers at most half the surface.
+pub fn draw_errors(overlay: &mut Overlay, theme: Theme, screen: [f32; 2], errors: &[(&str, &str)]) {
+    let glyph = (GLYPH_SIZE * SCALE) as f32;
+    let [width, height] = screen;
+    let columns = (((width - 2.0 * (MARGIN + PADDING)) / glyph) as usize).max(1);
+    let max_rows = ((height / 2.0 / glyph) as usize).max(1);
+
+    // Rows naming the pass are flagged, so they can be colored differently.
+    let mut rows: Vec<(bool, String)> = Vec::new();
+    for (pass, error) in errors {
+        rows.push((true, format!("Pass `{pass}` failed to compile:")));
+        let error = to_ascii(error);
+        for line in error.lines().filter(|line| !line.trim().is_empty()) {
+            let chars: Vec<char> = line.chars().collect();
+            rows.extend(
+                chars
+                    .chunks(columns)
+                    .map(|chunk| (false, chunk.iter().collect())),
+            );
+        }
+    }
+    if rows.len() > max_rows {
+        rows.truncate(max_rows - 1);
+        rows.push((false, "...".to_owned()));
+    }
+
+    let panel_height = rows.len() as f32 * glyph + 2.0 * PADDING;
+    let y = height - MARGIN - panel_height;
+    overlay.rect(
+        MARGIN,
+        y,
+        width - 2.0 * MARGIN,
+        panel_height,
+        theme.background(),
+    );
+    for (row, (header, text)) in rows.iter().enumerate() {
+        let color = if *header { ERROR_HEADER } else { theme.text() };
+        let row_y = y + PADDING + row as f32 * glyph;
+        overlay.text(MARGIN + PADDING, row_y, SCALE, color, text);
+    }
+}
+
+/// Replaces the box-drawing characters of naga's diagnostics, which the
+/// overlay font doesn't have, with ASCII ones.
+fn to_ascii(text: &str) -> String {
+    text.chars()
+        .map(|ch| match ch {
+            '─' | '━' => '-',
+            '│' | '┃' => '|',
+            '┌' | '└' | '├' | '┬' | '┴' | '┼' | '╭' | '╰' => '+',
+            ch => ch,
+        })
+        .collect()
+}
@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+
+use image::{
+    ImageReader,
+    imageops::{self, FilterType},
+};
+use serde::Deserialize;
+use wgpu::{
+    Device, Extent3d, Origin3d, Queue, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+
+/// PNG or JPEG file declared in the manifest, which passes can read by name.
+///
+/// ```toml
+/// [image.wood]
+/// path = "wood.png"
+/// srgb = true
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImageManifest {
+    pub path: PathBuf,
+    /// Whether the texels are sRGB colors, which are converted to linear
+    /// when sampled. Off by default like on Shadertoy, so shaders read the
+    /// stored values, e.g. of normal or height maps.
+    #[serde(default)]
+    pub srgb: bool,
+    /// Whether to generate mipmaps, so the image doesn't shimmer when it's
+    /// sampled at a smaller size.
+    #[serde(default = "default_mipmaps")]
+    pub mipmaps: bool,
+}
+
+fn default_mipmaps() -> bool {
+    true
+}
+
+/// Reads the image `name` and uploads it to a new texture, with a chain of
+/// mip levels down to 1x1 unless they're turned off.
+#[tracing::instrument(skip(device, queue, image))]
+pub fn create_image(
+    device: &Device,
+    queue: &Queue,
+    name: &str,
+    image: &ImageManifest,
+) -> Result<Texture, String> {
+    let error = |err: String| format!("image `{name}`: {err}");
+    let path = &image.path;
+    let mut level = ImageReader::open(path)
+        .map_err(|err| err.to_string())
+        .and_then(|reader| reader.decode().map_err(|err| err.to_string()))
+        .map_err(|err| error(format!("can't read `{}`: {err}", path.display())))?
+        .into_rgba8();
+    let (width, height) = level.dimensions();
+    let max = device.limits().max_texture_dimension_2d;
+    if width.max(height) > max {
+        return Err(error(format!(
+            "`{}` is {width}x{height}, the device supports textures up to {max}x{max}",
+            path.display()
+        )));
+    }
+
+    let mip_level_count = if image.mipmaps {
+        width.max(height).ilog2() + 1
+    } else {
+        1
+    };
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some(name),
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: if image.srgb {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        },
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    for mip_level in 0..mip_level_count {
+        // Each level halves the previous one, rounding down, which matches
+        // the sizes wgpu expects.
+        if mip_level > 0 {
+            let size = |side: u32| (side / 2).max(1);
+            level = imageops::resize(
+                &level,
+                size(level.width()),
+                size(level.height()),
+                FilterType::Triangle,
+            );
+        }
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &level,
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(level.width() * 4),
+                rows_per_image: None,
+            },
+            Extent3d {
+                width: level.width(),
+                height: level.height(),
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+    tracing::debug!(
+        "Loaded {width}x{height} with {mip_level_count} mip levels from `{}`",
+        path.display()
+    );
+    Ok(texture)
+}
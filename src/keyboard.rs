@@ -0,0 +1,209 @@
+use wgpu::{
+    Device, Extent3d, Origin3d, Queue, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+use winit::{event::ElementState, keyboard::KeyCode};
+
+/// Number of key codes, i.e. the width of the keyboard texture.
+const KEY_COUNT: usize = 256;
+/// Texture rows, as in Shadertoy: whether the key is held, whether it was
+/// pressed since the last frame, and whether it's toggled on.
+const ROWS: usize = 3;
+const HELD: usize = 0;
+const PRESSED: usize = 1;
+const TOGGLED: usize = 2;
+
+/// Keyboard state uploaded to a `256x3` texture indexed by JS key code, in
+/// the layout Shadertoy uses for its keyboard input.
+#[derive(Debug)]
+pub struct Keyboard {
+    texture: Texture,
+    state: [u8; KEY_COUNT * ROWS],
+    dirty: bool,
+}
+
+impl Keyboard {
+    pub fn new(device: &Device) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("keyboard texture"),
+            size: Extent3d {
+                width: KEY_COUNT as u32,
+                height: ROWS as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        Self {
+            texture,
+            state: [0; KEY_COUNT * ROWS],
+            dirty: true,
+        }
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Records a key event. Keys without a JS key code are ignored.
+    pub fn key(&mut self, code: KeyCode, state: ElementState, repeat: bool) {
+        let Some(index) = js_key_code(code) else {
+            return;
+        };
+        let index = usize::from(index);
+        match state {
+            ElementState::Pressed if !repeat => {
+                self.state[HELD * KEY_COUNT + index] = 255;
+                self.state[PRESSED * KEY_COUNT + index] = 255;
+                self.state[TOGGLED * KEY_COUNT + index] ^= 255;
+            }
+            ElementState::Pressed => return,
+            ElementState::Released => self.state[HELD * KEY_COUNT + index] = 0,
+        }
+        self.dirty = true;
+    }
+
+    /// Uploads the state if it changed, then clears the pressed row so
+    /// presses only show up for one frame.
+    pub fn upload(&mut self, queue: &Queue) {
+        if !std::mem::take(&mut self.dirty) {
+            return;
+        }
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &self.state,
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(KEY_COUNT as u32),
+                rows_per_image: None,
+            },
+            self.texture.size(),
+        );
+        let pressed = &mut self.state[PRESSED * KEY_COUNT..][..KEY_COUNT];
+        if pressed.iter().any(|&key| key != 0) {
+            pressed.fill(0);
+            self.dirty = true;
+        }
+    }
+}
+
+/// Maps a physical key to the JS `keyCode` Shadertoy uses to index its
+/// keyboard texture, so ported shaders read the right keys.
+///
+/// Keys with no `keyCode` equivalent, such as media keys, `IntlBackslash`,
+/// `Fn` and function keys past `F12`, have no mapping.
+pub fn js_key_code(code: KeyCode) -> Option<u8> {
+    use KeyCode::*;
+
+    Some(match code {
+        Backspace => 8,
+        Tab => 9,
+        Enter | NumpadEnter => 13,
+        ShiftLeft | ShiftRight => 16,
+        ControlLeft | ControlRight => 17,
+        AltLeft | AltRight => 18,
+        Pause => 19,
+        CapsLock => 20,
+        Escape => 27,
+        Space => 32,
+        PageUp => 33,
+        PageDown => 34,
+        End => 35,
+        Home => 36,
+        ArrowLeft => 37,
+        ArrowUp => 38,
+        ArrowRight => 39,
+        ArrowDown => 40,
+        Insert => 45,
+        Delete => 46,
+        Digit0 => 48,
+        Digit1 => 49,
+        Digit2 => 50,
+        Digit3 => 51,
+        Digit4 => 52,
+        Digit5 => 53,
+        Digit6 => 54,
+        Digit7 => 55,
+        Digit8 => 56,
+        Digit9 => 57,
+        KeyA => 65,
+        KeyB => 66,
+        KeyC => 67,
+        KeyD => 68,
+        KeyE => 69,
+        KeyF => 70,
+        KeyG => 71,
+        KeyH => 72,
+        KeyI => 73,
+        KeyJ => 74,
+        KeyK => 75,
+        KeyL => 76,
+        KeyM => 77,
+        KeyN => 78,
+        KeyO => 79,
+        KeyP => 80,
+        KeyQ => 81,
+        KeyR => 82,
+        KeyS => 83,
+        KeyT => 84,
+        KeyU => 85,
+        KeyV => 86,
+        KeyW => 87,
+        KeyX => 88,
+        KeyY => 89,
+        KeyZ => 90,
+        SuperLeft => 91,
+        SuperRight => 92,
+        ContextMenu => 93,
+        Numpad0 => 96,
+        Numpad1 => 97,
+        Numpad2 => 98,
+        Numpad3 => 99,
+        Numpad4 => 100,
+        Numpad5 => 101,
+        Numpad6 => 102,
+        Numpad7 => 103,
+        Numpad8 => 104,
+        Numpad9 => 105,
+        NumpadMultiply => 106,
+        NumpadAdd => 107,
+        NumpadSubtract => 109,
+        NumpadDecimal => 110,
+        NumpadDivide => 111,
+        F1 => 112,
+        F2 => 113,
+        F3 => 114,
+        F4 => 115,
+        F5 => 116,
+        F6 => 117,
+        F7 => 118,
+        F8 => 119,
+        F9 => 120,
+        F10 => 121,
+        F11 => 122,
+        F12 => 123,
+        NumLock => 144,
+        ScrollLock => 145,
+        Semicolon => 186,
+        Equal => 187,
+        Comma => 188,
+        Minus => 189,
+        Period => 190,
+        Slash => 191,
+        Backquote => 192,
+        BracketLeft => 219,
+        Backslash => 220,
+        BracketRight => 221,
+        Quote => 222,
+        _ => return None,
+    })
+}
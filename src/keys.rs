@@ -0,0 +1,258 @@
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::overlay::{Overlay, Theme};
+
+const SCALE: u32 = 2;
+const PADDING: f32 = 12.0;
+/// Space between the keys column and the descriptions, in characters.
+const GAP: usize = 2;
+
+/// Something a shortcut does, see [`BINDINGS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    CopyFrame,
+    SaveScreenshot,
+    RerollSeed,
+    TogglePause,
+    StepForward,
+    StepBack,
+    ScrubBack,
+    ScrubForward,
+    PreviousVersion,
+    NextVersion,
+    ToggleHelp,
+    ToggleHud,
+    ToggleFrameGraph,
+    OpenEditor,
+    PrintStatus,
+    LowerBrightness,
+    RaiseBrightness,
+    GrabCursor,
+    ReleaseCursor,
+    SaveState,
+    ResetScroll,
+}
+
+/// A key, with the modifiers that have to be held, bound to a [`Command`].
+#[derive(Debug)]
+pub struct Binding {
+    pub key: KeyCode,
+    pub modifiers: ModifiersState,
+    /// How the key is written in the help screen.
+    pub label: &'static str,
+    pub command: Command,
+    pub description: &'static str,
+}
+
+const fn bind(
+    key: KeyCode,
+    modifiers: ModifiersState,
+    label: &'static str,
+    command: Command,
+    description: &'static str,
+) -> Binding {
+    Binding {
+        key,
+        modifiers,
+        label,
+        command,
+        description,
+    }
+}
+
+const NONE: ModifiersState = ModifiersState::empty();
+
+/// Every shortcut of the window, in the order the help screen lists them.
+/// Keys are matched by position, so `?` is `Shift+/` on a US layout.
+pub const BINDINGS: &[Binding] = &[
+    bind(
+        KeyCode::F1,
+        NONE,
+        "F1",
+        Command::ToggleHelp,
+        "Show or hide this help",
+    ),
+    bind(
+        KeyCode::Slash,
+        ModifiersState::SHIFT,
+        "?",
+        Command::ToggleHelp,
+        "Show or hide this help",
+    ),
+    bind(
+        KeyCode::KeyC,
+        ModifiersState::CONTROL,
+        "Ctrl+C",
+        Command::CopyFrame,
+        "Copy the current frame to the clipboard",
+    ),
+    bind(
+        KeyCode::F12,
+        NONE,
+        "F12",
+        Command::SaveScreenshot,
+        "Save the current frame as a PNG",
+    ),
+    bind(
+        KeyCode::KeyR,
+        NONE,
+        "R",
+        Command::RerollSeed,
+        "Re-roll the random seed",
+    ),
+    bind(
+        KeyCode::Space,
+        NONE,
+        "Space",
+        Command::TogglePause,
+        "Pause or resume time",
+    ),
+    bind(
+        KeyCode::Period,
+        NONE,
+        ".",
+        Command::StepForward,
+        "Pause and step one frame forward",
+    ),
+    bind(
+        KeyCode::Comma,
+        NONE,
+        ",",
+        Command::StepBack,
+        "Pause and step one frame back",
+    ),
+    bind(
+        KeyCode::ArrowLeft,
+        NONE,
+        "Left",
+        Command::ScrubBack,
+        "Move time back 0.25s, or 2s with Shift",
+    ),
+    bind(
+        KeyCode::ArrowRight,
+        NONE,
+        "Right",
+        Command::ScrubForward,
+        "Move time forward 0.25s, or 2s with Shift",
+    ),
+    bind(
+        KeyCode::PageUp,
+        NONE,
+        "PgUp",
+        Command::PreviousVersion,
+        "Show the previous compiled version",
+    ),
+    bind(
+        KeyCode::PageDown,
+        NONE,
+        "PgDn",
+        Command::NextVersion,
+        "Show the next compiled version",
+    ),
+    bind(
+        KeyCode::F2,
+        NONE,
+        "F2",
+        Command::ToggleHud,
+        "Toggle the HUD",
+    ),
+    bind(
+        KeyCode::F3,
+        NONE,
+        "F3",
+        Command::ToggleFrameGraph,
+        "Toggle the frame time graph",
+    ),
+    bind(
+        KeyCode::F4,
+        NONE,
+        "F4",
+        Command::OpenEditor,
+        "Open the shader editor",
+    ),
+    bind(
+        KeyCode::F6,
+        NONE,
+        "F6",
+        Command::PrintStatus,
+        "Print the shader status to stdout",
+    ),
+    bind(
+        KeyCode::F7,
+        NONE,
+        "F7",
+        Command::LowerBrightness,
+        "Lower the brightness, see `--adjust`",
+    ),
+    bind(
+        KeyCode::F8,
+        NONE,
+        "F8",
+        Command::RaiseBrightness,
+        "Raise the brightness, see `--adjust`",
+    ),
+    bind(
+        KeyCode::F9,
+        NONE,
+        "F9",
+        Command::GrabCursor,
+        "Capture or release the cursor",
+    ),
+    bind(
+        KeyCode::Escape,
+        NONE,
+        "Esc",
+        Command::ReleaseCursor,
+        "Release the cursor",
+    ),
+    bind(
+        KeyCode::F10,
+        NONE,
+        "F10",
+        Command::SaveState,
+        "Save the state, see `--save-state`",
+    ),
+    bind(
+        KeyCode::Home,
+        NONE,
+        "Home",
+        Command::ResetScroll,
+        "Reset the scroll uniform",
+    ),
+];
+
+/// The command bound to `key` while `modifiers` are held. Modifiers a
+/// binding doesn't ask for are ignored, so `Shift+F7` still runs `F7`.
+pub fn command(key: KeyCode, modifiers: ModifiersState) -> Option<Command> {
+    BINDINGS
+        .iter()
+        .find(|binding| binding.key == key && modifiers.contains(binding.modifiers))
+        .map(|binding| binding.command)
+}
+
+/// Queues a panel listing [`BINDINGS`] in the middle of a surface of
+/// `screen` pixels.
+pub fn draw_help(overlay: &mut Overlay, theme: Theme, screen: [f32; 2]) {
+    let width = BINDINGS
+        .iter()
+        .map(|binding| binding.label.len())
+        .max()
+        .unwrap_or_default()
+        + GAP;
+    let text = BINDINGS
+        .iter()
+        .map(|binding| format!("{:width$}{}", binding.label, binding.description))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let [text_width, text_height] = Overlay::text_size(SCALE, &text);
+    let x = ((screen[0] - text_width) / 2.0).max(PADDING);
+    let y = ((screen[1] - text_height) / 2.0).max(PADDING);
+    overlay.rect(
+        x - PADDING,
+        y - PADDING,
+        text_width + 2.0 * PADDING,
+        text_height + 2.0 * PADDING,
+        theme.background(),
+    );
+    overlay.text(x, y, SCALE, theme.text(), &text);
+}
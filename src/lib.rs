@@ -0,0 +1,2511 @@
+mod accumulate;
+mod bench;
+mod blit;
+mod capture;
+mod cli;
+mod clock;
+mod config;
+mod device;
+mod dump;
+mod editor;
+mod events;
+mod export;
+mod flipbook;
+mod graph;
+mod history;
+mod hud;
+mod image_file;
+mod keyboard;
+mod keys;
+mod overlay;
+mod pacing;
+mod pipeline;
+mod preprocess;
+mod record;
+mod reflect;
+mod renderer;
+mod sampler;
+mod socket;
+mod state;
+mod stats;
+mod textures;
+mod uniforms;
+mod validate;
+mod volume;
+mod watcher;
+
+pub use crate::{cli::Args, device::GpuInfo, events::Event, graph::Reloaded, renderer::Renderer};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde_json::json;
+use wgpu::{
+    Adapter, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+    BufferBinding, BufferBindingType, BufferDescriptor, BufferUsages, CommandEncoderDescriptor,
+    CompositeAlphaMode, Device, DeviceLostReason, DownlevelFlags, Extent3d, LoadOp, Operations,
+    PollError, PollType, PresentMode, Queue, RenderPassColorAttachment, RenderPassDescriptor,
+    SamplerBindingType, ShaderStages, StoreOp, Surface, SurfaceConfiguration, SurfaceError,
+    Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureView, TextureViewDescriptor, TextureViewDimension,
+};
+use winit::{
+    application::ApplicationHandler,
+    dpi::{PhysicalPosition, PhysicalSize},
+    error::OsError,
+    event::{
+        DeviceEvent, DeviceId, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent,
+    },
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy},
+    keyboard::{ModifiersState, PhysicalKey},
+    window::{CursorGrabMode, Icon, Window, WindowId},
+};
+
+use crate::{
+    accumulate::Accumulator,
+    bench::{FrameLimit, PresentModeBench},
+    blit::{Adjustment, Blit, Viewport},
+    capture::Encoding,
+    cli::AlphaMode,
+    clock::Clock,
+    dump::Dump,
+    editor::{Action, Editor},
+    events::Events,
+    flipbook::FlipbookManifest,
+    graph::{MANIFEST_FILE, Manifest, RenderGraph},
+    history::History,
+    hud::Hud,
+    keyboard::Keyboard,
+    keys::Command,
+    overlay::{Overlay, Theme},
+    pacing::{FrameCap, FramePacer, Throttle},
+    socket::{Message, Request, Uniform},
+    state::SavedState,
+    stats::{FrameTimes, TitleFps},
+    uniforms::{
+        ANIMATED_BINDINGS, DATE_BINDING, Inputs, KEYBOARD_BINDING, Layout, MOUSE_BINDING,
+        REAL_TIME_BINDING, SAMPLER_BINDING, SCROLL_BINDING, STORAGE_BINDING, TIME_BINDING,
+        UniformProvider,
+    },
+    watcher::WatchGuard,
+};
+
+/// Runs the viewer like the `shadertoy` binary: reads the options from the
+/// command line and the config file, then validates, exports or records
+/// without a window, or opens one and runs the event loop until it closes.
+///
+/// Logs through `tracing`, so the caller installs the subscriber.
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args = config::parse_args()?;
+    if let Some(dir) = &args.validate {
+        if !validate::validate_dir(&args, dir)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(path) = &args.export {
+        return export::export(&load_manifest(&args)?, path);
+    }
+    if let Some(path) = &args.render {
+        return record::render_png(&args, path);
+    }
+    if let Some(path) = &args.gif {
+        return record::record_gif(&args, path);
+    }
+    if let Some(path) = &args.sequence {
+        return record::record_sequence(&args, path);
+    }
+    tracing::info!("Starting application...");
+    let el = EventLoop::with_user_event().build()?;
+    install_shutdown_handler(&el)?;
+    // Removes the socket file once the event loop has exited.
+    let _socket = args
+        .socket
+        .as_deref()
+        .map(|path| socket::listen(path, el.create_proxy()))
+        .transpose()
+        .map_err(|err| format!("Failed to listen on the socket: {err}"))?;
+    let proxy = el.create_proxy();
+    el.run_app(&mut App::new(args, proxy))?;
+    tracing::info!("Shut down cleanly");
+    Ok(())
+}
+
+/// Loads the manifest given by `--manifest`, `shadertoy.toml` or a single
+/// shader, with the command-line overrides applied.
+fn load_manifest(args: &Args) -> Result<Manifest, Box<dyn std::error::Error>> {
+    let mut manifest = match manifest_path(args) {
+        Some(path) => Manifest::load(&path)?,
+        None => Manifest {
+            default_source: Some(DEFAULT_SHADER),
+            ..Manifest::single(
+                args.shader
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(SHADER_FILE)),
+            )
+        },
+    };
+    apply_args(&mut manifest, args);
+    Ok(manifest)
+}
+
+/// The manifest given by `--manifest`, or `shadertoy.toml` if it exists and
+/// no shader was given.
+fn manifest_path(args: &Args) -> Option<PathBuf> {
+    args.manifest.clone().or_else(|| {
+        Some(PathBuf::from(MANIFEST_FILE)).filter(|path| args.shader.is_none() && path.exists())
+    })
+}
+
+/// Applies the command-line options overriding parts of the manifest.
+fn apply_args(manifest: &mut Manifest, args: &Args) {
+    if let Some(prelude) = &args.prelude {
+        manifest.prelude = Some(prelude.clone());
+    }
+    manifest.storage_size = args.storage_size.or(manifest.storage_size);
+    manifest.watch.extend(args.watch_globs.iter().cloned());
+    manifest.dump = args.dump_wgsl.clone().map(|path| Dump {
+        path,
+        naga_ir: args.dump_naga_ir,
+    });
+    manifest.initial = Some(args.initial.into());
+    manifest.max_file_size = Some(args.max_shader_size);
+    manifest.geometry = args.fullscreen_geometry.into();
+    if let Some(pass) = manifest.screen_pass_mut() {
+        for (channel, name) in args.channels() {
+            pass.inputs
+                .insert(format!("iChannel{channel}"), name.to_owned());
+        }
+    }
+    for (channel, dir, fps) in args.flipbooks() {
+        let name = format!("channel{channel}-flipbook");
+        manifest.flipbooks.insert(
+            name.clone(),
+            FlipbookManifest {
+                dir: dir.to_owned(),
+                fps: fps.unwrap_or(flipbook::DEFAULT_FPS),
+                looping: true,
+            },
+        );
+        if let Some(pass) = manifest.screen_pass_mut() {
+            pass.inputs.insert(format!("iChannel{channel}"), name);
+        }
+    }
+}
+
+/// Events sent to the event loop from outside of it.
+#[derive(Debug)]
+enum UserEvent {
+    /// Ctrl+C was pressed; exit the event loop and release the GPU.
+    Shutdown,
+    /// The driver lost the GPU device, e.g. after a reset, so the renderer
+    /// has to be rebuilt.
+    DeviceLost,
+    /// A client of `--socket` sent a request.
+    Request(socket::Message),
+}
+
+/// Routes Ctrl+C into the event loop so the app can exit cleanly. A second
+/// Ctrl+C while shutting down terminates the process immediately.
+fn install_shutdown_handler(el: &EventLoop<UserEvent>) -> Result<(), ctrlc::Error> {
+    let proxy = el.create_proxy();
+    let requested = AtomicBool::new(false);
+    ctrlc::set_handler(move || {
+        if requested.swap(true, Ordering::SeqCst) {
+            tracing::warn!("Forced exit");
+            std::process::exit(130);
+        }
+        tracing::info!("Interrupted, shutting down...");
+        if proxy.send_event(UserEvent::Shutdown).is_err() {
+            std::process::exit(130);
+        }
+    })
+}
+
+/// The renderer and everything it draws into a window.
+///
+/// It doesn't own the event loop: [`App`], or the host app through
+/// [`Renderer`], creates the window, hands it to [`AppState::new`] and
+/// forwards the window's events to it, `redraw` on `RedrawRequested` and
+/// `window_event` on the others, then calls `wake_at` once they're handled.
+#[derive(Debug)]
+struct AppState {
+    window: Arc<Window>,
+    device: Device,
+    queue: Queue,
+    surface: Surface<'static>,
+    graph: RenderGraph,
+    /// Rebuilds `graph` when the manifest changes.
+    manifest_watch: Option<ManifestWatch>,
+    config: SurfaceConfiguration,
+    buffer: Buffer,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    clock: Clock,
+    /// Whether reloads leave the clock running, see `--keep-time`.
+    keep_time: bool,
+    /// When the app started, for the real time uniform.
+    started: Instant,
+    uniforms: Vec<Box<dyn UniformProvider>>,
+    uniform_layout: Layout,
+    uniform_data: Vec<u8>,
+    modifiers: ModifiersState,
+    keyboard: Keyboard,
+    /// Whether the graph has to run this frame, rather than the scene
+    /// keeping its contents.
+    render_scene: bool,
+    /// Only redraw on input or reloads unless the shader is animated.
+    idle: bool,
+    smooth_mouse: bool,
+    cursor: PhysicalPosition<f64>,
+    /// Whether the cursor is hidden and held in the window, see `F9`. Mouse
+    /// motion then moves `cursor` without stopping at the window's edges.
+    cursor_grabbed: bool,
+    /// Cursor position in scene pixels when the left button was pressed.
+    click: Option<[f32; 2]>,
+    /// Value of the scroll uniform, see `Home`.
+    scroll: f32,
+    scroll_speed: f32,
+    copy_requested: bool,
+    /// Holds the frames copied with `Ctrl+C`, see [`capture::Clipboard`].
+    clipboard: capture::Clipboard,
+    /// Whether the next frame is saved as a PNG, see `F12`.
+    screenshot_requested: bool,
+    /// How copied frames and snapshots are encoded, see `--capture-encoding`.
+    capture_encoding: Encoding,
+    /// Where frames are saved after reloads, see `--snapshot-dir`.
+    snapshot_dir: Option<PathBuf>,
+    /// Whether the next frame of the scene is saved to `snapshot_dir`.
+    snapshot_requested: bool,
+    /// Where buffer pass contents are saved, see `--save-state`.
+    save_state: Option<PathBuf>,
+    overlay: Overlay,
+    frame_times: FrameTimes,
+    /// Window title, followed by the frame rate with `--title-fps`.
+    title: String,
+    /// Frame rate shown in the window title, see `--title-fps`.
+    title_fps: Option<TitleFps>,
+    show_frame_graph: bool,
+    /// Whether the shortcuts are listed over the output, see `F1`.
+    show_help: bool,
+    hud: Hud,
+    /// Colors of the HUD and frame graph, following the desktop theme.
+    theme: Theme,
+    editor: Editor,
+    /// Versions of the screen shader that compiled, see `PageUp`.
+    history: History,
+    gpu_info: GpuInfo,
+    blit: Blit,
+    /// Correction applied by the blit, see `--adjust`.
+    adjustment: Option<Adjustment>,
+    scene: Texture,
+    scene_bind_group: BindGroup,
+    /// Shader drawn next to the scene, see `--compare`.
+    comparison: Option<Comparison>,
+    /// Running mean of the scene, see `--accumulate`.
+    accumulator: Option<Accumulator>,
+    viewport: Viewport,
+    aspect: Option<f32>,
+    /// Whether the output is drawn upside down, see `--flip-y`.
+    flip_y: bool,
+    internal_size: Option<PhysicalSize<u32>>,
+    letterbox_color: wgpu::Color,
+    seed: u64,
+    /// Longest a frame may take before the shaders are replaced by the
+    /// fallback, see `--frame-timeout`.
+    frame_timeout: Option<Duration>,
+    /// Whether the window has no area, so nothing is rendered.
+    minimized: bool,
+    /// Comparison of present modes, see `--bench-present-modes`.
+    bench: Option<PresentModeBench>,
+    /// Frames left to render, see `--max-frames`.
+    frame_limit: Option<FrameLimit>,
+    /// Redraw schedule of `--low-latency`.
+    pacer: Option<FramePacer>,
+    /// Frame rate limit of `--max-fps`.
+    frame_cap: Option<FrameCap>,
+    /// Lowers the frame rate of static output, see `--throttle-after`.
+    throttle: Option<Throttle>,
+    /// Refresh rate of the window's monitor in hertz, if it's known.
+    refresh_rate: Option<f64>,
+    events: Events,
+    /// Set when the driver loses the device, see [`UserEvent::DeviceLost`].
+    lost: Arc<AtomicBool>,
+}
+
+/// The manifest the graph is built from, which rebuilds it when changed.
+#[derive(Debug)]
+struct ManifestWatch {
+    path: PathBuf,
+    rx: mpsc::Receiver<String>,
+    /// Options applied on top of every version of the manifest.
+    args: Args,
+    /// Size of the storage buffer, which is bound for the whole run.
+    storage_size: Option<u64>,
+    /// Option defaults, which are only read at startup.
+    options: toml::Table,
+    /// Stops the thread watching `path` when the window's state is dropped,
+    /// e.g. rebuilt after the device was lost.
+    _guard: WatchGuard,
+}
+
+/// Second shader drawn next to the first one, see `--compare`.
+#[derive(Debug)]
+struct Comparison {
+    graph: RenderGraph,
+    scene: Texture,
+}
+
+#[derive(Debug)]
+struct App {
+    args: Args,
+    proxy: EventLoopProxy<UserEvent>,
+    /// Shared by every renderer built for the window, so subscribers keep
+    /// receiving events across rebuilds.
+    events: Events,
+    state: Option<AppState>,
+    recovery: Recovery,
+}
+
+impl App {
+    fn new(args: Args, proxy: EventLoopProxy<UserEvent>) -> Self {
+        let events = Events::default();
+        if args.events {
+            events::print(events.subscribe());
+        }
+        Self {
+            args,
+            proxy,
+            events,
+            state: None,
+            recovery: Recovery::default(),
+        }
+    }
+
+    /// Schedules a rebuild of the renderer after its device was lost, backing
+    /// off while rebuilds keep failing or losing the device again soon.
+    /// Gives up and exits after [`MAX_RECOVERIES`] of them in a row.
+    fn recover(&mut self, el: &ActiveEventLoop) {
+        if self.recovery.retry_at.is_some() {
+            return;
+        }
+        let now = Instant::now();
+        if self
+            .recovery
+            .rebuilt
+            .is_some_and(|rebuilt| now - rebuilt < RECOVERY_STABLE)
+        {
+            self.recovery.failures += 1;
+        } else {
+            self.recovery.failures = 0;
+        }
+        self.schedule_recovery(el, now);
+    }
+
+    fn schedule_recovery(&mut self, el: &ActiveEventLoop, now: Instant) {
+        let failures = self.recovery.failures;
+        if failures >= MAX_RECOVERIES {
+            tracing::error!("The GPU device keeps getting lost, giving up after {failures} tries");
+            el.exit();
+            return;
+        }
+        let delay = if failures == 0 {
+            Duration::ZERO
+        } else {
+            RECOVERY_BACKOFF * 2u32.pow(failures - 1)
+        };
+        tracing::warn!("Rebuilding the renderer in {delay:.1?}");
+        self.recovery.retry_at = Some(now + delay);
+    }
+
+    /// Rebuilds the renderer on the same window, continuing with the shader
+    /// and clock of the old one, see [`AppState::keep`].
+    #[tracing::instrument(skip_all)]
+    fn rebuild(&mut self, el: &ActiveEventLoop) {
+        self.recovery.retry_at = None;
+        // The old surface is dropped first, since some platforms allow only
+        // one per window.
+        if let Some(old) = self.state.take() {
+            self.recovery.kept = Some(old.keep());
+        }
+        let Some(kept) = self.recovery.kept.take() else {
+            return;
+        };
+        let rebuilt = AppState::rebuild(
+            kept,
+            &self.args,
+            Some(self.proxy.clone()),
+            self.events.clone(),
+        );
+        match pollster::block_on(rebuilt) {
+            Ok(state) => {
+                self.state = Some(state);
+                self.recovery.rebuilt = Some(Instant::now());
+            }
+            Err((kept, err)) => {
+                tracing::error!("Failed to rebuild the renderer: {err}");
+                self.recovery.kept = Some(kept);
+                self.recovery.failures += 1;
+                self.schedule_recovery(el, Instant::now());
+            }
+        }
+    }
+}
+
+/// Progress of rebuilding the renderer after device losses, see
+/// [`App::recover`].
+#[derive(Debug, Default)]
+struct Recovery {
+    /// Rebuilds in a row that failed or lost the device again soon.
+    failures: u32,
+    /// When the renderer was last rebuilt.
+    rebuilt: Option<Instant>,
+    /// When the next rebuild is due, if one is scheduled.
+    retry_at: Option<Instant>,
+    /// What the lost renderer left, until a rebuild succeeds.
+    kept: Option<Kept>,
+}
+
+/// What a renderer whose device was lost leaves for the next one, see
+/// [`AppState::keep`].
+#[derive(Debug)]
+struct Kept {
+    window: Arc<Window>,
+    screen_source: Option<String>,
+    clock: Clock,
+    seed: u64,
+    scroll: f32,
+    cursor: PhysicalPosition<f64>,
+    cursor_grabbed: bool,
+    click: Option<[f32; 2]>,
+    modifiers: ModifiersState,
+    editor: Editor,
+    history: History,
+    show_help: bool,
+    show_frame_graph: bool,
+    show_hud: bool,
+    adjustment: Option<Adjustment>,
+    clipboard: capture::Clipboard,
+}
+
+impl AppState {
+    /// Builds the renderer against `window`, which can be any window on the
+    /// current event loop, and configures its surface to the window's size.
+    /// `proxy` is told when the device is lost, on top of `lost` being set,
+    /// and `events` receives what happens from then on.
+    #[tracing::instrument(skip_all)]
+    async fn new(
+        window: Arc<Window>,
+        args: &Args,
+        proxy: Option<EventLoopProxy<UserEvent>>,
+        events: Events,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        tracing::info!("Initializing renderer...");
+
+        let (width, height): (u32, u32) = window.inner_size().into();
+        tracing::debug!("Window size: {}x{}", width, height);
+        let minimized = width == 0 || height == 0;
+        let (width, height) = (width.max(1), height.max(1));
+
+        let instance = device::instance(args);
+
+        let surface = instance.create_surface(window.clone())?;
+        tracing::trace!("Surface created");
+
+        let adapter = device::adapter(&instance, args, Some(&surface)).await?;
+
+        let (device, queue) = device::request_device(&adapter).await?;
+        if let Some(dir) = &args.snapshot_dir {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("Failed to create `{}`: {err}", dir.display()))?;
+        }
+        let lost_events = events.clone();
+        let lost = Arc::new(AtomicBool::new(false));
+        let lost_flag = lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            tracing::error!("GPU device lost ({reason:?}): {message}");
+            lost_events.send(Event::DeviceLost {
+                reason: format!("{reason:?}"),
+                message,
+            });
+            // Dropping the device doesn't call this, and it's never
+            // destroyed on purpose, so every loss is the driver's.
+            if reason == DeviceLostReason::Unknown {
+                lost_flag.store(true, Ordering::SeqCst);
+                if let Some(proxy) = &proxy {
+                    let _ = proxy.send_event(UserEvent::DeviceLost);
+                }
+            }
+        });
+        tracing::trace!("Device and queue created");
+        let gpu_info = GpuInfo::new(&adapter, &device);
+        tracing::info!("GPU: {gpu_info}");
+
+        let (width, height) = fit_texture_limit(&device, PhysicalSize::new(width, height)).into();
+        let mut config = surface.get_default_config(&adapter, width, height).unwrap();
+        let capabilities = surface.get_capabilities(&adapter);
+        tracing::debug!("Supported surface formats: {:?}", capabilities.formats);
+        if let Some(format) = args.format.map(TextureFormat::from) {
+            if capabilities.formats.contains(&format) {
+                config.format = format;
+            } else {
+                tracing::warn!(
+                    "Surface format {format:?} isn't supported, using {:?}. Supported formats: {:?}",
+                    config.format,
+                    capabilities.formats
+                );
+            }
+        }
+        if let Some(mode) = args.alpha_mode.map(CompositeAlphaMode::from) {
+            if capabilities.alpha_modes.contains(&mode) {
+                config.alpha_mode = mode;
+            } else {
+                tracing::warn!(
+                    "Alpha mode {mode:?} isn't supported, using {:?}. Supported modes: {:?}",
+                    config.alpha_mode,
+                    capabilities.alpha_modes
+                );
+            }
+        }
+        tracing::info!("Alpha mode: {:?}", config.alpha_mode);
+        if capabilities.usages.contains(TextureUsages::COPY_SRC) {
+            config.usage |= TextureUsages::COPY_SRC;
+        } else {
+            tracing::warn!("Surface does not support readback, frame capture is unavailable");
+        }
+        let bench = args.bench_present_modes.map(|frames| {
+            let bench = PresentModeBench::new(&capabilities.present_modes, frames);
+            config.present_mode = bench.mode();
+            bench
+        });
+        let pacer = args.low_latency.then(|| {
+            config.desired_maximum_frame_latency = 1;
+            if capabilities.present_modes.contains(&PresentMode::Mailbox) {
+                config.present_mode = PresentMode::Mailbox;
+                Some(FramePacer::new(monitor_refresh_rate(&window)))
+            } else {
+                tracing::warn!("The surface doesn't support Mailbox, low latency mode uses Fifo");
+                config.present_mode = PresentMode::Fifo;
+                None
+            }
+        });
+        let pacer = pacer.flatten();
+        if let Some(mode) = args.present_mode.map(PresentMode::from) {
+            if capabilities.present_modes.contains(&mode) {
+                config.present_mode = mode;
+            } else {
+                tracing::warn!(
+                    "Present mode {mode:?} isn't supported, using Fifo. Supported modes: {:?}",
+                    capabilities.present_modes
+                );
+                config.present_mode = PresentMode::Fifo;
+            }
+        }
+        tracing::info!("Present mode: {:?}", config.present_mode);
+        // Benchmarks time every frame, so they always run at the full rate.
+        let throttle =
+            (!args.no_throttle && !args.idle && bench.is_none() && args.max_frames.is_none())
+                .then(|| Throttle::new(args.throttle_after, args.throttle_fps));
+        let frame_cap = args
+            .max_fps
+            .filter(|fps| *fps > 0.0 && bench.is_none())
+            .map(FrameCap::new);
+        surface.configure(&device, &config);
+        tracing::info!("Surface format: {:?}", config.format);
+
+        let alignment = u64::from(device.limits().min_uniform_buffer_offset_alignment);
+        tracing::debug!("Buffer alignment: {} bytes", alignment);
+
+        let manifest = load_manifest(args)?;
+
+        let uniforms = uniforms::defaults(args);
+        let uniform_layout = Layout::new(alignment, &uniforms);
+
+        let storage_buffer = manifest
+            .storage_size
+            .map(|size| Self::create_storage_buffer(&adapter, &device, size))
+            .transpose()?;
+
+        let keyboard = Keyboard::new(&device);
+        let (buffer, bind_group_layout, bind_group) = Self::create_bindings(
+            &device,
+            &uniform_layout,
+            storage_buffer.as_ref(),
+            &keyboard.texture().create_view(&Default::default()),
+        );
+
+        let overlay = Overlay::new(&device, &queue, config.format);
+
+        let mut blit = Blit::new(&device, config.format, args.upscale.into());
+        let adjustment = args.adjustment();
+        if let Some(adjustment) = adjustment {
+            blit.set_adjustment(&queue, adjustment);
+            log_adjustment(adjustment);
+        }
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if let Some(size) = args.internal
+            && size.width.max(size.height) > max_dimension
+        {
+            return Err(format!(
+                "Internal resolution {}x{} exceeds the maximum texture size of {max_dimension}",
+                size.width, size.height
+            )
+            .into());
+        }
+        let aspect = args.aspect.or_else(|| {
+            args.internal
+                .map(|size| size.width as f32 / size.height as f32)
+        });
+        let viewport = Viewport::letterbox(config.width, config.height, aspect);
+        let scene_size = args.internal.unwrap_or(viewport.size());
+        let scene = Self::create_scene(&device, config.format, scene_size);
+        let accumulator = args.accumulate.map(|samples| {
+            Accumulator::new(
+                &adapter,
+                &device,
+                config.format,
+                &scene.create_view(&Default::default()),
+                scene_size,
+                &blit,
+                samples,
+            )
+        });
+        let letterbox_color = if config.format.is_srgb() {
+            srgb_to_linear(args.letterbox_color)
+        } else {
+            args.letterbox_color
+        };
+
+        let graph = RenderGraph::new(
+            &device,
+            &queue,
+            &manifest,
+            config.format,
+            &bind_group_layout,
+            args.watch_config(),
+            scene_size,
+        )?;
+        let manifest_watch = manifest_path(args)
+            .zip(args.watch_config())
+            .map(|(path, config)| -> Result<_, Box<dyn std::error::Error>> {
+                let guard = WatchGuard::default();
+                let rx = watcher::spawn_watcher_thread(
+                    path.clone(),
+                    config,
+                    Some(args.max_shader_size),
+                    &guard,
+                )?;
+                // The graph was just built from the current contents.
+                let _ = rx.try_recv();
+                Ok(ManifestWatch {
+                    path,
+                    rx,
+                    args: args.clone(),
+                    storage_size: manifest.storage_size,
+                    options: manifest.options.clone(),
+                    _guard: guard,
+                })
+            })
+            .transpose()?;
+        let comparison = args
+            .compare
+            .as_ref()
+            .map(|path| -> Result<_, Box<dyn std::error::Error>> {
+                let manifest = Manifest {
+                    prelude: manifest.prelude.clone(),
+                    initial: manifest.initial,
+                    max_file_size: manifest.max_file_size,
+                    geometry: manifest.geometry,
+                    ..Manifest::single(path.clone())
+                };
+                Ok(Comparison {
+                    graph: RenderGraph::new(
+                        &device,
+                        &queue,
+                        &manifest,
+                        config.format,
+                        &bind_group_layout,
+                        args.watch_config(),
+                        scene_size,
+                    )?,
+                    scene: Self::create_scene(&device, config.format, scene_size),
+                })
+            })
+            .transpose()?;
+        if comparison.is_some() {
+            blit.set_split(&queue, Some(args.split.into()));
+        }
+        if args.flip_y {
+            blit.set_flip_y(&queue, true);
+        }
+        let scene_bind_group =
+            Self::create_scene_bind_group(&device, &blit, &scene, comparison.as_ref());
+        if args.no_watch {
+            tracing::info!("Shader hot reload disabled");
+        } else {
+            tracing::info!("Shader hot reload enabled");
+        }
+        let mut editor = Editor::new(graph.screen_source().0.to_owned());
+        if let Some(source) = graph.screen_source().1 {
+            editor.load(source);
+        }
+
+        let theme = Theme::from_window(window.theme());
+        tracing::debug!("Overlay theme: {theme:?}");
+
+        let refresh_rate = monitor_refresh_rate(&window);
+        log_refresh_rate(refresh_rate);
+
+        tracing::info!("Renderer ready");
+        let mut state = Self {
+            window,
+            device,
+            queue,
+            surface,
+            graph,
+            manifest_watch,
+            config,
+            buffer,
+            bind_group_layout,
+            bind_group,
+            clock: Clock::new(args.deterministic.then_some(args.fps)),
+            keep_time: args.keep_time,
+            started: Instant::now(),
+            uniform_data: vec![0; uniform_layout.size() as usize],
+            uniforms,
+            uniform_layout,
+            modifiers: ModifiersState::empty(),
+            keyboard,
+            render_scene: true,
+            idle: args.idle,
+            smooth_mouse: args.mouse_smoothing.is_some(),
+            cursor: PhysicalPosition::default(),
+            cursor_grabbed: false,
+            click: None,
+            scroll: 0.0,
+            scroll_speed: args.scroll_speed,
+            copy_requested: false,
+            clipboard: capture::Clipboard::default(),
+            screenshot_requested: false,
+            capture_encoding: args.capture_encoding.into(),
+            snapshot_dir: args.snapshot_dir.clone(),
+            snapshot_requested: false,
+            save_state: args.save_state.clone(),
+            overlay,
+            frame_times: FrameTimes::new(FRAME_GRAPH_SAMPLES),
+            title: args.title.clone(),
+            title_fps: args.title_fps.map(|frames| TitleFps::new(frames.get())),
+            show_frame_graph: false,
+            show_help: false,
+            hud: Hud::default(),
+            theme,
+            editor,
+            history: History::default(),
+            lost,
+            gpu_info,
+            blit,
+            adjustment,
+            scene,
+            scene_bind_group,
+            comparison,
+            accumulator,
+            viewport,
+            aspect,
+            flip_y: args.flip_y,
+            internal_size: args.internal,
+            letterbox_color,
+            seed: args.seed.unwrap_or_else(entropy_seed),
+            frame_timeout: args.frame_timeout,
+            minimized,
+            bench,
+            frame_limit: args.max_frames.map(FrameLimit::new),
+            refresh_rate,
+            pacer,
+            frame_cap,
+            throttle,
+            events,
+        };
+        log_seed(state.seed);
+        if let Some(path) = &args.load_state {
+            state
+                .load_state(path)
+                .map_err(|err| format!("Failed to load state `{}`: {err}", path.display()))?;
+        }
+        Ok(state)
+    }
+
+    /// Creates the offscreen texture the fragment shader renders into before
+    /// it's blitted onto the surface.
+    fn create_scene(device: &Device, format: TextureFormat, size: PhysicalSize<u32>) -> Texture {
+        device.create_texture(&TextureDescriptor {
+            label: Some("scene texture"),
+            size: Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Creates the blit bind group of `scene`, split with the scene of the
+    /// `comparison` if there is one.
+    fn create_scene_bind_group(
+        device: &Device,
+        blit: &Blit,
+        scene: &Texture,
+        comparison: Option<&Comparison>,
+    ) -> BindGroup {
+        let scene = scene.create_view(&Default::default());
+        match comparison {
+            Some(comparison) => blit.comparison_bind_group(
+                device,
+                &scene,
+                &comparison.scene.create_view(&Default::default()),
+            ),
+            None => blit.bind_group(device, &scene),
+        }
+    }
+
+    /// Creates the zero-initialized storage buffer shared with the fragment
+    /// shader, checking that the device can bind one of `size` bytes.
+    #[tracing::instrument(skip(adapter, device))]
+    fn create_storage_buffer(
+        adapter: &Adapter,
+        device: &Device,
+        size: u64,
+    ) -> Result<Buffer, Box<dyn std::error::Error>> {
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::FRAGMENT_WRITABLE_STORAGE)
+        {
+            return Err("This adapter does not support storage buffers in fragment shaders".into());
+        }
+        let max_size = device.limits().max_storage_buffer_binding_size;
+        if size == 0 || !size.is_multiple_of(4) || size > u64::from(max_size) {
+            return Err(format!(
+                "Storage buffer size must be a non-zero multiple of 4 of at most {max_size} bytes, got {size}"
+            )
+            .into());
+        }
+
+        tracing::debug!("Storage buffer size: {size} bytes");
+        Ok(device.create_buffer(&BufferDescriptor {
+            label: Some("storage buffer"),
+            size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        }))
+    }
+
+    #[tracing::instrument(skip(device, storage_buffer, keyboard))]
+    fn create_bindings(
+        device: &Device,
+        layout: &Layout,
+        storage_buffer: Option<&Buffer>,
+        keyboard: &TextureView,
+    ) -> (Buffer, BindGroupLayout, BindGroup) {
+        tracing::debug!(
+            "Uniform buffer: {} bytes for {} bindings",
+            layout.size(),
+            layout.bindings().len()
+        );
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("uniform buffer"),
+            size: layout.size(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut layout_entries: Vec<_> = layout
+            .bindings()
+            .iter()
+            .map(|&binding| BindGroupLayoutEntry {
+                binding,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::default(),
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect();
+        let mut entries: Vec<_> = layout
+            .bindings()
+            .iter()
+            .map(|&binding| BindGroupEntry {
+                binding,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer: &buffer,
+                    offset: layout.offset(binding),
+                    size: None,
+                }),
+            })
+            .collect();
+
+        if let Some(storage_buffer) = storage_buffer {
+            layout_entries.push(BindGroupLayoutEntry {
+                binding: STORAGE_BINDING,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+            entries.push(BindGroupEntry {
+                binding: STORAGE_BINDING,
+                resource: storage_buffer.as_entire_binding(),
+            });
+        }
+
+        layout_entries.push(BindGroupLayoutEntry {
+            binding: KEYBOARD_BINDING,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        });
+        entries.push(BindGroupEntry {
+            binding: KEYBOARD_BINDING,
+            resource: BindingResource::TextureView(keyboard),
+        });
+
+        let sampler = sampler::create_sampler(device, "default sampler", Default::default());
+        layout_entries.push(BindGroupLayoutEntry {
+            binding: SAMPLER_BINDING,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        });
+        entries.push(BindGroupEntry {
+            binding: SAMPLER_BINDING,
+            resource: BindingResource::Sampler(&sampler),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("bind group layout"),
+            entries: &layout_entries,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("bind group"),
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+        (buffer, bind_group_layout, bind_group)
+    }
+
+    /// Waits for all submitted GPU work to finish so the device can be dropped
+    /// without work in flight.
+    #[tracing::instrument(skip_all)]
+    fn shutdown(self) {
+        tracing::debug!("Waiting for the GPU queue to drain");
+        if let Err(err) = self.device.poll(PollType::wait_indefinitely()) {
+            tracing::warn!("Failed to drain GPU queue: {err}");
+        }
+        drop(self);
+        tracing::trace!("Device dropped");
+    }
+
+    /// Follows the window to its new inner size. A size without area pauses
+    /// rendering until the window is restored.
+    #[tracing::instrument(skip(self))]
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        let (width, height): (u32, u32) = size.into();
+        tracing::debug!("Resized to {}x{}", width, height);
+        // Minimized windows can report a zero size, which can't be
+        // configured. The last size stays until the window is restored.
+        self.minimized = width == 0 || height == 0;
+        if self.minimized {
+            tracing::debug!("Window has no area, pausing rendering");
+            return;
+        }
+        let size = fit_texture_limit(&self.device, size);
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.surface.configure(&self.device, &self.config);
+
+        self.viewport = Viewport::letterbox(self.config.width, self.config.height, self.aspect);
+        let scene_size = self.internal_size.unwrap_or(self.viewport.size());
+        if scene_size != self.scene_size() {
+            tracing::trace!(?scene_size, "Recreating scene texture");
+            self.scene = Self::create_scene(&self.device, self.config.format, scene_size);
+            self.graph.resize(&self.device, scene_size);
+            if let Some(comparison) = &mut self.comparison {
+                comparison.scene = Self::create_scene(&self.device, self.config.format, scene_size);
+                comparison.graph.resize(&self.device, scene_size);
+            }
+            self.scene_bind_group = Self::create_scene_bind_group(
+                &self.device,
+                &self.blit,
+                &self.scene,
+                self.comparison.as_ref(),
+            );
+            if let Some(accumulator) = &mut self.accumulator {
+                accumulator.resize(
+                    &self.device,
+                    &self.scene.create_view(&Default::default()),
+                    scene_size,
+                    &self.blit,
+                );
+            }
+            self.render_scene = true;
+        }
+    }
+
+    fn scene_size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.scene.width(), self.scene.height())
+    }
+
+    /// Records that the input or shaders changed, returning to the full frame
+    /// rate, see `--throttle-after`.
+    fn changed(&mut self) {
+        if let Some(throttle) = &mut self.throttle {
+            throttle.change(Instant::now());
+        }
+    }
+
+    /// Recompiles changed shaders, including the comparison's, restarting
+    /// the clock if any was reloaded, see [`Self::restart_time`].
+    /// The editor follows changes made outside of it unless it has unsaved
+    /// edits.
+    #[tracing::instrument(skip_all)]
+    fn reload(&mut self) -> bool {
+        let rebuilt = self.reload_manifest();
+        let reloaded = self.graph.reload(&self.device, &self.bind_group_layout);
+        let compared = self
+            .comparison
+            .as_mut()
+            .map_or_else(Vec::new, |comparison| {
+                comparison
+                    .graph
+                    .reload(&self.device, &self.bind_group_layout)
+            });
+        let any = rebuilt || !reloaded.is_empty() || !compared.is_empty();
+        if any {
+            self.restart_time();
+            self.changed();
+        }
+        if rebuilt || !reloaded.is_empty() {
+            self.snapshot_requested = self.snapshot_dir.is_some()
+                && self.graph.pass_status().all(|(_, _, compiled)| compiled);
+            self.reset_accumulation();
+            self.record_history();
+            if !self.editor.is_dirty()
+                && let Some(source) = self.graph.screen_source().1
+            {
+                self.editor.load(source);
+            }
+        }
+        for reloaded in reloaded.into_iter().chain(compared) {
+            self.events.send(Event::Reloaded(reloaded));
+        }
+
+        // New images don't restart time, since the shaders didn't change.
+        let mut images = self.graph.reload_images(&self.device, &self.queue);
+        if let Some(comparison) = &mut self.comparison {
+            images |= comparison.graph.reload_images(&self.device, &self.queue);
+        }
+        if images {
+            self.render_scene = true;
+            self.reset_accumulation();
+            self.changed();
+        }
+        any || images
+    }
+
+    /// Rebuilds the graph if the manifest changed, copying over the buffers
+    /// that kept their name and size. A manifest that fails to load keeps
+    /// the current graph running. Returns whether the graph was replaced.
+    #[tracing::instrument(skip_all)]
+    fn reload_manifest(&mut self) -> bool {
+        let Some(watch) = &self.manifest_watch else {
+            return false;
+        };
+        let Some(source) = watch.rx.try_iter().last() else {
+            return false;
+        };
+        let graph = Manifest::parse(&source, &watch.path).and_then(|mut manifest| {
+            apply_args(&mut manifest, &watch.args);
+            if manifest.storage_size != watch.storage_size {
+                return Err("changing `storage_size` needs a restart".into());
+            }
+            if manifest.options != watch.options {
+                return Err("changing `options` needs a restart".into());
+            }
+            RenderGraph::new(
+                &self.device,
+                &self.queue,
+                &manifest,
+                self.config.format,
+                &self.bind_group_layout,
+                watch.args.watch_config(),
+                self.scene_size(),
+            )
+        });
+        let mut graph = match graph {
+            Ok(graph) => graph,
+            Err(err) => {
+                tracing::error!(
+                    "Keeping the previous render graph, `{}` is invalid: {err}",
+                    watch.path.display()
+                );
+                return false;
+            }
+        };
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("graph rebuild encoder"),
+            });
+        let copied = graph.copy_targets_from(&self.graph, &mut encoder);
+        self.queue.submit([encoder.finish()]);
+        self.graph = graph;
+        for reloaded in self.graph.outcomes() {
+            self.events.send(Event::Reloaded(reloaded));
+        }
+        tracing::info!(
+            "Rebuilt the render graph from `{}`, keeping buffers {copied:?}",
+            watch.path.display()
+        );
+        true
+    }
+
+    /// Compiles the editor's text in place of the screen shader.
+    #[tracing::instrument(skip_all)]
+    fn apply_editor(&mut self) {
+        self.set_source(self.editor.text());
+        tracing::info!("Applied editor changes");
+    }
+
+    /// Compiles `source` in place of the screen shader and restarts the
+    /// clock, see [`Self::restart_time`], returning the outcome.
+    fn set_source(&mut self, source: String) -> Reloaded {
+        let reloaded = self
+            .graph
+            .set_screen_source(&self.device, &self.bind_group_layout, source);
+        self.events.send(Event::Reloaded(reloaded.clone()));
+        self.restart_time();
+        self.reset_accumulation();
+        self.record_history();
+        reloaded
+    }
+
+    /// Builds a new renderer on the window of `kept` and restores it, or
+    /// hands `kept` back with the error.
+    async fn rebuild(
+        kept: Kept,
+        args: &Args,
+        proxy: Option<EventLoopProxy<UserEvent>>,
+        events: Events,
+    ) -> Result<Self, (Kept, Box<dyn std::error::Error>)> {
+        match Self::new(kept.window.clone(), args, proxy, events).await {
+            Ok(mut state) => {
+                state.restore(kept);
+                state.window.request_redraw();
+                tracing::info!("Rebuilt the renderer after the GPU device was lost");
+                state.events.send(Event::DeviceRecovered);
+                Ok(state)
+            }
+            Err(err) => Err((kept, err)),
+        }
+    }
+
+    /// Tears down a renderer whose device was lost, keeping what the user
+    /// set up for [`Self::restore`]. Buffer contents were on the lost
+    /// device, so they start over.
+    fn keep(self) -> Kept {
+        Kept {
+            screen_source: self.graph.screen_source().1.map(str::to_owned),
+            window: self.window,
+            clock: self.clock,
+            seed: self.seed,
+            scroll: self.scroll,
+            cursor: self.cursor,
+            cursor_grabbed: self.cursor_grabbed,
+            click: self.click,
+            modifiers: self.modifiers,
+            editor: self.editor,
+            history: self.history,
+            show_help: self.show_help,
+            show_frame_graph: self.show_frame_graph,
+            show_hud: self.hud.visible,
+            adjustment: self.adjustment,
+            clipboard: self.clipboard,
+        }
+    }
+
+    /// Continues where the renderer `kept` came from left off: with its
+    /// screen shader's source if it was replaced without going through the
+    /// file, its clock, the uniforms driven by input and the editor.
+    fn restore(&mut self, kept: Kept) {
+        if let Some(source) = kept.screen_source
+            && self.graph.screen_source().1 != Some(source.as_str())
+        {
+            self.set_source(source);
+        }
+        self.clock = kept.clock;
+        self.seed = kept.seed;
+        self.scroll = kept.scroll;
+        self.cursor = kept.cursor;
+        self.cursor_grabbed = kept.cursor_grabbed;
+        self.click = kept.click;
+        self.modifiers = kept.modifiers;
+        self.editor = kept.editor;
+        self.history = kept.history;
+        self.show_help = kept.show_help;
+        self.show_frame_graph = kept.show_frame_graph;
+        self.hud.visible = kept.show_hud;
+        self.clipboard = kept.clipboard;
+        if let Some(adjustment) = kept.adjustment {
+            self.blit.set_adjustment(&self.queue, adjustment);
+            self.adjustment = Some(adjustment);
+        }
+    }
+
+    /// Restarts the clock after a reload, unless `--keep-time` continues the
+    /// animation from where it was.
+    fn restart_time(&mut self) {
+        if !self.keep_time {
+            self.clock.reset();
+        }
+    }
+
+    fn record_history(&mut self) {
+        if let Some(source) = self.graph.screen_compiled_source() {
+            self.history.push(source);
+        }
+    }
+
+    /// Swaps in the previous or next compiled version of the screen shader,
+    /// leaving the file and the clock alone.
+    fn step_history(&mut self, back: bool) {
+        let source = if back {
+            self.history.back()
+        } else {
+            self.history.forward()
+        };
+        let Some(source) = source.map(str::to_owned) else {
+            tracing::debug!("No more shader versions in that direction");
+            return;
+        };
+        let reloaded = self
+            .graph
+            .set_screen_source(&self.device, &self.bind_group_layout, source);
+        self.events.send(Event::Reloaded(reloaded));
+        self.render_scene = true;
+        self.reset_accumulation();
+        let (position, len) = self.history.status();
+        tracing::info!("Showing shader version {position} of {len}");
+    }
+
+    fn reset_accumulation(&mut self) {
+        if let Some(accumulator) = &mut self.accumulator {
+            accumulator.reset();
+        }
+    }
+
+    /// Whether the output can change without any input, and so has to be
+    /// redrawn every frame even in idle mode.
+    fn is_animated(&self) -> bool {
+        let changes_over_time = self.graphs().any(|graph| {
+            graph.has_feedback()
+                || graph.has_flipbooks()
+                || ANIMATED_BINDINGS
+                    .iter()
+                    .any(|&binding| graph.uses_binding(0, binding))
+        });
+        let reads_mouse = self
+            .graphs()
+            .any(|graph| graph.uses_binding(0, MOUSE_BINDING));
+        let reads_wall_clock = self.graphs().any(|graph| {
+            graph.uses_binding(0, REAL_TIME_BINDING) || graph.uses_binding(0, DATE_BINDING)
+        });
+        self.show_frame_graph
+            || self.hud.visible
+            || (changes_over_time && !self.clock.is_paused())
+            || reads_wall_clock
+            || (self.smooth_mouse && reads_mouse)
+            || self
+                .accumulator
+                .as_ref()
+                .is_some_and(|accumulator| !accumulator.is_done())
+    }
+
+    /// Whether stepping back a frame reproduces it. Feedback buffers and the
+    /// storage buffer keep state that can't be rewound.
+    fn can_step_back(&self) -> bool {
+        self.graphs()
+            .all(|graph| !graph.has_feedback() && !graph.uses_binding(0, STORAGE_BINDING))
+    }
+
+    /// The render graph of the scene, and of the comparison if there is one.
+    fn graphs(&self) -> impl Iterator<Item = &RenderGraph> {
+        std::iter::once(&self.graph)
+            .chain(self.comparison.as_ref().map(|comparison| &comparison.graph))
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn update(&mut self) {
+        self.reload();
+
+        self.render_scene |= self.clock.tick();
+        let time = self.clock.time().as_secs_f64();
+        self.render_scene |= self.graph.advance_flipbooks(&self.queue, time);
+        if let Some(comparison) = &mut self.comparison {
+            self.render_scene |= comparison.graph.advance_flipbooks(&self.queue, time);
+        }
+        self.keyboard.upload(&self.queue);
+        let inputs = Inputs {
+            time: self.clock.time().as_secs_f32(),
+            delta: self.clock.delta().as_secs_f32(),
+            real_time: self.started.elapsed().as_secs_f32(),
+            frame: self.clock.frame(),
+            frame_rate: self.frame_rate() as f32,
+            scale_factor: self.window.scale_factor() as f32,
+            resolution: self.scene_size().cast::<f32>().into(),
+            seed: self.seed,
+            mouse: {
+                let [x, y] = self.cursor_in_scene();
+                let [click_x, click_y] = self.click.unwrap_or_default();
+                [x, y, click_x, click_y]
+            },
+            scroll: self.scroll,
+            refresh_rate: self.refresh_rate.unwrap_or_default() as f32,
+            date: uniforms::date(SystemTime::now()),
+        };
+        if let Some(accumulator) = &mut self.accumulator {
+            // Time and the mouse move the camera of most path tracers, while
+            // the frame counter only reseeds their noise.
+            let mut camera = inputs.resolution.to_vec();
+            if self.graph.uses_binding(0, TIME_BINDING) {
+                camera.push(inputs.time);
+            }
+            if self.graph.uses_binding(0, MOUSE_BINDING) {
+                camera.extend(inputs.mouse);
+            }
+            if self.graph.uses_binding(0, SCROLL_BINDING) {
+                camera.push(inputs.scroll);
+            }
+            accumulator.set_inputs(camera);
+        }
+        tracing::trace!(?inputs, "Updating uniforms");
+        for provider in &mut self.uniforms {
+            provider.update(&inputs);
+            provider.write(&mut self.uniform_data, &self.uniform_layout);
+        }
+        self.queue.write_buffer(&self.buffer, 0, &self.uniform_data);
+    }
+
+    /// Frames per second reported to shaders: the fixed rate in
+    /// deterministic mode, otherwise the rate over the recent frames, or
+    /// [`INITIAL_FRAME_RATE`] until a frame was timed.
+    fn frame_rate(&self) -> f64 {
+        match self.clock.fixed_step() {
+            Some(step) => step.as_secs_f64().recip(),
+            None => self.frame_times.rate().unwrap_or(INITIAL_FRAME_RATE),
+        }
+    }
+
+    /// Maps the cursor from window coordinates to scene pixels with the
+    /// origin at the bottom left.
+    fn cursor_in_scene(&self) -> [f32; 2] {
+        let scene = self.scene_size().cast::<f64>();
+        let x = (self.cursor.x - f64::from(self.viewport.x)) * scene.width
+            / f64::from(self.viewport.width);
+        let y = (self.cursor.y - f64::from(self.viewport.y)) * scene.height
+            / f64::from(self.viewport.height);
+        // The scene's origin is at the bottom left, or at the top left when
+        // it's drawn upside down.
+        let y = if self.flip_y { y } else { scene.height - y };
+        [x as f32, y as f32]
+    }
+
+    /// Hides the cursor and locks it to the window, or confines it where
+    /// locking is unsupported, so mouse-look shaders can turn indefinitely.
+    fn grab_cursor(&mut self, grab: bool) {
+        let result = if grab {
+            self.window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            self.window.set_cursor_grab(CursorGrabMode::None)
+        };
+        if let Err(err) = result {
+            tracing::warn!("Failed to change the cursor grab: {err}");
+            if grab {
+                return;
+            }
+        }
+        self.window.set_cursor_visible(!grab);
+        self.cursor_grabbed = grab;
+        tracing::info!(enabled = grab, "Toggled cursor capture");
+    }
+
+    fn mouse_input(&mut self, button_state: ElementState, button: MouseButton) {
+        if button != MouseButton::Left {
+            return;
+        }
+        self.click = match button_state {
+            ElementState::Pressed => Some(self.cursor_in_scene()),
+            ElementState::Released => None,
+        };
+        tracing::trace!(click = ?self.click, "Mouse button changed");
+    }
+
+    /// Follows the refresh rate of the monitor the window is on, which
+    /// changes when it's moved to another one.
+    fn update_refresh_rate(&mut self) {
+        let refresh_rate = monitor_refresh_rate(&self.window);
+        if refresh_rate != self.refresh_rate {
+            self.refresh_rate = refresh_rate;
+            log_refresh_rate(refresh_rate);
+            self.window.request_redraw();
+        }
+    }
+
+    /// Adds the wheel's motion to the scroll uniform, counting touchpad
+    /// pixels in lines of `PIXELS_PER_LINE` logical pixels.
+    fn mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => {
+                (position.y / (PIXELS_PER_LINE * self.window.scale_factor())) as f32
+            }
+        };
+        self.scroll += lines * self.scroll_speed;
+        tracing::trace!(scroll = self.scroll, "Mouse wheel moved");
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn keyboard_input(&mut self, event: &KeyEvent) {
+        // The open editor takes all keys, so typing neither triggers
+        // shortcuts nor reaches the shader.
+        if self.editor.visible {
+            match self.editor.key(event, self.modifiers) {
+                Some(Action::Apply) => self.apply_editor(),
+                Some(Action::Close) => self.editor.visible = false,
+                None => {}
+            }
+            return;
+        }
+        let PhysicalKey::Code(code) = event.physical_key else {
+            return;
+        };
+        self.keyboard.key(code, event.state, event.repeat);
+        if self.graph.uses_binding(0, KEYBOARD_BINDING) {
+            self.reset_accumulation();
+        }
+        if event.state != ElementState::Pressed || event.repeat {
+            return;
+        }
+
+        let Some(command) = keys::command(code, self.modifiers) else {
+            return;
+        };
+        match command {
+            Command::CopyFrame => {
+                tracing::debug!("Frame copy requested");
+                self.copy_requested = true;
+            }
+            Command::SaveScreenshot => {
+                tracing::debug!("Screenshot requested");
+                self.screenshot_requested = true;
+            }
+            Command::RerollSeed => {
+                self.seed = entropy_seed();
+                log_seed(self.seed);
+                self.reset_accumulation();
+            }
+            Command::TogglePause => {
+                self.clock.set_paused(!self.clock.is_paused());
+                tracing::info!(
+                    paused = self.clock.is_paused(),
+                    frame = self.clock.frame(),
+                    "Toggled pause"
+                );
+            }
+            Command::StepForward => {
+                self.clock.step(true);
+                tracing::debug!("Stepping forward from frame {}", self.clock.frame());
+            }
+            Command::StepBack if self.clock.frame() == 0 => {
+                tracing::warn!("Can't step back from the first frame");
+            }
+            Command::StepBack if !self.can_step_back() => {
+                tracing::warn!("Can't step back: feedback and storage buffers can't be rewound");
+            }
+            Command::StepBack => {
+                self.clock.step(false);
+                tracing::debug!("Stepping back from frame {}", self.clock.frame());
+            }
+            Command::ScrubBack => self.scrub(false),
+            Command::ScrubForward => self.scrub(true),
+            Command::ToggleHelp => {
+                self.show_help = !self.show_help;
+                tracing::debug!(enabled = self.show_help, "Toggled help");
+            }
+            Command::ToggleHud => {
+                self.hud.visible = !self.hud.visible;
+                tracing::info!(enabled = self.hud.visible, "Toggled HUD");
+            }
+            Command::ToggleFrameGraph => {
+                self.show_frame_graph = !self.show_frame_graph;
+                tracing::info!(enabled = self.show_frame_graph, "Toggled frame time graph");
+            }
+            Command::PreviousVersion => self.step_history(true),
+            Command::NextVersion => self.step_history(false),
+            Command::OpenEditor => {
+                self.editor.visible = true;
+                tracing::debug!("Opened editor");
+            }
+            Command::PrintStatus => self.print_status(),
+            Command::LowerBrightness => self.adjust(false),
+            Command::RaiseBrightness => self.adjust(true),
+            Command::GrabCursor => self.grab_cursor(!self.cursor_grabbed),
+            Command::ReleaseCursor if self.cursor_grabbed => self.grab_cursor(false),
+            Command::ReleaseCursor => {}
+            Command::SaveState => self.save_state(),
+            Command::ResetScroll => {
+                self.scroll = 0.0;
+                tracing::info!("Reset the scroll uniform");
+            }
+        }
+    }
+
+    /// Moves shader time forward or back by [`SCRUB_STEP`], or by
+    /// [`SCRUB_STEP_FAST`] with `Shift`, stopping at zero. Paused time stays
+    /// paused at the new time.
+    fn scrub(&mut self, forward: bool) {
+        let step = if self.modifiers.shift_key() {
+            SCRUB_STEP_FAST
+        } else {
+            SCRUB_STEP
+        };
+        let time = if forward {
+            self.clock.time() + step
+        } else {
+            self.clock.time().saturating_sub(step)
+        };
+        if let Err(err) = self.clock.seek(time) {
+            tracing::warn!("Can't move time: {err}");
+            return;
+        }
+        self.reset_accumulation();
+        self.render_scene = true;
+        tracing::debug!("Moved time to {time:.2?}");
+    }
+
+    /// Steps the output correction up or down, if it's on: the brightness,
+    /// or the contrast with `Shift` or the gamma with `Ctrl`.
+    fn adjust(&mut self, up: bool) {
+        let Some(adjustment) = &mut self.adjustment else {
+            tracing::debug!("Output correction is off, see `--adjust`");
+            return;
+        };
+        let sign = if up { 1.0 } else { -1.0 };
+        if self.modifiers.shift_key() {
+            adjustment.contrast = (adjustment.contrast + sign * 0.1).max(0.0);
+        } else if self.modifiers.control_key() {
+            adjustment.gamma = (adjustment.gamma + sign * 0.1).max(0.1);
+        } else {
+            adjustment.brightness += sign * 0.05;
+        }
+        self.blit.set_adjustment(&self.queue, *adjustment);
+        log_adjustment(*adjustment);
+    }
+
+    /// Prints the loaded shaders and whether they compiled to stdout as one
+    /// JSON line, for scripts wrapping the viewer.
+    fn print_status(&self) {
+        println!("{}", self.status());
+    }
+
+    /// The loaded shaders and whether they compiled.
+    fn status(&self) -> serde_json::Value {
+        let passes: Vec<_> = self
+            .graph
+            .pass_status()
+            .map(|(name, shader, compiled)| {
+                json!({
+                    "name": name,
+                    "shader": shader.display().to_string(),
+                    "compiled": compiled,
+                })
+            })
+            .collect();
+        json!({
+            "shader": self.graph.screen_source().0.display().to_string(),
+            "compiled": self.graph.screen_compiled_source().is_some(),
+            "passes": passes,
+        })
+    }
+
+    /// Saves the scene to `snapshot_dir`, see `--snapshot-dir`.
+    #[tracing::instrument(skip_all)]
+    fn save_snapshot(&self) {
+        let Some(dir) = &self.snapshot_dir else {
+            return;
+        };
+        let path = dir.join(format!("{}.png", capture::timestamp(SystemTime::now())));
+        match self.save_scene(&path) {
+            Ok(()) => tracing::info!("Saved snapshot `{}`", path.display()),
+            Err(err) => tracing::warn!("Failed to save snapshot: {err}"),
+        }
+    }
+
+    /// Writes the scene to a PNG at `path`, without the HUD and letterbox.
+    fn save_scene(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut frame = capture::read_texture(
+            &self.device,
+            &self.queue,
+            &self.scene,
+            self.capture_encoding,
+        )?;
+        if self.flip_y {
+            frame.flip_rows();
+        }
+        Ok(capture::save_png(&frame, path)?)
+    }
+
+    /// Answers a request sent over `--socket`.
+    #[tracing::instrument(skip(self))]
+    fn answer(&mut self, request: Request) -> Result<serde_json::Value, String> {
+        let answer = match request {
+            Request::SetSource { source } => {
+                let reloaded = self.set_source(source.clone());
+                if !self.editor.is_dirty() {
+                    self.editor.load(&source);
+                }
+                json!(reloaded)
+            }
+            Request::SetUniform { uniform } => {
+                match uniform {
+                    Uniform::Time(time) => {
+                        let time =
+                            Duration::try_from_secs_f64(time).map_err(|err| err.to_string())?;
+                        self.clock.seek(time).map_err(|err| err.to_string())?;
+                    }
+                    Uniform::Scroll(scroll) => self.scroll = scroll,
+                    Uniform::Seed(seed) => {
+                        self.seed = seed;
+                        log_seed(seed);
+                    }
+                }
+                self.reset_accumulation();
+                self.render_scene = true;
+                json!(null)
+            }
+            Request::Screenshot { path } => {
+                self.save_scene(&path).map_err(|err| err.to_string())?;
+                tracing::info!("Saved screenshot `{}`", path.display());
+                json!({ "path": path.display().to_string() })
+            }
+            Request::Status => self.status(),
+        };
+        self.changed();
+        self.window.request_redraw();
+        Ok(answer)
+    }
+
+    /// Saves the buffer passes and the clock to `save_state`, see
+    /// `--save-state`.
+    #[tracing::instrument(skip_all)]
+    fn save_state(&self) {
+        let Some(path) = &self.save_state else {
+            tracing::debug!("Nowhere to save the state, see `--save-state`");
+            return;
+        };
+        let result = self
+            .graph
+            .save_targets(&self.device, &self.queue)
+            .and_then(|targets| {
+                let state = SavedState {
+                    frame: self.clock.frame(),
+                    time: self.clock.time(),
+                    targets,
+                };
+                Ok(state.write(path)?)
+            });
+        match result {
+            Ok(()) => tracing::info!("Saved state `{}`", path.display()),
+            Err(err) => tracing::error!("Failed to save state: {err}"),
+        }
+    }
+
+    /// Restores the buffer passes and the clock saved with `--save-state`.
+    #[tracing::instrument(skip(self))]
+    fn load_state(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let state = SavedState::read(path)?;
+        self.graph.restore_targets(&self.queue, &state.targets)?;
+        self.clock.resume_from(state.frame, state.time)?;
+        tracing::info!(
+            "Loaded {} buffers at frame {} from `{}`",
+            state.targets.len(),
+            state.frame,
+            path.display()
+        );
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn copy_frame(&mut self, frame: &wgpu::SurfaceTexture) {
+        let result = capture::read_texture(
+            &self.device,
+            &self.queue,
+            &frame.texture,
+            self.capture_encoding,
+        )
+        .and_then(|frame| Ok(self.clipboard.copy(&frame)?));
+        match result {
+            Ok(()) => tracing::info!("Frame copied to clipboard"),
+            Err(err) => tracing::error!("Failed to copy frame to clipboard: {err}"),
+        }
+    }
+
+    /// Saves `frame` as a PNG named by the UTC time to `snapshot_dir`, or
+    /// the working directory without `--snapshot-dir`.
+    #[tracing::instrument(skip_all)]
+    fn save_screenshot(&self, frame: &wgpu::SurfaceTexture) {
+        let path = self
+            .snapshot_dir
+            .clone()
+            .unwrap_or_default()
+            .join(format!("{}.png", capture::timestamp(SystemTime::now())));
+        let result = capture::read_texture(
+            &self.device,
+            &self.queue,
+            &frame.texture,
+            self.capture_encoding,
+        )
+        .and_then(|frame| Ok(capture::save_png(&frame, &path)?));
+        match result {
+            Ok(()) => tracing::info!("Saved screenshot `{}`", path.display()),
+            Err(err) => tracing::error!("Failed to save screenshot: {err}"),
+        }
+    }
+
+    /// Forwards an event of the window to the renderer: resizes the
+    /// surface on `Resized`, and handles the keyboard, mouse and wheel
+    /// input, modifiers, focus, moves, scale factor and theme changes.
+    /// Requests a redraw of the window when the event changes the frame.
+    /// `RedrawRequested` goes to [`AppState::redraw`] instead.
+    fn window_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::Resized(_)
+        | WindowEvent::KeyboardInput { .. }
+        | WindowEvent::CursorMoved { .. }
+        | WindowEvent::MouseInput { .. }
+        | WindowEvent::MouseWheel { .. } = event
+        {
+            self.changed();
+        }
+        match event {
+            WindowEvent::Resized(physical_size) => {
+                self.resize(*physical_size);
+                self.window.request_redraw();
+            }
+            WindowEvent::Moved(_) => self.update_refresh_rate(),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                tracing::debug!("Scale factor changed to {scale_factor}");
+                self.update_refresh_rate();
+                self.window.request_redraw();
+            }
+            WindowEvent::ThemeChanged(theme) => {
+                tracing::debug!("Theme changed to {theme:?}");
+                self.theme = Theme::from_window(Some(*theme));
+                self.window.request_redraw();
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::KeyboardInput { event, .. } => {
+                self.keyboard_input(event);
+                self.window.request_redraw();
+            }
+            // A grabbed cursor moves by the raw motion instead, see
+            // `device_event`.
+            WindowEvent::CursorMoved { position, .. } if !self.cursor_grabbed => {
+                self.cursor = *position;
+                self.window.request_redraw();
+            }
+            WindowEvent::Focused(false) if self.cursor_grabbed => self.grab_cursor(false),
+            WindowEvent::MouseInput {
+                state: button_state,
+                button,
+                ..
+            } => {
+                self.mouse_input(*button_state, *button);
+                self.window.request_redraw();
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.mouse_wheel(*delta);
+                self.window.request_redraw();
+            }
+            _ => { /* ignore */ }
+        }
+    }
+
+    /// Moves the grabbed cursor by the raw mouse motion, see `F9`.
+    fn device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (x, y) } = *event
+            && self.cursor_grabbed
+        {
+            self.cursor.x += x;
+            self.cursor.y += y;
+            self.changed();
+            self.window.request_redraw();
+        }
+    }
+
+    /// Updates the uniforms and renders a frame, unless the device was lost
+    /// or `--max-fps` holds the frame back.
+    fn redraw(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // Frames can't render until the renderer is rebuilt.
+        if self.lost.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if let Some(frame_cap) = &mut self.frame_cap
+            && !frame_cap.admit(Instant::now())
+        {
+            return Ok(());
+        }
+        if let Some(pacer) = &mut self.pacer {
+            pacer.begin(Instant::now());
+        }
+        self.update();
+        self.render()
+    }
+
+    /// Requests the redraws that are due once the window's events were
+    /// handled, and returns when to wake up for the next one, if anything
+    /// is scheduled. While idle, this is also when the shaders are checked
+    /// for changes.
+    fn wake_at(&mut self) -> Option<Instant> {
+        let mut wake_at = None;
+        if self.idle {
+            // Nothing requests redraws while idle, so wake up to check for
+            // shader changes.
+            wake_at = Some(Instant::now() + IDLE_POLL_INTERVAL);
+            if self.reload() {
+                self.window.request_redraw();
+            }
+        }
+        if let Some(title_fps) = &mut self.title_fps
+            && let Some(deadline) = title_fps.stall_deadline()
+        {
+            if Instant::now() >= deadline {
+                title_fps.reset();
+                self.window.set_title(&self.title);
+            } else {
+                wake_at = Some(wake_at.map_or(deadline, |wake_at: Instant| wake_at.min(deadline)));
+            }
+        }
+        if let Some(throttle) = &mut self.throttle {
+            if throttle.take_due(Instant::now()) {
+                self.window.request_redraw();
+            } else if let Some(next_frame) = throttle.next_frame() {
+                wake_at =
+                    Some(wake_at.map_or(next_frame, |wake_at: Instant| wake_at.min(next_frame)));
+            }
+        }
+        if let Some(pacer) = &mut self.pacer {
+            if pacer.take_due(Instant::now()) {
+                self.window.request_redraw();
+            } else if let Some(next_frame) = pacer.next_frame() {
+                wake_at =
+                    Some(wake_at.map_or(next_frame, |wake_at: Instant| wake_at.min(next_frame)));
+            }
+        }
+        if let Some(frame_cap) = &mut self.frame_cap {
+            if frame_cap.take_due(Instant::now()) {
+                self.window.request_redraw();
+            } else if let Some(next_frame) = frame_cap.next_frame() {
+                wake_at =
+                    Some(wake_at.map_or(next_frame, |wake_at: Instant| wake_at.min(next_frame)));
+            }
+        }
+        wake_at
+    }
+
+    /// The adapter the renderer runs on and the limits of its device.
+    fn gpu_info(&self) -> &GpuInfo {
+        &self.gpu_info
+    }
+
+    fn hud_lines(&self) -> Vec<String> {
+        let frame_time = self.frame_times.average().unwrap_or_default();
+        let fps = self.frame_times.rate().unwrap_or_default();
+        let mut lines = vec![
+            format!("{} ({:?})", self.gpu_info().name, self.gpu_info().backend),
+            format!("{}x{}", self.config.width, self.config.height),
+            format!("{fps:.0} fps ({:.2} ms)", frame_time.as_secs_f64() * 1000.0),
+            format!("GPU memory: {}", self.hud.gpu_memory()),
+            match self.refresh_rate {
+                Some(refresh_rate) => format!("Display: {refresh_rate:.1} Hz"),
+                None => "Display: unknown refresh rate".to_owned(),
+            },
+        ];
+        if let Some(accumulator) = &self.accumulator {
+            let (samples, max_samples) = accumulator.samples();
+            lines.push(format!("Samples: {samples}/{max_samples}"));
+        }
+        if let Some(pacer) = &self.pacer {
+            let (interval, cost) = pacer.timing();
+            lines.push(format!(
+                "Paced to {:.2} ms, frames take {:.2} ms",
+                interval.as_secs_f64() * 1000.0,
+                cost.unwrap_or_default().as_secs_f64() * 1000.0
+            ));
+        }
+        lines
+    }
+
+    /// Draws the queued overlay shapes on top of `view`.
+    #[tracing::instrument(skip_all)]
+    fn render_overlay(&mut self, view: &TextureView) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("overlay command encoder"),
+            });
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("overlay render pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        let screen = [self.config.width as f32, self.config.height as f32];
+        self.overlay
+            .draw(&self.device, &self.queue, &mut render_pass, screen);
+        drop(render_pass);
+
+        self.queue.submit([encoder.finish()]);
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn render(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.minimized {
+            return Ok(());
+        }
+        let start = Instant::now();
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(SurfaceError::Timeout) => {
+                tracing::warn!(
+                    "Timed out waiting for the next frame, the GPU may be stuck on an expensive shader"
+                );
+                self.window.request_redraw();
+                return Ok(());
+            }
+            // While the window is dragged to a new size, the surface can be
+            // outdated before `Resized` arrives, so it's reconfigured to the
+            // window's current size rather than the last one seen.
+            Err(err @ (SurfaceError::Outdated | SurfaceError::Lost)) => {
+                tracing::debug!("Reconfiguring surface: {err}");
+                self.resize(self.window.inner_size());
+                self.window.request_redraw();
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let view = frame.texture.create_view(&TextureViewDescriptor {
+            label: Some("view"),
+            ..Default::default()
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("command encoder"),
+            });
+
+        let scene_view = self.scene.create_view(&TextureViewDescriptor {
+            label: Some("scene view"),
+            ..Default::default()
+        });
+        // While paused, the scene keeps the last frame rather than running
+        // feedback passes again, and a finished mean needs no more frames.
+        let accumulated = self.accumulator.as_ref().is_some_and(Accumulator::is_done);
+        let scene_rendered = std::mem::take(&mut self.render_scene) && !accumulated;
+        if scene_rendered {
+            self.graph
+                .render(&mut encoder, &self.bind_group, &scene_view);
+            if let Some(comparison) = &mut self.comparison {
+                comparison.graph.render(
+                    &mut encoder,
+                    &self.bind_group,
+                    &comparison.scene.create_view(&Default::default()),
+                );
+            }
+            if let Some(accumulator) = &mut self.accumulator {
+                accumulator.accumulate(&self.queue, &mut encoder);
+                if accumulator.is_done() {
+                    tracing::info!("Accumulated {} samples", accumulator.samples().0);
+                }
+            }
+        }
+        let scene_bind_group = self
+            .accumulator
+            .as_ref()
+            .map_or(&self.scene_bind_group, Accumulator::display_bind_group);
+
+        let mut blit_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("blit pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(self.letterbox_color),
+                    store: StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        self.blit
+            .draw(&mut blit_pass, scene_bind_group, self.viewport);
+        drop(blit_pass);
+
+        let submission = self.queue.submit([encoder.finish()]);
+        if let Some(timeout) = self.frame_timeout {
+            let poll = self.device.poll(PollType::Wait {
+                submission_index: Some(submission),
+                timeout: Some(timeout),
+            });
+            if let Err(PollError::Timeout) = poll {
+                tracing::error!(
+                    "Frame took longer than {timeout:?}, switching to the fallback shader until \
+                     the next reload"
+                );
+                self.graph
+                    .use_fallback(&self.device, &self.bind_group_layout);
+                if let Some(comparison) = &mut self.comparison {
+                    comparison
+                        .graph
+                        .use_fallback(&self.device, &self.bind_group_layout);
+                }
+                self.reset_accumulation();
+            }
+        }
+        if std::mem::take(&mut self.copy_requested) {
+            self.copy_frame(&frame);
+        }
+        if std::mem::take(&mut self.screenshot_requested) {
+            self.save_screenshot(&frame);
+        }
+        if scene_rendered && std::mem::take(&mut self.snapshot_requested) {
+            self.save_snapshot();
+        }
+
+        if self.show_frame_graph {
+            self.frame_times
+                .draw_graph(&mut self.overlay, self.theme, 10.0, 10.0);
+        }
+        if self.hud.visible {
+            self.hud.update_gpu_memory(&self.device);
+            let lines = self.hud_lines();
+            self.hud.draw(
+                &mut self.overlay,
+                self.theme,
+                self.config.width as f32,
+                &lines,
+            );
+        }
+        let screen = [self.config.width as f32, self.config.height as f32];
+        let comparison_errors = self
+            .comparison
+            .iter()
+            .flat_map(|comparison| comparison.graph.errors());
+        let errors: Vec<_> = self.graph.errors().chain(comparison_errors).collect();
+        let has_errors = !errors.is_empty();
+        if has_errors {
+            hud::draw_errors(&mut self.overlay, self.theme, screen, &errors);
+        }
+        if self.show_help {
+            keys::draw_help(&mut self.overlay, self.theme, screen);
+        }
+        if self.editor.visible {
+            self.editor.draw(&mut self.overlay, screen);
+        }
+        if self.show_frame_graph
+            || self.hud.visible
+            || self.show_help
+            || self.editor.visible
+            || has_errors
+        {
+            self.render_overlay(&view);
+        }
+
+        frame.present();
+        let now = Instant::now();
+        self.events.send(Event::Rendered {
+            frame: self.clock.frame(),
+            time: self.clock.time().as_secs_f32(),
+            duration_ms: (now - start).as_secs_f64() * 1000.0,
+        });
+        self.frame_times.tick(now);
+        if let Some(average) = self.title_fps.as_mut().and_then(|title| title.tick(now)) {
+            self.window.set_title(&format!(
+                "{} — {:.0} fps ({:.1} ms)",
+                self.title,
+                average.as_secs_f64().recip(),
+                average.as_secs_f64() * 1000.0
+            ));
+        }
+        if let Some(pacer) = &mut self.pacer {
+            pacer.end(now);
+        }
+        if let Some(frame_limit) = &mut self.frame_limit {
+            frame_limit.tick(now);
+        }
+        if let Some(mode) = self.bench.as_mut().and_then(|bench| bench.tick(now)) {
+            tracing::info!("Switching to {mode:?}");
+            self.config.present_mode = mode;
+            self.surface.configure(&self.device, &self.config);
+        }
+        if !self.idle || self.is_animated() {
+            let throttled = !self.is_animated()
+                && self
+                    .throttle
+                    .as_mut()
+                    .is_some_and(|throttle| throttle.request(now));
+            match (&mut self.pacer, &mut self.frame_cap) {
+                _ if throttled => {}
+                (Some(pacer), _) => pacer.request(now),
+                (None, Some(frame_cap)) => frame_cap.request(now),
+                (None, None) => self.window.request_redraw(),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a seed drawn from OS-provided entropy.
+fn entropy_seed() -> u64 {
+    use std::hash::{BuildHasher, RandomState};
+
+    RandomState::new().hash_one(SystemTime::now())
+}
+
+fn log_seed(seed: u64) {
+    tracing::info!("Seed: {seed} (pass `--seed {seed}` to reproduce)");
+}
+
+/// Refresh rate in hertz of the monitor `window` is on, if the platform
+/// reports it.
+fn monitor_refresh_rate(window: &Window) -> Option<f64> {
+    window
+        .current_monitor()
+        .and_then(|monitor| monitor.refresh_rate_millihertz())
+        .map(|millihertz| f64::from(millihertz) / 1000.0)
+}
+
+fn log_refresh_rate(refresh_rate: Option<f64>) {
+    match refresh_rate {
+        Some(refresh_rate) => tracing::info!("Monitor refresh rate: {refresh_rate:.1} Hz"),
+        None => tracing::info!("Monitor refresh rate unknown"),
+    }
+}
+
+fn log_adjustment(adjustment: Adjustment) {
+    let Adjustment {
+        brightness,
+        contrast,
+        gamma,
+    } = adjustment;
+    tracing::info!(
+        "Output correction: --brightness {brightness:.2} --contrast {contrast:.2} --gamma {gamma:.2}"
+    );
+}
+
+/// `size` shrunk to the largest texture `device` supports, which the surface
+/// and the scene can't exceed. The compositor stretches a smaller surface
+/// over the window.
+fn fit_texture_limit(device: &Device, size: PhysicalSize<u32>) -> PhysicalSize<u32> {
+    let max = device.limits().max_texture_dimension_2d;
+    if size.width.max(size.height) <= max {
+        return size;
+    }
+    let fitted = PhysicalSize::new(size.width.min(max), size.height.min(max));
+    tracing::warn!(
+        "The window is {}x{}, the device supports textures up to {max}x{max}, rendering at {}x{}",
+        size.width,
+        size.height,
+        fitted.width,
+        fitted.height
+    );
+    fitted
+}
+
+/// Converts an sRGB-encoded color to the linear values expected when
+/// clearing an sRGB render target.
+fn srgb_to_linear(color: wgpu::Color) -> wgpu::Color {
+    let decode = |c: f64| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    wgpu::Color {
+        r: decode(color.r),
+        g: decode(color.g),
+        b: decode(color.b),
+        a: color.a,
+    }
+}
+
+/// Logical pixels of touchpad scrolling counted as one wheel notch.
+const PIXELS_PER_LINE: f64 = 20.0;
+
+/// How far `Left` and `Right` move shader time.
+const SCRUB_STEP: Duration = Duration::from_millis(250);
+/// How far `Shift+Left` and `Shift+Right` move shader time.
+const SCRUB_STEP_FAST: Duration = Duration::from_secs(2);
+
+/// How often shaders are checked for changes while idle.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Number of frames shown in the frame time graph.
+const FRAME_GRAPH_SAMPLES: usize = 120;
+
+/// Frame rate reported to shaders before the first frame was timed.
+const INITIAL_FRAME_RATE: f64 = 60.0;
+
+/// Delay before the second rebuild in a row after device losses, doubled
+/// for every further one.
+const RECOVERY_BACKOFF: Duration = Duration::from_secs(1);
+/// How long a rebuilt renderer has to run for a device loss not to count as
+/// its rebuild failing.
+const RECOVERY_STABLE: Duration = Duration::from_secs(30);
+/// Rebuilds in a row that can fail before giving up.
+const MAX_RECOVERIES: u32 = 5;
+
+/// Creates the viewer's own window, at `--pos` if it's on a monitor and
+/// centered otherwise.
+fn create_window(el: &ActiveEventLoop, args: &Args) -> Result<Window, OsError> {
+    let icon = load_icon(args.icon.as_deref())
+        .or_else(|err| {
+            tracing::warn!("Failed to load window icon: {err}. Using the built-in icon");
+            load_icon(None)
+        })
+        .ok();
+    let position = args.pos.filter(|&position| {
+        let on_screen = el.available_monitors().any(|monitor| {
+            let (origin, size) = (monitor.position(), monitor.size().cast::<i32>());
+            (origin.x..origin.x + size.width).contains(&position.x)
+                && (origin.y..origin.y + size.height).contains(&position.y)
+        });
+        if !on_screen {
+            tracing::warn!(
+                ?position,
+                "Window position is off-screen, centering instead"
+            );
+        }
+        on_screen
+    });
+    let mut attributes = Window::default_attributes()
+        .with_title(&args.title)
+        .with_window_icon(icon)
+        .with_transparent(
+            args.alpha_mode
+                .is_some_and(|mode| mode != AlphaMode::Opaque),
+        );
+    if let Some(position) = position {
+        attributes = attributes.with_position(position);
+    }
+    if let Some(size) = args.size {
+        attributes = attributes.with_inner_size(size);
+    }
+    let window = el.create_window(attributes)?;
+    if args.pos.is_some() && position.is_none() {
+        center_window(&window);
+    }
+    Ok(window)
+}
+
+/// Moves `window` to the center of the monitor it's on, or the primary
+/// monitor.
+fn center_window(window: &Window) {
+    let Some(monitor) = window
+        .current_monitor()
+        .or_else(|| window.primary_monitor())
+    else {
+        return;
+    };
+    let (origin, size) = (monitor.position(), monitor.size().cast::<i32>());
+    let window_size = window.outer_size().cast::<i32>();
+    window.set_outer_position(PhysicalPosition::new(
+        origin.x + (size.width - window_size.width) / 2,
+        origin.y + (size.height - window_size.height) / 2,
+    ));
+}
+
+const ICON: &[u8] = include_bytes!("../assets/icon.png");
+/// Shader run without a manifest or a shader on the command line.
+const SHADER_FILE: &str = "shader.wgsl";
+/// Shader drawn while the shader file doesn't exist, so a first run without
+/// any files shows something.
+const DEFAULT_SHADER: &str = include_str!("../assets/default.wgsl");
+
+#[tracing::instrument]
+fn load_icon(path: Option<&Path>) -> Result<Icon, Box<dyn std::error::Error>> {
+    let image = match path {
+        Some(path) => image::open(path)?,
+        None => image::load_from_memory(ICON)?,
+    }
+    .into_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(Icon::from_rgba(image.into_raw(), width, height)?)
+}
+
+impl ApplicationHandler<UserEvent> for App {
+    #[tracing::instrument(skip_all)]
+    fn resumed(&mut self, el: &ActiveEventLoop) {
+        let window = match create_window(el, &self.args) {
+            Ok(window) => Arc::new(window),
+            Err(err) => {
+                tracing::error!("Failed to create window: {err}");
+                el.exit();
+                return;
+            }
+        };
+        tracing::trace!("Window created");
+
+        let state = match pollster::block_on(AppState::new(
+            window,
+            &self.args,
+            Some(self.proxy.clone()),
+            self.events.clone(),
+        )) {
+            Ok(state) => state,
+            Err(err) => {
+                tracing::error!("Failed to init app: {err}");
+                el.exit();
+                return;
+            }
+        };
+        tracing::trace!("AppState initialized successfully");
+        self.state = Some(state);
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn window_event(&mut self, el: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let Some(state) = &mut self.state else { return };
+
+        match event {
+            WindowEvent::CloseRequested | WindowEvent::Destroyed => {
+                tracing::info!("Closing app");
+                el.exit();
+            }
+            WindowEvent::RedrawRequested => {
+                if let Err(e) = state.redraw() {
+                    if matches!(e.downcast_ref(), Some(SurfaceError::OutOfMemory)) {
+                        tracing::error!("Out of memory for the window's frames, exiting");
+                        el.exit();
+                        return;
+                    }
+                    tracing::error!("Render error: {}", e);
+                }
+                if let Some(bench) = state.bench.as_ref().filter(|bench| bench.is_done()) {
+                    print!("{}", bench.report());
+                    el.exit();
+                }
+                if let Some(frame_limit) =
+                    state.frame_limit.as_ref().filter(|limit| limit.is_done())
+                {
+                    print!("{}", frame_limit.report());
+                    el.exit();
+                }
+            }
+            event => state.window_event(&event),
+        }
+    }
+
+    fn device_event(&mut self, _: &ActiveEventLoop, _: DeviceId, event: DeviceEvent) {
+        if let Some(state) = &mut self.state {
+            state.device_event(&event);
+        }
+    }
+
+    fn about_to_wait(&mut self, el: &ActiveEventLoop) {
+        if self
+            .recovery
+            .retry_at
+            .is_some_and(|retry_at| Instant::now() >= retry_at)
+        {
+            self.rebuild(el);
+        }
+        if let Some(retry_at) = self.recovery.retry_at {
+            el.set_control_flow(ControlFlow::WaitUntil(retry_at));
+            return;
+        }
+        let Some(state) = &mut self.state else { return };
+        match state.wake_at() {
+            Some(wake_at) => el.set_control_flow(ControlFlow::WaitUntil(wake_at)),
+            None => el.set_control_flow(ControlFlow::Wait),
+        }
+    }
+
+    fn user_event(&mut self, el: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            UserEvent::Shutdown => el.exit(),
+            UserEvent::DeviceLost => {
+                if self
+                    .state
+                    .as_ref()
+                    .is_some_and(|state| state.lost.load(Ordering::SeqCst))
+                {
+                    self.recover(el);
+                }
+            }
+            UserEvent::Request(Message { request, reply }) => {
+                let answer = match &mut self.state {
+                    Some(state) => state.answer(request),
+                    None => Err("the renderer isn't running".to_owned()),
+                };
+                // The client may have disconnected while waiting.
+                let _ = reply.send(answer);
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn exiting(&mut self, _: &ActiveEventLoop) {
+        if let Some(state) = self.state.take() {
+            state.save_state();
+            state.shutdown();
+        }
+    }
+}
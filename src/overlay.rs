@@ -0,0 +1,418 @@
+use font8x8::legacy::BASIC_LEGACY;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer, BufferAddress,
+    BufferBindingType, BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites, Device,
+    Extent3d, FilterMode, FragmentState, MultisampleState, Origin3d, PipelineCompilationOptions,
+    PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, TexelCopyBufferLayout, TexelCopyTextureInfo, TextureAspect,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureViewDescriptor, TextureViewDimension, VertexAttribute, VertexBufferLayout, VertexFormat,
+    VertexState, VertexStepMode,
+};
+
+/// An RGBA color with components in `0.0..=1.0`.
+pub type Color = [f32; 4];
+
+/// Colors of overlay panels, following the desktop's light or dark mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Dark,
+    Light,
+    /// Used when the desktop doesn't report a preference.
+    #[default]
+    HighContrast,
+}
+
+impl Theme {
+    pub fn from_window(theme: Option<winit::window::Theme>) -> Self {
+        match theme {
+            Some(winit::window::Theme::Dark) => Self::Dark,
+            Some(winit::window::Theme::Light) => Self::Light,
+            None => Self::HighContrast,
+        }
+    }
+
+    /// Translucent background of panels.
+    pub fn background(self) -> Color {
+        match self {
+            Self::Dark => [0.1, 0.1, 0.1, 0.75],
+            Self::Light => [0.95, 0.95, 0.95, 0.8],
+            Self::HighContrast => [0.0, 0.0, 0.0, 0.9],
+        }
+    }
+
+    pub fn text(self) -> Color {
+        match self {
+            Self::Dark => [0.9, 0.9, 0.9, 1.0],
+            Self::Light => [0.1, 0.1, 0.1, 1.0],
+            Self::HighContrast => [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// Width and height of a glyph in the font atlas, in texels.
+pub const GLYPH_SIZE: u32 = 8;
+/// Glyphs per atlas row.
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_WIDTH: u32 = ATLAS_COLUMNS * GLYPH_SIZE;
+const ATLAS_HEIGHT: u32 = BASIC_LEGACY.len() as u32 / ATLAS_COLUMNS * GLYPH_SIZE;
+/// Atlas cell filled with solid texels, used for untextured shapes. The font
+/// has no glyph for the NUL character, so its cell is free.
+const SOLID_GLYPH: usize = 0;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: Color,
+}
+
+/// Screen-space overlay drawn on top of the shader output.
+///
+/// Shapes and text are queued in pixel coordinates (origin at the top left)
+/// during a frame and flushed by [`Overlay::draw`]. Text uses the built-in
+/// 8x8 bitmap font and is limited to ASCII.
+#[derive(Debug)]
+pub struct Overlay {
+    pipeline: RenderPipeline,
+    screen_buffer: Buffer,
+    bind_group: BindGroup,
+    vertex_buffer: Buffer,
+    vertices: Vec<Vertex>,
+}
+
+impl Overlay {
+    #[tracing::instrument(skip(device, queue))]
+    pub fn new(device: &Device, queue: &Queue, format: TextureFormat) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("overlay shader"),
+            source: ShaderSource::Wgsl(OVERLAY_SHADER.into()),
+        });
+
+        let screen_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("overlay screen buffer"),
+            size: size_of::<[f32; 2]>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let atlas = Self::create_font_atlas(device, queue);
+        let atlas_view = atlas.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("overlay sampler"),
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("overlay bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::default(),
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("overlay bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: screen_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&atlas_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("overlay pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("overlay pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[VertexBufferLayout {
+                    array_stride: size_of::<Vertex>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &[
+                        VertexAttribute {
+                            format: VertexFormat::Float32x2,
+                            offset: 0,
+                            shader_location: 0,
+                        },
+                        VertexAttribute {
+                            format: VertexFormat::Float32x2,
+                            offset: size_of::<[f32; 2]>() as BufferAddress,
+                            shader_location: 1,
+                        },
+                        VertexAttribute {
+                            format: VertexFormat::Float32x4,
+                            offset: size_of::<[f32; 4]>() as BufferAddress,
+                            shader_location: 2,
+                        },
+                    ],
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::default(),
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let vertex_buffer = Self::create_vertex_buffer(device, 0);
+
+        Self {
+            pipeline,
+            screen_buffer,
+            bind_group,
+            vertex_buffer,
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Rasterizes the ASCII half of the bitmap font into a single-channel
+    /// atlas texture.
+    fn create_font_atlas(device: &Device, queue: &Queue) -> wgpu::Texture {
+        let mut texels = vec![0u8; (ATLAS_WIDTH * ATLAS_HEIGHT) as usize];
+        for (index, glyph) in BASIC_LEGACY.iter().enumerate() {
+            let (column, row) = (index as u32 % ATLAS_COLUMNS, index as u32 / ATLAS_COLUMNS);
+            for (y, bits) in glyph.iter().enumerate() {
+                for x in 0..GLYPH_SIZE {
+                    let lit = index == SOLID_GLYPH || bits & (1 << x) != 0;
+                    let texel_x = column * GLYPH_SIZE + x;
+                    let texel_y = row * GLYPH_SIZE + y as u32;
+                    texels[(texel_y * ATLAS_WIDTH + texel_x) as usize] = if lit { 255 } else { 0 };
+                }
+            }
+        }
+
+        let size = Extent3d {
+            width: ATLAS_WIDTH,
+            height: ATLAS_HEIGHT,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("overlay font atlas"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &texels,
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(ATLAS_WIDTH),
+                rows_per_image: Some(ATLAS_HEIGHT),
+            },
+            size,
+        );
+        texture
+    }
+
+    fn create_vertex_buffer(device: &Device, vertices: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("overlay vertex buffer"),
+            size: (vertices.max(1) * size_of::<Vertex>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Atlas UV rectangle (`[u0, v0, u1, v1]`) of the glyph for `index`.
+    fn glyph_uv(index: usize) -> [f32; 4] {
+        let column = (index as u32 % ATLAS_COLUMNS * GLYPH_SIZE) as f32;
+        let row = (index as u32 / ATLAS_COLUMNS * GLYPH_SIZE) as f32;
+        let size = GLYPH_SIZE as f32;
+        [
+            column / ATLAS_WIDTH as f32,
+            row / ATLAS_HEIGHT as f32,
+            (column + size) / ATLAS_WIDTH as f32,
+            (row + size) / ATLAS_HEIGHT as f32,
+        ]
+    }
+
+    fn quad(&mut self, [x0, y0, x1, y1]: [f32; 4], [u0, v0, u1, v1]: [f32; 4], color: Color) {
+        self.vertices.extend(
+            [
+                ([x0, y0], [u0, v0]),
+                ([x0, y1], [u0, v1]),
+                ([x1, y0], [u1, v0]),
+                ([x1, y0], [u1, v0]),
+                ([x0, y1], [u0, v1]),
+                ([x1, y1], [u1, v1]),
+            ]
+            .map(|(position, uv)| Vertex {
+                position,
+                uv,
+                color,
+            }),
+        );
+    }
+
+    /// Queues a filled axis-aligned rectangle.
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        let [u0, v0, u1, v1] = Self::glyph_uv(SOLID_GLYPH);
+        // Sample the middle of the solid cell so filtering never bleeds in
+        // neighbouring glyphs.
+        let (u, v) = ((u0 + u1) / 2.0, (v0 + v1) / 2.0);
+        self.quad([x, y, x + width, y + height], [u, v, u, v], color);
+    }
+
+    /// Queues `text` with its top left corner at `(x, y)`, each glyph scaled
+    /// up by the integer factor `scale`. Newlines start a new line;
+    /// non-ASCII characters are drawn as `?`.
+    pub fn text(&mut self, x: f32, y: f32, scale: u32, color: Color, text: &str) {
+        let size = (GLYPH_SIZE * scale) as f32;
+        for (row, line) in text.lines().enumerate() {
+            let glyph_y = y + row as f32 * size;
+            for (column, ch) in line.chars().enumerate() {
+                if ch == ' ' {
+                    continue;
+                }
+                let index = if ch.is_ascii() {
+                    ch as usize
+                } else {
+                    '?' as usize
+                };
+                let glyph_x = x + column as f32 * size;
+                let rect = [glyph_x, glyph_y, glyph_x + size, glyph_y + size];
+                self.quad(rect, Self::glyph_uv(index), color);
+            }
+        }
+    }
+
+    /// Size in pixels of the box [`Overlay::text`] would fill for `text`.
+    pub fn text_size(scale: u32, text: &str) -> [f32; 2] {
+        let size = (GLYPH_SIZE * scale) as f32;
+        let columns = text.lines().map(|line| line.chars().count()).max();
+        let rows = text.lines().count();
+        [columns.unwrap_or(0) as f32 * size, rows as f32 * size]
+    }
+
+    /// Uploads the queued shapes and draws them into `render_pass`, clearing
+    /// the queue.
+    #[tracing::instrument(skip_all)]
+    pub fn draw(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        render_pass: &mut RenderPass<'_>,
+        screen: [f32; 2],
+    ) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let size = (self.vertices.len() * size_of::<Vertex>()) as BufferAddress;
+        if size > self.vertex_buffer.size() {
+            tracing::trace!(
+                vertices = self.vertices.len(),
+                "Growing overlay vertex buffer"
+            );
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertices.len() * 2);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::bytes_of(&screen));
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..size));
+        render_pass.draw(0..self.vertices.len() as u32, 0..1);
+        self.vertices.clear();
+    }
+}
+
+const OVERLAY_SHADER: &str = "
+@group(0) @binding(0)
+var<uniform> screen: vec2<f32>;
+
+@group(0) @binding(1)
+var atlas: texture_2d<f32>;
+
+@group(0) @binding(2)
+var atlas_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+) -> VertexOutput {
+    let ndc = position / screen * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+    return VertexOutput(vec4<f32>(ndc, 0.0, 1.0), uv, color);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas, atlas_sampler, in.uv).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
+";
@@ -0,0 +1,234 @@
+use std::time::{Duration, Instant};
+
+/// Refresh rate assumed when the monitor doesn't report one.
+const DEFAULT_REFRESH_RATE: f64 = 60.0;
+/// Weight of the newest frame in the moving average of frame costs.
+const COST_SMOOTHING: f64 = 0.1;
+
+/// Schedules redraws once per monitor refresh for `--low-latency`.
+///
+/// With `Mailbox`, presenting never blocks, so redrawing as soon as the last
+/// frame was presented renders frames the monitor never shows. Instead, each
+/// frame is started one refresh after the previous one, so every frame is
+/// displayed and samples its input as late as the refresh rate allows.
+#[derive(Debug)]
+pub struct FramePacer {
+    interval: Duration,
+    /// When the last frame started.
+    frame_start: Option<Instant>,
+    /// When the next frame should start, if one was requested.
+    next_frame: Option<Instant>,
+    /// Moving average of the time from the start of a frame to its present.
+    cost: Option<Duration>,
+}
+
+impl FramePacer {
+    /// Paces frames to a monitor refreshing at `refresh_rate` hertz, or at
+    /// 60 Hz if it's unknown.
+    pub fn new(refresh_rate: Option<f64>) -> Self {
+        let refresh_rate = refresh_rate.unwrap_or_else(|| {
+            tracing::warn!(
+                "The monitor's refresh rate is unknown, pacing frames at \
+                 {DEFAULT_REFRESH_RATE} Hz"
+            );
+            DEFAULT_REFRESH_RATE
+        });
+        tracing::info!("Pacing frames at {refresh_rate:.1} Hz");
+        Self {
+            interval: Duration::from_secs_f64(refresh_rate.recip()),
+            frame_start: None,
+            next_frame: None,
+            cost: None,
+        }
+    }
+
+    /// Records that a frame starts rendering at `now`.
+    pub fn begin(&mut self, now: Instant) {
+        self.frame_start = Some(now);
+        self.next_frame = None;
+    }
+
+    /// Records that the frame was presented at `now`.
+    pub fn end(&mut self, now: Instant) {
+        let Some(start) = self.frame_start else {
+            return;
+        };
+        let cost = now - start;
+        let average = self.cost.map_or(cost, |average| {
+            average.mul_f64(1.0 - COST_SMOOTHING) + cost.mul_f64(COST_SMOOTHING)
+        });
+        if average > self.interval && self.cost.is_none_or(|cost| cost <= self.interval) {
+            tracing::warn!(
+                "Frames take {:.2} ms, longer than a refresh ({:.2} ms)",
+                average.as_secs_f64() * 1000.0,
+                self.interval.as_secs_f64() * 1000.0
+            );
+        }
+        self.cost = Some(average);
+    }
+
+    /// Schedules a frame a refresh after the start of the last one, or right
+    /// away if that's already past.
+    pub fn request(&mut self, now: Instant) {
+        let next_frame = self
+            .frame_start
+            .map_or(now, |start| (start + self.interval).max(now));
+        self.next_frame = Some(next_frame);
+    }
+
+    /// When the next frame should start, if one was requested.
+    pub fn next_frame(&self) -> Option<Instant> {
+        self.next_frame
+    }
+
+    /// Takes the scheduled frame if it's due at `now`.
+    pub fn take_due(&mut self, now: Instant) -> bool {
+        let due = self.next_frame.is_some_and(|next_frame| next_frame <= now);
+        if due {
+            self.next_frame = None;
+        }
+        due
+    }
+
+    /// Refresh interval frames are paced to, and the average time a frame
+    /// takes to render and present.
+    pub fn timing(&self) -> (Duration, Option<Duration>) {
+        (self.interval, self.cost)
+    }
+}
+
+/// Limits the frame rate, see `--max-fps`.
+///
+/// Frames are started at least an interval apart, whether they were
+/// requested after the last frame or by input. A frame requested too early
+/// is scheduled for when the interval is over, so nothing waits in a loop.
+#[derive(Debug)]
+pub struct FrameCap {
+    interval: Duration,
+    /// When the last frame started.
+    frame_start: Option<Instant>,
+    /// When the next frame should start, if one was deferred.
+    next_frame: Option<Instant>,
+}
+
+impl FrameCap {
+    /// Limits frames to `fps` per second.
+    pub fn new(fps: f64) -> Self {
+        tracing::info!("Limiting frames to {fps} fps");
+        Self {
+            interval: Duration::from_secs_f64(fps.recip()),
+            frame_start: None,
+            next_frame: None,
+        }
+    }
+
+    /// Whether a frame can start at `now`, recording its start if it can and
+    /// scheduling it for later if not.
+    pub fn admit(&mut self, now: Instant) -> bool {
+        let earliest = self.frame_start.map_or(now, |start| start + self.interval);
+        if earliest > now {
+            self.next_frame = Some(earliest);
+            return false;
+        }
+        self.frame_start = Some(now);
+        self.next_frame = None;
+        true
+    }
+
+    /// Schedules a frame an interval after the start of the last one, or
+    /// right away if that's already past.
+    pub fn request(&mut self, now: Instant) {
+        let next_frame = self
+            .frame_start
+            .map_or(now, |start| (start + self.interval).max(now));
+        self.next_frame = Some(next_frame);
+    }
+
+    /// When the next frame should start, if one was scheduled.
+    pub fn next_frame(&self) -> Option<Instant> {
+        self.next_frame
+    }
+
+    /// Takes the scheduled frame if it's due at `now`.
+    pub fn take_due(&mut self, now: Instant) -> bool {
+        let due = self.next_frame.is_some_and(|next_frame| next_frame <= now);
+        if due {
+            self.next_frame = None;
+        }
+        due
+    }
+}
+
+/// Lowers the frame rate while nothing changes, see `--throttle-after`.
+///
+/// Without `--idle`, frames are rendered back to back even when the output
+/// is static. Once the shader doesn't animate and no input or reload was
+/// seen for a while, frames are instead rendered at a low rate, which still
+/// picks up changes the window isn't told about, until the next change
+/// restores the full rate.
+#[derive(Debug)]
+pub struct Throttle {
+    /// How long the output has to be static before throttling.
+    after: Duration,
+    interval: Duration,
+    /// When the last input or reload happened.
+    last_change: Instant,
+    throttled: bool,
+    /// When the next throttled frame should start, if one was requested.
+    next_frame: Option<Instant>,
+}
+
+impl Throttle {
+    /// Throttles to `fps` frames per second once nothing changed for
+    /// `after`.
+    pub fn new(after: Duration, fps: f64) -> Self {
+        Self {
+            after,
+            interval: Duration::from_secs_f64(fps.recip()),
+            last_change: Instant::now(),
+            throttled: false,
+            next_frame: None,
+        }
+    }
+
+    /// Records an input or reload at `now`, returning to the full rate.
+    pub fn change(&mut self, now: Instant) {
+        self.last_change = now;
+        self.next_frame = None;
+        if std::mem::take(&mut self.throttled) {
+            tracing::debug!("Output changed, rendering at the full rate");
+        }
+    }
+
+    /// Schedules the next frame of a static output at `now` if it has been
+    /// static for long enough. Returns whether it did, or else the frame
+    /// should be requested right away.
+    pub fn request(&mut self, now: Instant) -> bool {
+        if now - self.last_change < self.after {
+            return false;
+        }
+        if !std::mem::replace(&mut self.throttled, true) {
+            tracing::debug!(
+                "Output static for {:.1?}, rendering at {:.1} fps",
+                self.after,
+                self.interval.as_secs_f64().recip()
+            );
+        }
+        self.next_frame = Some(now + self.interval);
+        true
+    }
+
+    /// When the next throttled frame should start, if one was requested.
+    pub fn next_frame(&self) -> Option<Instant> {
+        self.next_frame
+    }
+
+    /// Takes the scheduled frame if it's due at `now`.
+    pub fn take_due(&mut self, now: Instant) -> bool {
+        let due = self.next_frame.is_some_and(|next_frame| next_frame <= now);
+        if due {
+            self.next_frame = None;
+        }
+        due
+    }
+}
@@ -0,0 +1,252 @@
+use serde::Deserialize;
+use wgpu::{
+    BindGroupLayout, BlendComponent, BlendFactor, BlendOperation, BlendState, Color,
+    ColorTargetState, ColorWrites, Device, FragmentState, MultisampleState,
+    PipelineCompilationOptions, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, TextureFormat,
+    VertexState,
+};
+
+use crate::{
+    dump::{self, Dump},
+    preprocess::Preprocessed,
+    reflect,
+};
+
+/// Shader modules every pipeline of a graph is built from.
+#[derive(Debug, Clone, Copy)]
+pub struct Modules<'a> {
+    /// Covers the target, see [`create_vertex_shader`].
+    pub vertex: &'a ShaderModule,
+    /// Used if the fragment shader is missing or fails validation.
+    pub fallback: &'a ShaderModule,
+}
+
+/// Builds the full-screen pipeline for `fragment`, rendering into `targets`
+/// by location, falling back to `modules.fallback` if the source is missing
+/// or fails validation.
+///
+/// The source is written to `dump` before it's compiled, and to the
+/// temporary directory if it fails to compile without a `dump`. Returns the
+/// pipeline and the error `fragment` failed to compile with, if it did.
+#[tracing::instrument(skip(device, modules, fragment, bind_group_layouts, dump))]
+pub fn create_pipeline(
+    device: &Device,
+    label: &str,
+    targets: &[Option<ColorTargetState>],
+    modules: Modules,
+    fragment: Option<&Preprocessed>,
+    bind_group_layouts: &[&BindGroupLayout],
+    dump: Option<&Dump>,
+) -> (RenderPipeline, Option<String>) {
+    if let (Some(dump), Some(fragment)) = (dump, fragment) {
+        dump.write(&fragment.source);
+    }
+
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts,
+        immediate_size: 0,
+    });
+
+    let create_render_pipeline = |fragment_shader| {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: modules.vertex,
+                entry_point: None,
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &fragment_shader,
+                entry_point: None,
+                compilation_options: PipelineCompilationOptions::default(),
+                targets,
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        })
+    };
+
+    let error_scope_guard = device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let fallback = || {
+        tracing::warn!("Using initial fragment shader");
+        modules.fallback.clone()
+    };
+    let t = create_render_pipeline(fragment.map_or_else(fallback, |fragment| {
+        tracing::debug!("Fragment shader module created successfully");
+        device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(label),
+            source: ShaderSource::Wgsl(fragment.source.as_str().into()),
+        })
+    }));
+    let ef = error_scope_guard.pop();
+    pollster::block_on(ef).map_or_else(
+        || (t, None),
+        |error| {
+            let error = fragment.map_or_else(
+                || error.to_string(),
+                |fragment| {
+                    let error =
+                        reflect::diagnose(&fragment.source).unwrap_or_else(|| error.to_string());
+                    reflect::explain_error(
+                        &fragment.source,
+                        device.features(),
+                        fragment.remap_error(&error),
+                    )
+                },
+            );
+            tracing::error!("Fragment shader module creation failed: {error}");
+            if let (None, Some(fragment)) = (dump, fragment) {
+                dump::write_failed(label, &fragment.source);
+            }
+            (create_render_pipeline(fallback()), Some(error))
+        },
+    )
+}
+
+/// How a pass combines its output with the contents of its target, see
+/// [`PassManifest::blend`](crate::graph::PassManifest::blend).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Blend {
+    /// Straight alpha: `src * a + dst * (1 - a)`.
+    Alpha,
+    /// `src + dst`, for glows and light accumulation.
+    Additive,
+    /// Premultiplied alpha: `src + dst * (1 - a)`.
+    Premultiplied,
+}
+
+impl From<Blend> for BlendState {
+    fn from(blend: Blend) -> Self {
+        match blend {
+            Blend::Alpha => Self::ALPHA_BLENDING,
+            Blend::Additive => {
+                let add = BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                };
+                Self {
+                    color: add,
+                    alpha: add,
+                }
+            }
+            Blend::Premultiplied => Self::PREMULTIPLIED_ALPHA_BLENDING,
+        }
+    }
+}
+
+/// Color target of `format`, blended with `blend` if given.
+pub fn color_target(format: TextureFormat, blend: Option<Blend>) -> Option<ColorTargetState> {
+    Some(ColorTargetState {
+        format,
+        blend: blend.map(BlendState::from),
+        write_mask: ColorWrites::default(),
+    })
+}
+
+/// Geometry covering the target of every pass, see
+/// `--fullscreen-geometry`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Geometry {
+    /// One triangle larger than the target, clipped to it.
+    #[default]
+    Triangle,
+    /// Two triangles exactly covering the target.
+    Quad,
+}
+
+impl Geometry {
+    /// Vertices to draw with the module of [`create_vertex_shader`].
+    pub fn vertex_count(self) -> u32 {
+        match self {
+            Self::Triangle => 3,
+            Self::Quad => 6,
+        }
+    }
+}
+
+/// Creates the module drawing `geometry`. Both cover the target, so
+/// fragments see the same positions either way.
+pub fn create_vertex_shader(device: &Device, geometry: Geometry) -> ShaderModule {
+    device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("vertex shader"),
+        source: ShaderSource::Wgsl(
+            match geometry {
+                Geometry::Triangle => TRIANGLE_VERTEX_SHADER,
+                Geometry::Quad => QUAD_VERTEX_SHADER,
+            }
+            .into(),
+        ),
+    })
+}
+
+/// Creates the module used until a shader has been loaded, and whenever the
+/// loaded shader fails to compile.
+pub fn create_fallback_shader(device: &Device) -> ShaderModule {
+    device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("initial fragment shader"),
+        source: ShaderSource::Wgsl(INITIAL_FRAGMENT_SHADER.into()),
+    })
+}
+
+/// Creates a module filling the target with `color`, shown instead of the
+/// fallback shader until a pass's shader is first read, see `--initial`.
+pub fn create_solid_shader(device: &Device, color: Color) -> ShaderModule {
+    let Color { r, g, b, a } = color;
+    device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("initial fragment shader"),
+        source: ShaderSource::Wgsl(
+            format!(
+                "
+@fragment
+fn main(@builtin(position) p: vec4<f32>) -> @location(0) vec4<f32> {{
+    return vec4({r:?}, {g:?}, {b:?}, {a:?});
+}}
+"
+            )
+            .into(),
+        ),
+    })
+}
+
+const TRIANGLE_VERTEX_SHADER: &str = "
+@vertex
+fn main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let vert = array(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+    );
+    return vec4<f32>(vert[vertex_index] * 4 - 1, 0.0, 1.0);
+}
+";
+
+const QUAD_VERTEX_SHADER: &str = "
+@vertex
+fn main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let vert = array(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    return vec4<f32>(vert[vertex_index] * 2 - 1, 0.0, 1.0);
+}
+";
+
+const INITIAL_FRAGMENT_SHADER: &str = "
+@fragment
+fn main(@builtin(position) p: vec4<f32>) -> @location(0) vec4<f32> {
+    return vec4(1.0, 0.0, 1.0, 1.0);
+}
+";
@@ -0,0 +1,74 @@
+/// Fragment source ready to compile, after the prelude was prepended.
+#[derive(Debug, Clone)]
+pub struct Preprocessed {
+    pub source: String,
+    /// Number of lines inserted before the user's source.
+    line_offset: usize,
+}
+
+/// Prepends `prelude` to `source`.
+pub fn preprocess(prelude: Option<&str>, source: &str) -> Preprocessed {
+    let Some(prelude) = prelude else {
+        return Preprocessed {
+            source: source.to_owned(),
+            line_offset: 0,
+        };
+    };
+    let mut combined = String::with_capacity(prelude.len() + source.len() + 1);
+    combined.push_str(prelude);
+    if !prelude.is_empty() && !prelude.ends_with('\n') {
+        combined.push('\n');
+    }
+    combined.push_str(source);
+    Preprocessed {
+        source: combined,
+        line_offset: prelude.lines().count(),
+    }
+}
+
+impl Preprocessed {
+    /// Rewrites the line numbers in a compile error so they point into the
+    /// user's file instead of the combined source. Lines inside the prelude
+    /// are reported as `prelude:LINE`.
+    pub fn remap_error(&self, message: &str) -> String {
+        if self.line_offset == 0 {
+            return message.to_owned();
+        }
+        message
+            .lines()
+            .map(|line| {
+                self.remap_location(line)
+                    .unwrap_or_else(|| self.remap_gutter(line))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Rewrites `wgsl:LINE:COLUMN` locations.
+    fn remap_location(&self, line: &str) -> Option<String> {
+        let start = line.find("wgsl:")?;
+        let rest = &line[start + "wgsl:".len()..];
+        let (number, rest) = rest.split_once(':')?;
+        let number: usize = number.parse().ok()?;
+        let location = match number.checked_sub(self.line_offset) {
+            Some(number) if number > 0 => format!("wgsl:{number}"),
+            _ => format!("prelude:{number}"),
+        };
+        Some(format!("{}{location}:{rest}", &line[..start]))
+    }
+
+    /// Rewrites the line numbers in the `LINE │ code` source snippets.
+    fn remap_gutter(&self, line: &str) -> String {
+        let Some((gutter, code)) = line.split_once(" │") else {
+            return line.to_owned();
+        };
+        let Ok(number) = gutter.trim().parse::<usize>() else {
+            return line.to_owned();
+        };
+        let number = match number.checked_sub(self.line_offset) {
+            Some(number) if number > 0 => number.to_string(),
+            _ => format!("prelude:{number}"),
+        };
+        format!("{number:>width$} │{code}", width = gutter.len())
+    }
+}
@@ -0,0 +1,555 @@
+use std::{
+    borrow::Cow,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use color_quant::NeuQuant;
+use gif::{Encoder, Frame, Repeat};
+use serde_json::json;
+use wgpu::{
+    BindGroup, Buffer, CommandEncoderDescriptor, Device, Extent3d, FilterMode, Queue, Texture,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    AppState,
+    accumulate::Accumulator,
+    blit::Blit,
+    capture::{self, Encoding, Frame as Image},
+    cli::{Args, GifPalette},
+    clock::Clock,
+    device, entropy_seed,
+    graph::RenderGraph,
+    keyboard::Keyboard,
+    load_manifest, log_seed,
+    uniforms::{self, Inputs, Layout, UniformProvider},
+};
+
+/// Size of GIFs and PNGs without `--size` or `--internal`.
+const DEFAULT_SIZE: PhysicalSize<u32> = PhysicalSize::new(480, 270);
+/// Shortest frame delay in hundredths of a second that browsers play as
+/// written. Shorter delays are slowed down to a tenth of a second.
+const MIN_DELAY: u16 = 2;
+/// Extensions `--sequence` encodes to a video rather than a directory.
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "mov", "webm"];
+/// How often `--sequence` logs its progress.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The render graph drawing into a texture instead of a window, with the
+/// uniforms it reads, for recording without a window.
+struct Offscreen {
+    device: Device,
+    queue: Queue,
+    graph: RenderGraph,
+    uniforms: Vec<Box<dyn UniformProvider>>,
+    uniform_layout: Layout,
+    uniform_data: Vec<u8>,
+    buffer: Buffer,
+    bind_group: BindGroup,
+    target: Texture,
+    view: TextureView,
+    /// Averages the samples of each frame, see `--motion-blur-samples`.
+    accumulator: Option<Accumulator>,
+    size: PhysicalSize<u32>,
+    seed: u64,
+    /// Wall-clock time the date uniform counts from.
+    started: SystemTime,
+    encoding: Encoding,
+    flip_y: bool,
+}
+
+impl Offscreen {
+    /// Loads the shaders of `args` on a headless device, failing if any pass
+    /// doesn't compile. With more than one of `samples` per frame, frames are
+    /// averaged in linear colors.
+    fn new(
+        args: &Args,
+        size: PhysicalSize<u32>,
+        samples: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (adapter, device, queue) = pollster::block_on(device::headless(args))?;
+        let manifest = load_manifest(args)?;
+        let uniforms = uniforms::defaults(args);
+        let alignment = u64::from(device.limits().min_uniform_buffer_offset_alignment);
+        let uniform_layout = Layout::new(alignment, &uniforms);
+        let storage_buffer = manifest
+            .storage_size
+            .map(|size| AppState::create_storage_buffer(&adapter, &device, size))
+            .transpose()?;
+        let keyboard = Keyboard::new(&device);
+        let (buffer, bind_group_layout, bind_group) = AppState::create_bindings(
+            &device,
+            &uniform_layout,
+            storage_buffer.as_ref(),
+            &keyboard.texture().create_view(&Default::default()),
+        );
+
+        let format = TextureFormat::Rgba8UnormSrgb;
+        // Samples are averaged in linear colors, so the graph renders them to
+        // a float scene rather than the 8-bit frame.
+        let scene_format = if samples > 1 {
+            TextureFormat::Rgba16Float
+        } else {
+            format
+        };
+        let graph = RenderGraph::new(
+            &device,
+            &queue,
+            &manifest,
+            scene_format,
+            &bind_group_layout,
+            None,
+            size,
+        )?;
+        if let Some((name, shader, _)) = graph.pass_status().find(|(_, _, compiled)| !compiled) {
+            return Err(format!(
+                "Pass `{name}` failed to compile, see `{}`",
+                shader.display()
+            )
+            .into());
+        }
+        let target = device.create_texture(&TextureDescriptor {
+            label: Some("offscreen frame"),
+            size: Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: scene_format,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&Default::default());
+        let accumulator = (samples > 1).then(|| {
+            let blit = Blit::new(&device, format, FilterMode::Nearest);
+            Accumulator::new(&adapter, &device, format, &view, size, &blit, samples)
+        });
+
+        let seed = args.seed.unwrap_or_else(entropy_seed);
+        log_seed(seed);
+        Ok(Self {
+            uniform_data: vec![0; uniform_layout.size() as usize],
+            device,
+            queue,
+            graph,
+            uniforms,
+            uniform_layout,
+            buffer,
+            bind_group,
+            target,
+            view,
+            accumulator,
+            size,
+            seed,
+            // The date follows the shader's time from when the recording
+            // started, so it advances by the same fixed step.
+            started: SystemTime::now(),
+            encoding: args.capture_encoding.into(),
+            flip_y: args.flip_y,
+        })
+    }
+
+    /// Starts averaging the samples of a new frame.
+    fn start_frame(&mut self) {
+        if let Some(accumulator) = &mut self.accumulator {
+            accumulator.reset();
+        }
+    }
+
+    /// Renders the shader at the time of `clock`, adding the result to the
+    /// current frame, and returns the uniforms it was rendered with.
+    fn render(&mut self, clock: &Clock, frame_rate: f64) -> Inputs {
+        self.graph
+            .advance_flipbooks(&self.queue, clock.time().as_secs_f64());
+        let inputs = Inputs {
+            time: clock.time().as_secs_f32(),
+            delta: clock.delta().as_secs_f32(),
+            real_time: clock.time().as_secs_f32(),
+            frame: clock.frame(),
+            frame_rate: frame_rate as f32,
+            scale_factor: 1.0,
+            resolution: self.size.cast::<f32>().into(),
+            seed: self.seed,
+            mouse: [0.0; 4],
+            scroll: 0.0,
+            refresh_rate: 0.0,
+            date: uniforms::date(self.started + clock.time()),
+        };
+        for provider in &mut self.uniforms {
+            provider.update(&inputs);
+            provider.write(&mut self.uniform_data, &self.uniform_layout);
+        }
+        self.queue.write_buffer(&self.buffer, 0, &self.uniform_data);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("offscreen frame encoder"),
+            });
+        self.graph
+            .render(&mut encoder, &self.bind_group, &self.view);
+        if let Some(accumulator) = &mut self.accumulator {
+            accumulator.accumulate(&self.queue, &mut encoder);
+        }
+        self.queue.submit([encoder.finish()]);
+        inputs
+    }
+
+    /// Reads back the current frame, flipped if `--flip-y` is set.
+    fn read(&self) -> Result<Image, Box<dyn std::error::Error>> {
+        let frame = self
+            .accumulator
+            .as_ref()
+            .map_or(&self.target, Accumulator::display);
+        let mut image = capture::read_texture(&self.device, &self.queue, frame, self.encoding)?;
+        if self.flip_y {
+            image.flip_rows();
+        }
+        Ok(image)
+    }
+}
+
+/// Size of recordings: `--size`, `--internal` or [`DEFAULT_SIZE`].
+fn recording_size(args: &Args) -> PhysicalSize<u32> {
+    args.size.or(args.internal).unwrap_or(DEFAULT_SIZE)
+}
+
+/// Renders the single frame at `--time` without a window and writes it to
+/// a PNG at `path`, see `--render`.
+///
+/// Passes reading their previous frame see empty buffers, since no frame
+/// was rendered before.
+#[tracing::instrument(skip(args))]
+pub fn render_png(args: &Args, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let size = recording_size(args);
+    let mut offscreen = Offscreen::new(args, size, 1)?;
+    let mut clock = Clock::new(Some(args.fps));
+    clock.seek(Duration::try_from_secs_f64(args.time)?)?;
+    tracing::info!(
+        "Rendering {}x{} at {:.3}s",
+        size.width,
+        size.height,
+        args.time
+    );
+    offscreen.render(&clock, args.fps);
+    capture::save_png(&offscreen.read()?, path)?;
+    tracing::info!("Wrote `{}`", path.display());
+    Ok(())
+}
+
+/// Renders `--duration` seconds of the shader without a window and writes
+/// them to a looping GIF at `path`, see `--gif`.
+///
+/// Time advances by a fixed step like with `--deterministic`, so the GIF
+/// doesn't depend on how fast the frames render.
+#[tracing::instrument(skip(args))]
+pub fn record_gif(args: &Args, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    // GIF frame delays are whole hundredths of a second, so the frame rate
+    // is rounded to one that can be played back exactly.
+    let delay = ((100.0 / args.fps).round() as u16).max(MIN_DELAY);
+    let fps = 100.0 / f64::from(delay);
+    if (fps - args.fps).abs() > 0.01 {
+        tracing::info!(
+            "GIFs can't play at {} fps, recording at {fps} fps",
+            args.fps
+        );
+    }
+    let frames = (args.duration * fps).round().max(1.0) as u32;
+    let size = recording_size(args);
+    let (Ok(width), Ok(height)) = (u16::try_from(size.width), u16::try_from(size.height)) else {
+        return Err(format!(
+            "GIFs can't be larger than 65535x65535, got {}x{}",
+            size.width, size.height
+        )
+        .into());
+    };
+
+    let samples = args.motion_blur_samples.unwrap_or(1);
+    let mut offscreen = Offscreen::new(args, size, samples)?;
+    tracing::info!("Recording {frames} frames of {width}x{height} at {fps} fps");
+    if samples > 1 {
+        tracing::info!("Averaging {samples} samples per frame");
+    }
+    // Samples are spread evenly over each frame's duration, so the clock
+    // steps by a fraction of a frame.
+    let mut clock = Clock::new(Some(fps * f64::from(samples)));
+    let mut images = Vec::with_capacity(frames as usize);
+    let mut metadata = Vec::new();
+    for index in 0..frames {
+        offscreen.start_frame();
+        let mut frame_samples = Vec::new();
+        for _ in 0..samples {
+            clock.tick();
+            let inputs = offscreen.render(&clock, fps);
+            if args.gif_metadata.is_some() {
+                frame_samples.push(inputs);
+            }
+        }
+        images.push(offscreen.read()?);
+        if args.gif_metadata.is_some() {
+            metadata.push(json!({ "index": index, "samples": frame_samples }));
+        }
+        tracing::debug!("Rendered frame {}", clock.frame());
+    }
+    if let Some(metadata_path) = &args.gif_metadata {
+        let metadata = json!({
+            "gif": path.display().to_string(),
+            "width": width,
+            "height": height,
+            "fps": fps,
+            "delay_ms": u32::from(delay) * 10,
+            "samples_per_frame": samples,
+            "seed": offscreen.seed,
+            "frames": metadata,
+        });
+        let mut file = BufWriter::new(File::create(metadata_path)?);
+        serde_json::to_writer_pretty(&mut file, &metadata)?;
+        file.flush()?;
+        tracing::info!("Wrote metadata `{}`", metadata_path.display());
+    }
+
+    // NeuQuant samples every `speed`th pixel: 1 is the slowest and most
+    // accurate, 30 the fastest.
+    let speed = 31 - 3 * i32::from(args.gif_quality);
+    write_gif(
+        path,
+        width,
+        height,
+        delay,
+        &mut images,
+        args.gif_palette,
+        speed,
+    )?;
+    let written = fs::metadata(path)?.len();
+    tracing::info!(
+        "Wrote `{}` ({} frames, {:.1?}, {} KiB)",
+        path.display(),
+        frames,
+        Duration::from_secs_f64(f64::from(frames) / fps),
+        written / 1024
+    );
+    Ok(())
+}
+
+/// Renders `--duration` seconds of the shader at `--fps` without a window
+/// and writes every frame to `path`, see `--sequence`.
+///
+/// Time comes from the frame index like with `--deterministic`, so every
+/// run writes the same frames. Ctrl+C stops after the current frame and
+/// finishes the frames written so far.
+#[tracing::instrument(skip(args))]
+pub fn record_sequence(args: &Args, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let frames = (args.duration * args.fps).round().max(1.0) as u32;
+    let size = recording_size(args);
+    let samples = args.motion_blur_samples.unwrap_or(1);
+    let mut offscreen = Offscreen::new(args, size, samples)?;
+    let mut sink = Sink::new(path, size, args.fps)?;
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let handler_cancelled = cancelled.clone();
+    ctrlc::set_handler(move || {
+        if handler_cancelled.swap(true, Ordering::SeqCst) {
+            tracing::warn!("Forced exit");
+            std::process::exit(130);
+        }
+        tracing::info!("Interrupted, finishing the frames written so far...");
+    })?;
+
+    tracing::info!(
+        "Exporting {frames} frames of {}x{} at {} fps",
+        size.width,
+        size.height,
+        args.fps
+    );
+    if samples > 1 {
+        tracing::info!("Averaging {samples} samples per frame");
+    }
+    let started = Instant::now();
+    let mut reported = started;
+    let mut clock = Clock::new(Some(args.fps * f64::from(samples)));
+    let mut written = 0;
+    while written < frames && !cancelled.load(Ordering::SeqCst) {
+        offscreen.start_frame();
+        for _ in 0..samples {
+            clock.tick();
+            offscreen.render(&clock, args.fps);
+        }
+        sink.write(written, &offscreen.read()?)?;
+        written += 1;
+        if reported.elapsed() >= PROGRESS_INTERVAL {
+            reported = Instant::now();
+            tracing::info!("Exported {written}/{frames} frames");
+        }
+    }
+    sink.finish()?;
+    if written < frames {
+        tracing::warn!(
+            "Wrote `{}` with {written} of {frames} frames",
+            path.display()
+        );
+    } else {
+        tracing::info!(
+            "Wrote `{}` ({frames} frames in {:.1?})",
+            path.display(),
+            started.elapsed()
+        );
+    }
+    Ok(())
+}
+
+/// Where `--sequence` writes its frames.
+enum Sink {
+    /// Numbered PNGs in a directory.
+    Images(PathBuf),
+    /// Raw RGBA frames piped into `ffmpeg`.
+    Video(Child),
+}
+
+impl Sink {
+    /// A video encoder if `path` has a video extension, otherwise the
+    /// directory at `path`, created if it doesn't exist.
+    fn new(
+        path: &Path,
+        size: PhysicalSize<u32>,
+        fps: f64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let is_video = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        if !is_video {
+            fs::create_dir_all(path)
+                .map_err(|err| format!("Failed to create `{}`: {err}", path.display()))?;
+            return Ok(Self::Images(path.to_owned()));
+        }
+
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-video_size", &format!("{}x{}", size.width, size.height)])
+            .args(["-framerate", &fps.to_string(), "-i", "-"])
+            // Most players only decode 4:2:0 video, which needs an even size.
+            .args([
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                "-pix_fmt",
+                "yuv420p",
+            ])
+            .arg(path)
+            .stdin(Stdio::piped());
+        // Ctrl+C goes to the whole process group, and ffmpeg should only
+        // stop once it was sent every frame.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        match command.spawn() {
+            Ok(child) => Ok(Self::Video(child)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(format!(
+                "Encoding `{}` needs `ffmpeg` on the PATH, pass a directory to write PNGs instead",
+                path.display()
+            )
+            .into()),
+            Err(err) => Err(format!("Failed to start `ffmpeg`: {err}").into()),
+        }
+    }
+
+    fn write(&mut self, index: u32, image: &Image) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Images(dir) => capture::save_png(image, &dir.join(format!("{index:05}.png")))?,
+            Self::Video(child) => child
+                .stdin
+                .as_mut()
+                .ok_or("`ffmpeg` closed its input")?
+                .write_all(&image.rgba)
+                .map_err(|err| format!("Failed to send a frame to `ffmpeg`: {err}"))?,
+        }
+        Ok(())
+    }
+
+    /// Waits for `ffmpeg` to encode the frames it was sent.
+    fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Self::Video(mut child) = self {
+            drop(child.stdin.take());
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(format!("`ffmpeg` failed with {status}").into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Quantizes `images` to 256 colors each and writes them as a looping GIF.
+/// GIF transparency is all or nothing, so the alpha channel is ignored.
+fn write_gif(
+    path: &Path,
+    width: u16,
+    height: u16,
+    delay: u16,
+    images: &mut [Image],
+    palette: GifPalette,
+    speed: i32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for image in images.iter_mut() {
+        for pixel in image.rgba.chunks_exact_mut(4) {
+            pixel[3] = u8::MAX;
+        }
+    }
+    let quantizer = match palette {
+        GifPalette::Global => {
+            let pixels: Vec<u8> = images
+                .iter()
+                .flat_map(|image| image.rgba.iter().copied())
+                .collect();
+            Some(NeuQuant::new(speed, 256, &pixels))
+        }
+        GifPalette::PerFrame => None,
+    };
+    let global_palette = quantizer
+        .as_ref()
+        .map(NeuQuant::color_map_rgb)
+        .unwrap_or_default();
+
+    let mut encoder = Encoder::new(
+        BufWriter::new(File::create(path)?),
+        width,
+        height,
+        &global_palette,
+    )?;
+    encoder.set_repeat(Repeat::Infinite)?;
+    for image in images {
+        let mut frame = match &quantizer {
+            Some(quantizer) => Frame {
+                width,
+                height,
+                buffer: Cow::Owned(
+                    image
+                        .rgba
+                        .chunks_exact(4)
+                        .map(|pixel| quantizer.index_of(pixel) as u8)
+                        .collect(),
+                ),
+                ..Frame::default()
+            },
+            None => Frame::from_rgba_speed(width, height, &mut image.rgba, speed),
+        };
+        frame.delay = delay;
+        encoder.write_frame(&frame)?;
+    }
+    encoder.into_inner()?.flush()?;
+    Ok(())
+}
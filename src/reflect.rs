@@ -0,0 +1,188 @@
+use wgpu::{
+    Features,
+    naga::{
+        front::wgsl,
+        valid::{Capabilities, ValidationFlags, Validator},
+    },
+};
+
+/// Optional device features shaders commonly use, with the capability naga
+/// validates them against and what the shader uses them for.
+const OPTIONAL_FEATURES: &[(Features, &str, Capabilities, &str)] = &[
+    (
+        Features::SHADER_F64,
+        "SHADER_F64",
+        Capabilities::FLOAT64,
+        "f64",
+    ),
+    (
+        Features::SHADER_F16,
+        "SHADER_F16",
+        Capabilities::SHADER_FLOAT16,
+        "f16",
+    ),
+    (
+        Features::SHADER_INT64,
+        "SHADER_INT64",
+        Capabilities::SHADER_INT64,
+        "i64 or u64",
+    ),
+    (
+        Features::SHADER_PRIMITIVE_INDEX,
+        "SHADER_PRIMITIVE_INDEX",
+        Capabilities::PRIMITIVE_INDEX,
+        "`@builtin(primitive_index)`",
+    ),
+    (
+        Features::DUAL_SOURCE_BLENDING,
+        "DUAL_SOURCE_BLENDING",
+        Capabilities::DUAL_SOURCE_BLENDING,
+        "`@blend_src`",
+    ),
+    (
+        Features::SUBGROUP,
+        "SUBGROUP",
+        Capabilities::SUBGROUP,
+        "subgroup operations",
+    ),
+];
+
+/// Returns the `(group, binding)` pairs of the resources any entry point of
+/// `source` reads or writes, or `None` if it doesn't compile.
+pub fn used_bindings(source: &str) -> Option<Vec<(u32, u32)>> {
+    let module = wgsl::parse_str(source).ok()?;
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .ok()?;
+    let used = module
+        .global_variables
+        .iter()
+        .filter(|&(handle, _)| {
+            (0..module.entry_points.len())
+                .any(|index| !info.get_entry_point(index)[handle].is_empty())
+        })
+        .filter_map(|(_, variable)| variable.binding.as_ref())
+        .map(|binding| (binding.group, binding.binding))
+        .collect();
+    Some(used)
+}
+
+/// Prefixes `error`, which `source` failed to compile with, with the optional
+/// features it uses that aren't among `features`, since the validation
+/// error alone doesn't say so. Returns `error` as is if none are missing.
+pub fn explain_error(source: &str, features: Features, error: String) -> String {
+    let Ok(module) = wgsl::parse_str(source) else {
+        return error;
+    };
+    let validates = |capabilities| {
+        Validator::new(ValidationFlags::all(), capabilities)
+            .validate(&module)
+            .is_ok()
+    };
+    // Shaders that don't compile even with every capability have another
+    // problem, which the error describes.
+    if !validates(Capabilities::all()) {
+        return error;
+    }
+    let hints: Vec<_> = OPTIONAL_FEATURES
+        .iter()
+        .filter(|&&(feature, ..)| !features.contains(feature))
+        .filter(|&&(_, _, capability, _)| !validates(Capabilities::all() - capability))
+        .map(|(_, name, _, usage)| {
+            format!("This shader uses {usage}, but the GPU doesn't support `{name}`")
+        })
+        .collect();
+    if hints.is_empty() {
+        return error;
+    }
+    format!("{}\n\n{error}", hints.join("\n"))
+}
+
+/// naga's diagnostic for why `source` doesn't compile, like rustc's: the
+/// message, `wgsl:LINE:COLUMN` and the offending source line with a caret
+/// under the span. `None` if naga accepts `source` with every capability, in
+/// which case only wgpu's error says what's wrong, e.g. a missing feature.
+pub fn diagnose(source: &str) -> Option<String> {
+    let diagnostic = match wgsl::parse_str(source) {
+        Ok(module) => Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .err()?
+            .emit_to_string(source),
+        Err(err) => err.emit_to_string(source),
+    };
+    Some(diagnostic.trim_end().to_owned())
+}
+
+/// Name, byte offset and size of each member of the struct `name` declared
+/// in `source`, followed by the struct's size, as naga lays them out.
+#[cfg(test)]
+pub fn struct_layout(source: &str, name: &str) -> (Vec<(String, u32, u32)>, u32) {
+    use wgpu::naga::TypeInner;
+
+    let module = wgsl::parse_str(source).expect("the WGSL parses");
+    let ty = module
+        .types
+        .iter()
+        .find_map(|(_, ty)| (ty.name.as_deref() == Some(name)).then_some(ty))
+        .unwrap_or_else(|| panic!("the WGSL has no struct `{name}`"));
+    let TypeInner::Struct { members, span } = &ty.inner else {
+        panic!("`{name}` isn't a struct");
+    };
+    let members = members
+        .iter()
+        .map(|member| {
+            (
+                member.name.clone().unwrap_or_default(),
+                member.offset,
+                module.types[member.ty].inner.size(module.to_ctx()),
+            )
+        })
+        .collect();
+    (members, *span)
+}
+
+/// Size of each `var<uniform>` declared in `source` by `(group, binding)`.
+#[cfg(test)]
+pub fn uniform_sizes(source: &str) -> Vec<((u32, u32), u32)> {
+    use wgpu::naga::AddressSpace;
+
+    let module = wgsl::parse_str(source).expect("the WGSL parses");
+    module
+        .global_variables
+        .iter()
+        .filter(|(_, variable)| variable.space == AddressSpace::Uniform)
+        .filter_map(|(_, variable)| {
+            let binding = variable.binding.as_ref()?;
+            let size = module.types[variable.ty].inner.size(module.to_ctx());
+            Some(((binding.group, binding.binding), size))
+        })
+        .collect()
+}
+
+/// Panics with both layouts side by side unless `actual`, the WGSL's layout
+/// of some value, matches `expected`, the host's, as `(name, offset, size)`.
+#[cfg(test)]
+pub fn assert_layout(expected: &[(String, u32, u32)], actual: &[(String, u32, u32)]) {
+    if expected == actual {
+        return;
+    }
+    let row = |entry: Option<&(String, u32, u32)>| {
+        entry.map_or_else(
+            || "-".to_owned(),
+            |(name, offset, size)| format!("{name} @ {offset} ({size} bytes)"),
+        )
+    };
+    let rows: Vec<_> = (0..expected.len().max(actual.len()))
+        .map(|index| {
+            let (expected, actual) = (expected.get(index), actual.get(index));
+            let marker = if expected == actual { " " } else { "!" };
+            format!("{marker} {:<32} {}", row(expected), row(actual))
+        })
+        .collect();
+    panic!(
+        "host and WGSL layouts differ:\n  {:<32} {}\n{}",
+        "host",
+        "WGSL",
+        rows.join("\n")
+    );
+}
@@ -0,0 +1,203 @@
+use std::{
+    sync::{Arc, atomic::Ordering, mpsc},
+    time::Instant,
+};
+
+use wgpu::PollType;
+use winit::{
+    dpi::PhysicalSize,
+    event::{DeviceEvent, WindowEvent},
+    window::Window,
+};
+
+use crate::{
+    AppState, Kept,
+    cli::Args,
+    device::GpuInfo,
+    events::{self, Event, Events},
+};
+
+/// The viewer drawing into a window the host app created and runs the event
+/// loop of, e.g. a game drawing a shader behind its scene.
+///
+/// The host forwards the window's events: `RedrawRequested` to
+/// [`Renderer::redraw`], every other window event to
+/// [`Renderer::window_event`], which also follows `Resized`, and raw device
+/// events to [`Renderer::device_event`], which moves the grabbed cursor.
+/// Once the events of an iteration are handled, e.g. in `about_to_wait`, it
+/// calls [`Renderer::about_to_wait`] and wakes the loop up at the time it
+/// returns. The renderer requests the window's redraws itself.
+///
+/// The options are those of the binary, e.g.
+/// `Args::try_parse_from(["shadertoy", "background.wgsl"])` with
+/// [`clap::Parser`] in scope, which also reads the `SHADERTOY_*` environment
+/// variables but not the config file. Options that only make sense for the
+/// binary are rejected: the modes that run without a window, `--socket` and
+/// `--config`, which `run` sets up, `--icon`, `--pos` and `--size` of the
+/// window the host creates, and `--bench-present-modes` and `--max-frames`,
+/// which end the event loop.
+#[derive(Debug)]
+pub struct Renderer {
+    args: Args,
+    window: Arc<Window>,
+    events: Events,
+    /// Missing while the device is lost and rebuilding it failed, see
+    /// [`Renderer::recover`].
+    state: Option<AppState>,
+    /// What carries over to the next renderer once the device is lost.
+    kept: Option<Kept>,
+    gpu_info: GpuInfo,
+}
+
+impl Renderer {
+    /// Builds the renderer against `window`, which has to stay on the
+    /// event loop the events are forwarded from, and configures its surface
+    /// to the window's size. Fails if `args` sets an option only the binary
+    /// supports.
+    pub async fn new(window: Arc<Window>, args: &Args) -> Result<Self, Box<dyn std::error::Error>> {
+        let unsupported = unsupported_options(args);
+        if !unsupported.is_empty() {
+            return Err(format!(
+                "Options not supported by an embedded renderer: {}",
+                unsupported.join(", ")
+            )
+            .into());
+        }
+        let events = Events::default();
+        if args.events {
+            events::print(events.subscribe());
+        }
+        let state = AppState::new(window.clone(), args, None, events.clone()).await?;
+        Ok(Self {
+            args: args.clone(),
+            window,
+            events,
+            gpu_info: state.gpu_info().clone(),
+            state: Some(state),
+            kept: None,
+        })
+    }
+
+    pub fn window(&self) -> &Arc<Window> {
+        &self.window
+    }
+
+    /// The adapter the renderer runs on, and the features and limits of its
+    /// device.
+    pub fn gpu_info(&self) -> &GpuInfo {
+        &self.gpu_info
+    }
+
+    /// Returns a receiver of every [`Event`] from now on: passes being
+    /// recompiled, frames being presented, and the device being lost and
+    /// recovered. Subscriptions last across [`Renderer::recover`].
+    pub fn subscribe(&self) -> mpsc::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Handles an event of the window other than `RedrawRequested`: the
+    /// size, keyboard, mouse and wheel input, modifiers, focus, moves,
+    /// scale factor and theme. Other events are ignored.
+    pub fn window_event(&mut self, event: &WindowEvent) {
+        if let Some(state) = &mut self.state {
+            state.window_event(event);
+        }
+    }
+
+    /// Resizes the surface to `size`, for hosts that size it themselves
+    /// rather than forwarding `Resized`.
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.window_event(&WindowEvent::Resized(size));
+    }
+
+    pub fn device_event(&mut self, event: &DeviceEvent) {
+        if let Some(state) = &mut self.state {
+            state.device_event(event);
+        }
+    }
+
+    /// Renders a frame, on `RedrawRequested`. Errors of one frame leave the
+    /// next ones unaffected, except [`wgpu::SurfaceError::OutOfMemory`].
+    pub fn redraw(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match &mut self.state {
+            Some(state) => state.redraw(),
+            None => Ok(()),
+        }
+    }
+
+    /// Requests the redraws that are due and returns when to call it again,
+    /// or `None` to wait for the next event.
+    pub fn about_to_wait(&mut self) -> Option<Instant> {
+        self.state.as_mut().and_then(AppState::wake_at)
+    }
+
+    /// Whether the driver lost the GPU device, e.g. after a reset. Nothing
+    /// renders until [`Renderer::recover`] succeeds.
+    pub fn is_lost(&self) -> bool {
+        self.state
+            .as_ref()
+            .is_none_or(|state| state.lost.load(Ordering::SeqCst))
+    }
+
+    /// Rebuilds the renderer on the same window after the device was lost,
+    /// carrying over the shader source, the clock and the input uniforms
+    /// like the binary does. Can be retried if it fails, e.g. after a
+    /// while, since the driver may still be resetting.
+    pub async fn recover(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // The old surface is dropped first, since some platforms allow only
+        // one per window.
+        if let Some(old) = self.state.take() {
+            self.kept = Some(old.keep());
+        }
+        let Some(kept) = self.kept.take() else {
+            return Ok(());
+        };
+        match AppState::rebuild(kept, &self.args, None, self.events.clone()).await {
+            Ok(state) => {
+                self.gpu_info = state.gpu_info().clone();
+                self.state = Some(state);
+                Ok(())
+            }
+            Err((kept, err)) => {
+                self.kept = Some(kept);
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Drop for Renderer {
+    /// Saves the state like the binary does on exit, see `--save-state`, and
+    /// waits for the GPU to finish before the device is dropped.
+    fn drop(&mut self) {
+        let Some(state) = &self.state else { return };
+        state.save_state();
+        if let Err(err) = state.device.poll(PollType::wait_indefinitely()) {
+            tracing::warn!("Failed to drain GPU queue: {err}");
+        }
+    }
+}
+
+/// The options only the binary supports that `args` sets.
+fn unsupported_options(args: &Args) -> Vec<&'static str> {
+    [
+        ("`--validate`", args.validate.is_some()),
+        ("`--export`", args.export.is_some()),
+        ("`--gif`", args.gif.is_some()),
+        ("`--sequence`", args.sequence.is_some()),
+        ("`--render`", args.render.is_some()),
+        ("`--socket`", args.socket.is_some()),
+        ("`--config`", args.config.is_some()),
+        ("`--icon`", args.icon.is_some()),
+        ("`--pos`", args.pos.is_some()),
+        ("`--size`", args.size.is_some()),
+        (
+            "`--bench-present-modes`",
+            args.bench_present_modes.is_some(),
+        ),
+        ("`--max-frames`", args.max_frames.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(name, set)| set.then_some(name))
+    .collect()
+}
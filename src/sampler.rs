@@ -0,0 +1,54 @@
+use serde::Deserialize;
+use wgpu::{AddressMode, Device, FilterMode, MipmapFilterMode, Sampler, SamplerDescriptor};
+
+/// Filtering and wrapping of a sampler. The default, linear filtering with
+/// repeating coordinates, is what channels use unless overridden.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SamplerOptions {
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub wrap: Wrap,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Filter {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Wrap {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+pub fn create_sampler(device: &Device, label: &str, options: SamplerOptions) -> Sampler {
+    // Mip levels are blended like texels, which only matters for images
+    // with mipmaps.
+    let (filter, mipmap_filter) = match options.filter {
+        Filter::Linear => (FilterMode::Linear, MipmapFilterMode::Linear),
+        Filter::Nearest => (FilterMode::Nearest, MipmapFilterMode::Nearest),
+    };
+    let address_mode = match options.wrap {
+        Wrap::Repeat => AddressMode::Repeat,
+        Wrap::Clamp => AddressMode::ClampToEdge,
+        Wrap::Mirror => AddressMode::MirrorRepeat,
+    };
+    device.create_sampler(&SamplerDescriptor {
+        label: Some(label),
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        address_mode_w: address_mode,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter,
+        ..Default::default()
+    })
+}
@@ -0,0 +1,175 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use winit::event_loop::EventLoopProxy;
+
+use crate::UserEvent;
+
+/// Something a client of `--socket` asks for, sent as one JSON object per
+/// line, e.g. `{"command":"status"}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Compiles `source` in place of the shader drawn to the window, like
+    /// `Ctrl+Enter` in the editor.
+    SetSource { source: String },
+    /// Sets a uniform the viewer otherwise drives itself.
+    SetUniform {
+        #[serde(flatten)]
+        uniform: Uniform,
+    },
+    /// Saves the scene to `path` as a PNG.
+    Screenshot { path: PathBuf },
+    /// Reports the loaded shaders like `F6`.
+    Status,
+}
+
+/// New value of a uniform, e.g. `{"name":"scroll","value":2.5}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "name", content = "value", rename_all = "kebab-case")]
+pub enum Uniform {
+    /// Shader time in seconds, which keeps running from there.
+    Time(f64),
+    Scroll(f32),
+    Seed(u64),
+}
+
+/// A request waiting for the event loop to answer it on `reply`.
+#[derive(Debug)]
+pub struct Message {
+    pub request: Request,
+    pub reply: mpsc::Sender<Result<Value, String>>,
+}
+
+/// The socket clients connect to, removed when dropped, see `--socket`.
+#[derive(Debug)]
+pub struct Socket {
+    path: PathBuf,
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            tracing::debug!("Failed to remove `{}`: {err}", self.path.display());
+        }
+    }
+}
+
+/// Listens on a Unix domain socket at `path` and forwards every request to
+/// the event loop behind `proxy`, writing each answer back as one JSON line.
+///
+/// Clients can connect, disconnect and reconnect at any time, and several
+/// can be connected at once. A socket left behind by a viewer that crashed
+/// is replaced, one another viewer still listens on is an error.
+#[cfg(unix)]
+#[tracing::instrument(skip(proxy))]
+pub fn listen(path: &Path, proxy: EventLoopProxy<UserEvent>) -> io::Result<Socket> {
+    use std::{
+        os::unix::net::{UnixListener, UnixStream},
+        thread,
+    };
+
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("`{}` is already in use", path.display()),
+        ));
+    }
+    match std::fs::remove_file(path) {
+        Ok(()) => tracing::debug!("Removed stale socket `{}`", path.display()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    let listener = UnixListener::bind(path)?;
+    tracing::info!("Listening on `{}`", path.display());
+
+    thread::Builder::new()
+        .name("socket".to_owned())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        tracing::warn!("Failed to accept a connection: {err}");
+                        continue;
+                    }
+                };
+                let proxy = proxy.clone();
+                let spawned = thread::Builder::new()
+                    .name("socket client".to_owned())
+                    .spawn(move || {
+                        tracing::debug!("Client connected");
+                        let reader = match stream.try_clone() {
+                            Ok(reader) => reader,
+                            Err(err) => {
+                                tracing::warn!("Failed to read from the client: {err}");
+                                return;
+                            }
+                        };
+                        match serve(reader, stream, &proxy) {
+                            Ok(()) => tracing::debug!("Client disconnected"),
+                            Err(err) => tracing::debug!("Client connection failed: {err}"),
+                        }
+                    });
+                if let Err(err) = spawned {
+                    tracing::warn!("Failed to spawn a client thread: {err}");
+                }
+            }
+        })?;
+    Ok(Socket {
+        path: path.to_owned(),
+    })
+}
+
+#[cfg(not(unix))]
+pub fn listen(_: &Path, _: EventLoopProxy<UserEvent>) -> io::Result<Socket> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "`--socket` needs Unix domain sockets, which this platform doesn't have",
+    ))
+}
+
+/// Answers the requests of one client until it disconnects or the event
+/// loop exits. Lines are buffered until they're complete, so requests can
+/// arrive in any number of pieces.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn serve(
+    reader: impl io::Read,
+    mut writer: impl io::Write,
+    proxy: &EventLoopProxy<UserEvent>,
+) -> io::Result<()> {
+    use io::BufRead;
+
+    for line in io::BufReader::new(reader).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => {
+                let (reply, rx) = mpsc::channel();
+                if proxy
+                    .send_event(UserEvent::Request(Message { request, reply }))
+                    .is_err()
+                {
+                    return Ok(());
+                }
+                rx.recv()
+                    .unwrap_or_else(|_| Err("the viewer is shutting down".to_owned()))
+            }
+            Err(err) => Err(format!("invalid request: {err}")),
+        };
+        let response = match response {
+            Ok(result) => json!({ "ok": true, "result": result }),
+            Err(error) => json!({ "ok": false, "error": error }),
+        };
+        writeln!(writer, "{response}")?;
+        writer.flush()?;
+    }
+    Ok(())
+}
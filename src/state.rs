@@ -0,0 +1,123 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::Duration,
+};
+
+use crate::clock;
+
+/// Identifies state files, see `--save-state`.
+const MAGIC: &[u8; 8] = b"STOYSTAT";
+/// Layout version of state files, bumped when it changes.
+const VERSION: u32 = 1;
+
+/// Contents of the offscreen targets at some frame, see `--save-state` and
+/// `--load-state`.
+///
+/// Stored as the magic bytes and version, followed by the clock and each
+/// target's name, format, size and texels, all little-endian.
+#[derive(Debug)]
+pub struct SavedState {
+    pub frame: u32,
+    pub time: Duration,
+    pub targets: Vec<SavedTarget>,
+}
+
+/// Texels of one offscreen target.
+#[derive(Debug)]
+pub struct SavedTarget {
+    pub name: String,
+    /// Texture format, as `wgpu` prints it.
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed rows of texels.
+    pub data: Vec<u8>,
+}
+
+impl SavedState {
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&VERSION.to_le_bytes())?;
+        file.write_all(&self.frame.to_le_bytes())?;
+        file.write_all(&self.time.as_secs_f64().to_le_bytes())?;
+        file.write_all(&(self.targets.len() as u32).to_le_bytes())?;
+        for target in &self.targets {
+            write_bytes(&mut file, target.name.as_bytes())?;
+            write_bytes(&mut file, target.format.as_bytes())?;
+            file.write_all(&target.width.to_le_bytes())?;
+            file.write_all(&target.height.to_le_bytes())?;
+            write_bytes(&mut file, &target.data)?;
+        }
+        file.flush()
+    }
+
+    pub fn read(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0; MAGIC.len()];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err("not a state file".into());
+        }
+        let version = read_u32(&mut file)?;
+        if version != VERSION {
+            return Err(format!("unsupported state file version {version}").into());
+        }
+        let frame = read_u32(&mut file)?;
+        let mut time = [0; 8];
+        file.read_exact(&mut time)?;
+        let time = Duration::try_from_secs_f64(f64::from_le_bytes(time))?;
+        // Checked up front, so a state the clock can't resume from doesn't
+        // replace the buffers either.
+        clock::start_for(time)?;
+        let count = read_u32(&mut file)?;
+        let targets = (0..count)
+            .map(|_| {
+                let name = String::from_utf8(read_bytes(&mut file)?)?;
+                let format = String::from_utf8(read_bytes(&mut file)?)?;
+                let width = read_u32(&mut file)?;
+                let height = read_u32(&mut file)?;
+                let data = read_bytes(&mut file)?;
+                Ok(SavedTarget {
+                    name,
+                    format,
+                    width,
+                    height,
+                    data,
+                })
+            })
+            .collect::<Result<_, Box<dyn std::error::Error>>>()?;
+        Ok(Self {
+            frame,
+            time,
+            targets,
+        })
+    }
+}
+
+/// Writes `bytes` prefixed with their length.
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes(reader: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    let mut len = [0; 8];
+    reader.read_exact(&mut len)?;
+    let mut bytes = Vec::new();
+    reader
+        .take(u64::from_le_bytes(len))
+        .read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != u64::from_le_bytes(len) {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::overlay::{Color, Overlay, Theme};
+
+/// How often the frame rate in the window title is refreshed.
+const TITLE_INTERVAL: Duration = Duration::from_secs(1);
+/// How long rendering can stall before the frame rate leaves the title.
+const TITLE_STALL: Duration = Duration::from_secs(2);
+
+/// Rolling record of the most recent frame durations.
+#[derive(Debug)]
+pub struct FrameTimes {
+    samples: VecDeque<Duration>,
+    capacity: usize,
+    last_frame: Option<Instant>,
+}
+
+impl FrameTimes {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            last_frame: None,
+        }
+    }
+
+    /// Records a frame finished at `now`, evicting the oldest sample once the
+    /// buffer is full.
+    pub fn tick(&mut self, now: Instant) {
+        if let Some(last_frame) = self.last_frame.replace(now) {
+            if self.samples.len() == self.capacity {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(now - last_frame);
+        }
+    }
+
+    /// When the last frame finished, if any has.
+    pub fn last_frame(&self) -> Option<Instant> {
+        self.last_frame
+    }
+
+    /// Forgets every recorded frame.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.last_frame = None;
+    }
+
+    /// Mean of the recorded frame durations, if any have been recorded.
+    pub fn average(&self) -> Option<Duration> {
+        let count = u32::try_from(self.samples.len()).ok().filter(|&n| n > 0)?;
+        Some(self.samples.iter().sum::<Duration>() / count)
+    }
+
+    /// Frames per second over the recorded frames, if any have been
+    /// recorded.
+    pub fn rate(&self) -> Option<f64> {
+        self.average()
+            .filter(|average| !average.is_zero())
+            .map(|average| average.as_secs_f64().recip())
+    }
+
+    /// Draws the recorded frame times as a bar graph with its top left corner
+    /// at `(x, y)`, marking the 60 and 120 fps budgets.
+    pub fn draw_graph(&self, overlay: &mut Overlay, theme: Theme, x: f32, y: f32) {
+        const HEIGHT: f32 = 80.0;
+        const BAR_WIDTH: f32 = 2.0;
+        /// Frame time shown at the top of the graph.
+        const MAX_MS: f32 = 33.3;
+        const BUDGETS_MS: [f32; 2] = [16.6, 8.3];
+
+        let width = self.capacity as f32 * BAR_WIDTH;
+        overlay.rect(x, y, width, HEIGHT, theme.background());
+
+        for (i, sample) in self.samples.iter().enumerate() {
+            let ms = sample.as_secs_f32() * 1000.0;
+            let color: Color = if ms <= BUDGETS_MS[1] {
+                [0.2, 0.8, 1.0, 0.9]
+            } else if ms <= BUDGETS_MS[0] {
+                [0.2, 1.0, 0.2, 0.9]
+            } else if ms <= MAX_MS {
+                [1.0, 0.8, 0.2, 0.9]
+            } else {
+                [1.0, 0.2, 0.2, 0.9]
+            };
+            let height = ms.min(MAX_MS) / MAX_MS * HEIGHT;
+            let bar_x = x + i as f32 * BAR_WIDTH;
+            overlay.rect(bar_x, y + HEIGHT - height, BAR_WIDTH, height, color);
+        }
+
+        for budget in BUDGETS_MS {
+            let line_y = y + HEIGHT - budget / MAX_MS * HEIGHT;
+            let [r, g, b, _] = theme.text();
+            overlay.rect(x, line_y, width, 1.0, [r, g, b, 0.5]);
+        }
+    }
+}
+
+/// Frame rate shown in the window title, see `--title-fps`. The title only
+/// changes once per [`TITLE_INTERVAL`], so updating it costs next to
+/// nothing per frame.
+#[derive(Debug)]
+pub struct TitleFps {
+    times: FrameTimes,
+    /// When the title last showed a new frame rate, or `None` while it's
+    /// plain.
+    updated: Option<Instant>,
+}
+
+impl TitleFps {
+    /// Averages the frame rate over the last `frames` frames.
+    pub fn new(frames: usize) -> Self {
+        Self {
+            times: FrameTimes::new(frames),
+            updated: None,
+        }
+    }
+
+    /// Records a frame finished at `now`. Returns the average frame time to
+    /// show if the title is due for an update.
+    pub fn tick(&mut self, now: Instant) -> Option<Duration> {
+        self.times.tick(now);
+        if self
+            .updated
+            .is_some_and(|updated| now - updated < TITLE_INTERVAL)
+        {
+            return None;
+        }
+        let average = self.times.average().filter(|average| !average.is_zero())?;
+        self.updated = Some(now);
+        Some(average)
+    }
+
+    /// When the frame rate has to leave the title unless another frame
+    /// finishes first, or `None` if the title is plain.
+    pub fn stall_deadline(&self) -> Option<Instant> {
+        self.updated?;
+        Some(self.times.last_frame()? + TITLE_STALL)
+    }
+
+    /// Forgets the recorded frames after rendering stalled, so the gap
+    /// doesn't drag down the next frame rate shown.
+    pub fn reset(&mut self) {
+        self.times.clear();
+        self.updated = None;
+    }
+}
@@ -0,0 +1,93 @@
+use image::{Rgba, RgbaImage};
+use wgpu::{
+    Device, Extent3d, Origin3d, Queue, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+
+use crate::uniforms::splitmix64;
+
+/// Names of the built-in textures channels can read, in the spirit of
+/// Shadertoy's standard noise assets.
+pub const BUILTIN_TEXTURES: [&str; 3] = ["noise-rgba-256", "noise-rgba-64", "noise-gray-256"];
+
+/// Texel offset between the red and green (and blue and alpha) channels of
+/// the RGBA noise: the green value at `(x, y)` is the red value at
+/// `(x - 37, y - 17)`. Shaders ported from Shadertoy rely on this to fetch
+/// two slices of 3D value noise with one texture read.
+const CHANNEL_OFFSET: (u32, u32) = (37, 17);
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTIN_TEXTURES.contains(&name)
+}
+
+/// Generates the built-in texture `name` and uploads it.
+#[tracing::instrument(skip(device, queue))]
+pub fn create_builtin(device: &Device, queue: &Queue, name: &str) -> Option<Texture> {
+    let image = match name {
+        "noise-rgba-256" => rgba_noise(256, 0x5EED_0256),
+        "noise-rgba-64" => rgba_noise(64, 0x5EED_0064),
+        "noise-gray-256" => gray_noise(256, 0x5EED_6256),
+        _ => return None,
+    };
+    tracing::debug!("Generated built-in texture");
+    Some(upload(device, queue, name, &image))
+}
+
+/// White noise in every channel, with green and alpha offset copies of red
+/// and blue, see [`CHANNEL_OFFSET`].
+fn rgba_noise(size: u32, seed: u64) -> RgbaImage {
+    let mut state = seed;
+    let mut noise = || (splitmix64(&mut state) >> 56) as u8;
+    let red: Vec<u8> = (0..size * size).map(|_| noise()).collect();
+    let blue: Vec<u8> = (0..size * size).map(|_| noise()).collect();
+    let (dx, dy) = CHANNEL_OFFSET;
+    RgbaImage::from_fn(size, size, |x, y| {
+        let index = (y * size + x) as usize;
+        let offset =
+            ((y + size - dy % size) % size * size + (x + size - dx % size) % size) as usize;
+        Rgba([red[index], red[offset], blue[index], blue[offset]])
+    })
+}
+
+fn gray_noise(size: u32, seed: u64) -> RgbaImage {
+    let mut state = seed;
+    RgbaImage::from_fn(size, size, |_, _| {
+        let value = (splitmix64(&mut state) >> 56) as u8;
+        Rgba([value, value, value, 255])
+    })
+}
+
+/// Uploads `image` to a new linear RGBA texture.
+fn upload(device: &Device, queue: &Queue, name: &str, image: &RgbaImage) -> Texture {
+    let size = Extent3d {
+        width: image.width(),
+        height: image.height(),
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some(name),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba8Unorm,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        image,
+        TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(image.width() * 4),
+            rows_per_image: None,
+        },
+        size,
+    );
+    texture
+}
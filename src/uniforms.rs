@@ -0,0 +1,629 @@
+use std::{fmt, time::SystemTime};
+
+use bytemuck::Pod;
+use chrono::{DateTime, Datelike, Local, TimeZone, Timelike};
+use serde::Serialize;
+
+use crate::cli::Args;
+
+/// Binding of the time uniform in group 0.
+pub const TIME_BINDING: u32 = 0;
+/// Binding of the resolution uniform in group 0.
+pub const RESOLUTION_BINDING: u32 = 1;
+/// Binding of the seed uniform in group 0.
+pub const SEED_BINDING: u32 = 2;
+/// Binding of the optional read-write storage buffer in group 0.
+pub const STORAGE_BINDING: u32 = 3;
+/// Binding of the mouse uniform in group 0.
+pub const MOUSE_BINDING: u32 = 4;
+/// Binding of the frame counter in group 0.
+pub const FRAME_BINDING: u32 = 5;
+/// Binding of the delta time uniform in group 0.
+pub const DELTA_BINDING: u32 = 6;
+/// Binding of the keyboard texture in group 0.
+pub const KEYBOARD_BINDING: u32 = 7;
+/// Binding of the frame rate uniform in group 0.
+pub const FRAME_RATE_BINDING: u32 = 8;
+/// Binding of the default linear, repeating sampler in group 0.
+pub const SAMPLER_BINDING: u32 = 9;
+/// Binding of the scale factor uniform in group 0.
+pub const SCALE_FACTOR_BINDING: u32 = 10;
+/// Binding of the real time uniform in group 0, which keeps running while
+/// paused.
+pub const REAL_TIME_BINDING: u32 = 11;
+/// Binding of the scroll uniform in group 0.
+pub const SCROLL_BINDING: u32 = 12;
+/// Binding of the refresh rate uniform in group 0.
+pub const REFRESH_RATE_BINDING: u32 = 13;
+/// Binding of the date uniform in group 0, which follows the wall clock like
+/// real time.
+pub const DATE_BINDING: u32 = 14;
+
+/// Group 0 bindings that change every frame: time, the storage buffer, the
+/// frame counter and the delta time.
+pub const ANIMATED_BINDINGS: [u32; 4] =
+    [TIME_BINDING, STORAGE_BINDING, FRAME_BINDING, DELTA_BINDING];
+
+/// Values uniform providers derive their data from, gathered once per frame.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Inputs {
+    /// Seconds since the shader was (re)loaded.
+    pub time: f32,
+    /// Seconds since the previous frame.
+    pub delta: f32,
+    /// Wall-clock seconds since the app started, unaffected by pausing,
+    /// stepping, reloads or `--deterministic`.
+    pub real_time: f32,
+    /// Frames rendered since the shader was (re)loaded.
+    pub frame: u32,
+    /// Smoothed frames per second.
+    pub frame_rate: f32,
+    /// Physical pixels per logical pixel of the window's display.
+    pub scale_factor: f32,
+    /// Size of the scene texture in pixels.
+    pub resolution: [f32; 2],
+    pub seed: u64,
+    /// Cursor position and click position in scene pixels, with the origin
+    /// at the bottom left. The click position is zero while no button is
+    /// held.
+    pub mouse: [f32; 4],
+    /// Mouse wheel notches scrolled since the start or the last reset,
+    /// upward being positive.
+    pub scroll: f32,
+    /// Refresh rate of the window's monitor in hertz, or zero if it's
+    /// unknown.
+    pub refresh_rate: f32,
+    /// Wall-clock date, see [`date`].
+    pub date: [f32; 4],
+}
+
+/// `time` in the local time zone as `[year, month, day, seconds]` like
+/// Shadertoy's `iDate`: the month counts from 0, the day from 1, and the
+/// seconds since midnight include the fraction.
+pub fn date(time: SystemTime) -> [f32; 4] {
+    civil_date(DateTime::<Local>::from(time))
+}
+
+/// `time` as [`date`] lays it out, in the time zone of `time`.
+fn civil_date<Tz: TimeZone>(time: DateTime<Tz>) -> [f32; 4] {
+    // A leap second counts as the last second of the day.
+    let seconds = f64::from(time.num_seconds_from_midnight())
+        + f64::from(time.nanosecond().min(999_999_999)) / 1e9;
+    [
+        time.year() as f32,
+        time.month0() as f32,
+        time.day() as f32,
+        seconds as f32,
+    ]
+}
+
+/// Placement of each uniform binding in the uniform buffer. Every binding
+/// gets a slot as large as its provider's value, starting at a multiple of
+/// `min_uniform_buffer_offset_alignment`.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    bindings: Vec<u32>,
+    /// Byte offset and size of each binding's slot.
+    slots: Vec<(u64, u64)>,
+    size: u64,
+}
+
+impl Layout {
+    /// Assigns a slot to the binding of each provider, in order.
+    pub fn new(alignment: u64, providers: &[Box<dyn UniformProvider>]) -> Self {
+        let bindings: Vec<_> = providers.iter().map(|p| p.binding()).collect();
+        for (index, binding) in bindings.iter().enumerate() {
+            assert!(
+                !bindings[..index].contains(binding),
+                "uniform binding {binding} is provided twice"
+            );
+        }
+        let mut slots = Vec::with_capacity(providers.len());
+        let mut size = 0u64;
+        for provider in providers {
+            let offset = size.next_multiple_of(alignment);
+            slots.push((offset, provider.size()));
+            size = offset + provider.size();
+        }
+        Self {
+            bindings,
+            slots,
+            size: size.next_multiple_of(alignment),
+        }
+    }
+
+    /// Uniform bindings in group 0, in buffer order.
+    pub fn bindings(&self) -> &[u32] {
+        &self.bindings
+    }
+
+    /// Byte offset of `binding` in the buffer.
+    pub fn offset(&self, binding: u32) -> u64 {
+        self.slot(binding).0
+    }
+
+    fn slot(&self, binding: u32) -> (u64, u64) {
+        let slot = self
+            .bindings
+            .iter()
+            .position(|&b| b == binding)
+            .unwrap_or_else(|| panic!("uniform binding {binding} isn't in the layout"));
+        self.slots[slot]
+    }
+
+    /// Total size of the buffer in bytes, rounded up to the alignment.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Copies `value` into the slot of `binding` in `buf`.
+    pub fn put<T: Pod>(&self, buf: &mut [u8], binding: u32, value: &T) {
+        let bytes = bytemuck::bytes_of(value);
+        let (offset, size) = self.slot(binding);
+        debug_assert!(bytes.len() as u64 <= size);
+        let offset = offset as usize;
+        buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+}
+
+/// Source of one uniform binding in group 0.
+///
+/// Providers are refreshed with [`update`](Self::update) every frame and
+/// then [`write`](Self::write) their value into the CPU copy of the uniform
+/// buffer, which is uploaded in one go.
+pub trait UniformProvider: fmt::Debug {
+    /// Binding of the uniform in group 0.
+    fn binding(&self) -> u32;
+
+    /// Size in bytes of the value [`write`](Self::write) puts in the buffer.
+    fn size(&self) -> u64;
+
+    /// Refreshes the provided value from this frame's inputs.
+    fn update(&mut self, _inputs: &Inputs) {}
+
+    /// Writes the current value into `buf` at the slot given by `layout`.
+    fn write(&self, buf: &mut [u8], layout: &Layout);
+}
+
+/// Providers for the built-in uniforms.
+pub fn defaults(args: &Args) -> Vec<Box<dyn UniformProvider>> {
+    vec![
+        Box::new(Time::default()),
+        Box::new(Resolution::default()),
+        Box::new(Seed::default()),
+        Box::new(Mouse::new(args.mouse_smoothing)),
+        Box::new(Frame::default()),
+        Box::new(TimeDelta::default()),
+        Box::new(FrameRate::default()),
+        Box::new(ScaleFactor::default()),
+        Box::new(RealTime::default()),
+        Box::new(Scroll::default()),
+        Box::new(RefreshRate::default()),
+        Box::new(Date::default()),
+    ]
+}
+
+/// Elapsed time in seconds as `f32`.
+#[derive(Debug, Default)]
+pub struct Time(f32);
+
+impl UniformProvider for Time {
+    fn binding(&self) -> u32 {
+        TIME_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<f32>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        self.0 = inputs.time;
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.0);
+    }
+}
+
+/// Wall-clock seconds since the app started as `f32`, which keeps running
+/// while [`Time`] is paused or stepped.
+#[derive(Debug, Default)]
+pub struct RealTime(f32);
+
+impl UniformProvider for RealTime {
+    fn binding(&self) -> u32 {
+        REAL_TIME_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<f32>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        self.0 = inputs.real_time;
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.0);
+    }
+}
+
+/// Accumulated mouse wheel notches as `f32`, for shaders driving any
+/// parameter with the wheel.
+#[derive(Debug, Default)]
+pub struct Scroll(f32);
+
+impl UniformProvider for Scroll {
+    fn binding(&self) -> u32 {
+        SCROLL_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<f32>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        self.0 = inputs.scroll;
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.0);
+    }
+}
+
+/// Refresh rate of the window's monitor in hertz as `f32`, zero if the
+/// platform doesn't report it.
+#[derive(Debug, Default)]
+pub struct RefreshRate(f32);
+
+impl UniformProvider for RefreshRate {
+    fn binding(&self) -> u32 {
+        REFRESH_RATE_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<f32>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        self.0 = inputs.refresh_rate;
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.0);
+    }
+}
+
+/// Wall-clock date as `vec4<f32>`, see [`date`].
+#[derive(Debug, Default)]
+pub struct Date([f32; 4]);
+
+impl UniformProvider for Date {
+    fn binding(&self) -> u32 {
+        DATE_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<[f32; 4]>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        self.0 = inputs.date;
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.0);
+    }
+}
+
+/// Scene size in pixels as `vec2<f32>`.
+#[derive(Debug, Default)]
+pub struct Resolution([f32; 2]);
+
+impl UniformProvider for Resolution {
+    fn binding(&self) -> u32 {
+        RESOLUTION_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<[f32; 2]>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        self.0 = inputs.resolution;
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.0);
+    }
+}
+
+/// Four pseudo-random floats in `[0, 1)` derived from the seed, as
+/// `vec4<f32>`.
+#[derive(Debug, Default)]
+pub struct Seed {
+    seed: Option<u64>,
+    random: [f32; 4],
+}
+
+impl UniformProvider for Seed {
+    fn binding(&self) -> u32 {
+        SEED_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<[f32; 4]>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        if self.seed == Some(inputs.seed) {
+            return;
+        }
+        self.seed = Some(inputs.seed);
+        let mut state = inputs.seed;
+        self.random = [(); 4].map(|()| {
+            let bits = splitmix64(&mut state) >> 40;
+            bits as f32 / (1u64 << 24) as f32
+        });
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.random);
+    }
+}
+
+/// Frame counter as `u32`.
+#[derive(Debug, Default)]
+pub struct Frame(u32);
+
+impl UniformProvider for Frame {
+    fn binding(&self) -> u32 {
+        FRAME_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<u32>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        self.0 = inputs.frame;
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.0);
+    }
+}
+
+/// Time since the previous frame in seconds as `f32`.
+#[derive(Debug, Default)]
+pub struct TimeDelta(f32);
+
+impl UniformProvider for TimeDelta {
+    fn binding(&self) -> u32 {
+        DELTA_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<f32>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        self.0 = inputs.delta;
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.0);
+    }
+}
+
+/// Smoothed frames per second as `f32`, like Shadertoy's `iFrameRate`.
+#[derive(Debug, Default)]
+pub struct FrameRate(f32);
+
+impl UniformProvider for FrameRate {
+    fn binding(&self) -> u32 {
+        FRAME_RATE_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<f32>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        self.0 = inputs.frame_rate;
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.0);
+    }
+}
+
+/// Display scale factor as `f32`, e.g. `2.0` on HiDPI screens.
+#[derive(Debug, Default)]
+pub struct ScaleFactor(f32);
+
+impl UniformProvider for ScaleFactor {
+    fn binding(&self) -> u32 {
+        SCALE_FACTOR_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<f32>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        self.0 = inputs.scale_factor;
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.0);
+    }
+}
+
+/// Cursor and click position as `vec4<f32>`, like Shadertoy's `iMouse`.
+#[derive(Debug)]
+pub struct Mouse {
+    /// Fraction of the remaining distance to the cursor covered each frame,
+    /// or `None` to follow the cursor exactly.
+    smoothing: Option<f32>,
+    value: Option<[f32; 4]>,
+}
+
+impl Mouse {
+    pub fn new(smoothing: Option<f32>) -> Self {
+        Self {
+            smoothing,
+            value: None,
+        }
+    }
+}
+
+impl UniformProvider for Mouse {
+    fn binding(&self) -> u32 {
+        MOUSE_BINDING
+    }
+
+    fn size(&self) -> u64 {
+        size_of::<[f32; 4]>() as u64
+    }
+
+    fn update(&mut self, inputs: &Inputs) {
+        let [x, y, click_x, click_y] = inputs.mouse;
+        let (x, y) = match (self.smoothing, self.value) {
+            (Some(factor), Some([last_x, last_y, ..])) => (
+                last_x + (x - last_x) * factor,
+                last_y + (y - last_y) * factor,
+            ),
+            _ => (x, y),
+        };
+        self.value = Some([x, y, click_x, click_y]);
+    }
+
+    fn write(&self, buf: &mut [u8], layout: &Layout) {
+        layout.put(buf, self.binding(), &self.value.unwrap_or_default());
+    }
+}
+
+/// Advances `state` and returns the next value of the SplitMix64 sequence.
+pub fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use chrono::FixedOffset;
+
+    use super::*;
+    use crate::{
+        DEFAULT_SHADER,
+        graph::{CHANNEL_COUNT, Resolutions},
+        reflect,
+    };
+
+    const README: &str = include_str!("../README.md");
+
+    /// The built-in uniforms as the README lists them, one
+    /// ``-   `@group(0) binding(N)`: ... (`TYPE`)`` item each, declared in
+    /// WGSL. Items without a parenthesized type aren't uniforms.
+    fn documented_uniforms() -> String {
+        README
+            .lines()
+            .filter_map(|line| {
+                let rest = line.strip_prefix("-   `@group(0) binding(")?;
+                let (binding, rest) = rest.split_once(")`")?;
+                let (_, ty) = rest.split_once("(`")?;
+                let (ty, _) = ty.split_once("`)")?;
+                Some(format!(
+                    "@group(0) @binding({binding}) var<uniform> u{binding}: {ty};\n"
+                ))
+            })
+            .collect()
+    }
+
+    /// The README's WGSL code block containing `needle`.
+    fn documented_wgsl(needle: &str) -> &'static str {
+        README
+            .split("```wgsl\n")
+            .skip(1)
+            .filter_map(|block| Some(block.split_once("```")?.0))
+            .find(|block| block.contains(needle))
+            .unwrap_or_else(|| panic!("the README has no WGSL block with `{needle}`"))
+    }
+
+    fn provider_sizes() -> Vec<(u32, u32)> {
+        let args = Args::parse_from(["shadertoy"]);
+        defaults(&args)
+            .iter()
+            .map(|provider| (provider.binding(), provider.size() as u32))
+            .collect()
+    }
+
+    #[test]
+    fn providers_match_readme() {
+        let mut expected = provider_sizes();
+        let mut actual: Vec<_> = reflect::uniform_sizes(&documented_uniforms())
+            .into_iter()
+            .map(|((group, binding), size)| {
+                assert_eq!(group, 0, "the built-in uniforms are in group 0");
+                (binding, size)
+            })
+            .collect();
+        expected.sort_unstable();
+        actual.sort_unstable();
+        let rows = |bindings: Vec<(u32, u32)>| {
+            bindings
+                .into_iter()
+                .map(|(binding, size)| (format!("binding {binding}"), 0, size))
+                .collect::<Vec<_>>()
+        };
+        reflect::assert_layout(&rows(expected), &rows(actual));
+    }
+
+    #[test]
+    fn providers_match_default_shader() {
+        let expected = provider_sizes();
+        let declared = reflect::uniform_sizes(DEFAULT_SHADER);
+        assert!(!declared.is_empty());
+        for ((group, binding), size) in declared {
+            assert_eq!(group, 0);
+            assert!(
+                expected.contains(&(binding, size)),
+                "binding {binding} of the default shader has {size} bytes, the providers are {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn date_follows_the_time_zone() {
+        let utc = DateTime::parse_from_rfc3339("2024-02-29T23:30:00.25Z").unwrap();
+        assert_eq!(civil_date(utc), [2024.0, 1.0, 29.0, 84_600.25]);
+        let ahead = utc.with_timezone(&FixedOffset::east_opt(3600).unwrap());
+        assert_eq!(civil_date(ahead), [2024.0, 2.0, 1.0, 1_800.25]);
+        let behind = utc.with_timezone(&FixedOffset::west_opt(24 * 3600 - 1).unwrap());
+        assert_eq!(civil_date(behind), [2024.0, 1.0, 28.0, 84_601.25]);
+    }
+
+    #[test]
+    fn resolutions_match_readme() {
+        let source = documented_wgsl("var<uniform> resolutions");
+        // The resolutions follow the channel textures and their samplers.
+        let binding = 2 * CHANNEL_COUNT as u32;
+        let size = size_of::<Resolutions>() as u32;
+        assert_eq!(reflect::uniform_sizes(source), [((1, binding), size)]);
+
+        let (members, struct_size) = reflect::struct_layout(source, "Resolutions");
+        let own = size_of::<[f32; 4]>() as u32;
+        let expected = [
+            ("own".to_owned(), 0, own),
+            ("channels".to_owned(), own, size - own),
+        ];
+        reflect::assert_layout(&expected, &members);
+        assert_eq!(struct_size, size);
+    }
+}
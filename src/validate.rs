@@ -0,0 +1,67 @@
+use std::{fs, path::Path};
+
+use wgpu::{ErrorFilter, ShaderModuleDescriptor, ShaderSource};
+
+use crate::{cli::Args, device, preprocess, reflect};
+
+/// Compiles every `.wgsl` file in `dir` on one headless device, printing
+/// `PASS` or `FAIL` with the compile error for each. Returns whether all of
+/// them compiled. The prelude, if any, is prepended to each of them.
+#[tracing::instrument(skip(args))]
+pub fn validate_dir(args: &Args, dir: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let prelude_path = args.prelude.as_deref();
+    let prelude = prelude_path.map(fs::read_to_string).transpose()?;
+    // The prelude may live next to the shaders, but isn't one of them.
+    let prelude_path = prelude_path.map(fs::canonicalize).transpose()?;
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file()
+            && fs::canonicalize(&path).ok() != prelude_path
+            && path.extension().is_some_and(|ext| ext == "wgsl")
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("No `.wgsl` files found in `{}`", dir.display()).into());
+    }
+
+    let (_, device, _) = pollster::block_on(device::headless(args))?;
+    let mut failed = 0;
+    for path in &paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                println!("FAIL {}\n  {err}", path.display());
+                failed += 1;
+                continue;
+            }
+        };
+
+        let fragment = preprocess::preprocess(prelude.as_deref(), &source);
+        let error_scope_guard = device.push_error_scope(ErrorFilter::Validation);
+        let _ = device.create_shader_module(ShaderModuleDescriptor {
+            label: path.to_str(),
+            source: ShaderSource::Wgsl(fragment.source.as_str().into()),
+        });
+        match pollster::block_on(error_scope_guard.pop()) {
+            None => println!("PASS {}", path.display()),
+            Some(error) => {
+                let error =
+                    reflect::diagnose(&fragment.source).unwrap_or_else(|| error.to_string());
+                let error = reflect::explain_error(
+                    &fragment.source,
+                    device.features(),
+                    fragment.remap_error(&error),
+                );
+                println!("FAIL {}\n{error}", path.display());
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{} passed, {failed} failed", paths.len() - failed);
+    Ok(failed == 0)
+}
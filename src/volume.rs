@@ -0,0 +1,166 @@
+use std::{fs, path::PathBuf};
+
+use image::{DynamicImage, ImageReader};
+use serde::Deserialize;
+use wgpu::{
+    Device, Extent3d, Origin3d, Queue, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+
+/// 3D texture declared in the manifest, read from a raw dump of its texels
+/// or from a stack of images, one per slice.
+///
+/// ```toml
+/// [volume.smoke]
+/// size = [64, 64, 64]
+/// format = "r8unorm"
+/// data = "smoke.raw"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeManifest {
+    /// Width, height and depth in texels.
+    pub size: [u32; 3],
+    #[serde(default)]
+    pub format: VolumeFormat,
+    /// Raw texels, slice by slice and row by row without padding.
+    pub data: Option<PathBuf>,
+    /// Images of the slices from front to back, converted to `format`.
+    #[serde(default)]
+    pub slices: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VolumeFormat {
+    R8unorm,
+    #[default]
+    Rgba8unorm,
+}
+
+impl VolumeFormat {
+    fn texture_format(self) -> TextureFormat {
+        match self {
+            Self::R8unorm => TextureFormat::R8Unorm,
+            Self::Rgba8unorm => TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    fn bytes_per_texel(self) -> u32 {
+        match self {
+            Self::R8unorm => 1,
+            Self::Rgba8unorm => 4,
+        }
+    }
+
+    /// Texels of `image` in this format.
+    fn convert(self, image: DynamicImage) -> Vec<u8> {
+        match self {
+            Self::R8unorm => image.into_luma8().into_raw(),
+            Self::Rgba8unorm => image.into_rgba8().into_raw(),
+        }
+    }
+}
+
+impl VolumeManifest {
+    /// Reads the texels of the volume, checking they match its size.
+    fn read(&self) -> Result<Vec<u8>, String> {
+        let [width, height, depth] = self.size;
+        let slice_len = (width * height * self.format.bytes_per_texel()) as usize;
+        match (&self.data, self.slices.as_slice()) {
+            (Some(path), []) => {
+                let data = fs::read(path)
+                    .map_err(|err| format!("can't read `{}`: {err}", path.display()))?;
+                let expected = slice_len * depth as usize;
+                if data.len() != expected {
+                    return Err(format!(
+                        "`{}` is {} bytes, expected {expected} for {width}x{height}x{depth} texels of {} bytes",
+                        path.display(),
+                        data.len(),
+                        self.format.bytes_per_texel()
+                    ));
+                }
+                Ok(data)
+            }
+            (None, slices) if !slices.is_empty() => {
+                if slices.len() != depth as usize {
+                    return Err(format!(
+                        "{} slices given for a depth of {depth}",
+                        slices.len()
+                    ));
+                }
+                let mut data = Vec::with_capacity(slice_len * slices.len());
+                for path in slices {
+                    let image = ImageReader::open(path)
+                        .map_err(|err| err.to_string())
+                        .and_then(|reader| reader.decode().map_err(|err| err.to_string()))
+                        .map_err(|err| format!("can't read `{}`: {err}", path.display()))?;
+                    if (image.width(), image.height()) != (width, height) {
+                        return Err(format!(
+                            "slice `{}` is {}x{}, expected {width}x{height}",
+                            path.display(),
+                            image.width(),
+                            image.height()
+                        ));
+                    }
+                    data.extend(self.format.convert(image));
+                }
+                Ok(data)
+            }
+            _ => Err("exactly one of `data` and `slices` must be given".to_owned()),
+        }
+    }
+}
+
+/// Reads the volume `name` and uploads it to a new 3D texture.
+#[tracing::instrument(skip(device, queue, volume))]
+pub fn create_volume(
+    device: &Device,
+    queue: &Queue,
+    name: &str,
+    volume: &VolumeManifest,
+) -> Result<Texture, String> {
+    let error = |err: String| format!("volume `{name}`: {err}");
+    let max = device.limits().max_texture_dimension_3d;
+    if volume.size.iter().any(|&side| side == 0 || side > max) {
+        return Err(error(format!(
+            "size {:?} is outside of the 1 to {max} texels per side the device supports",
+            volume.size
+        )));
+    }
+    let data = volume.read().map_err(error)?;
+
+    let [width, height, depth] = volume.size;
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: depth,
+    };
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some(name),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D3,
+        format: volume.format.texture_format(),
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        &data,
+        TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(width * volume.format.bytes_per_texel()),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+    tracing::debug!("Loaded {width}x{height}x{depth} volume");
+    Ok(texture)
+}
@@ -0,0 +1,399 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Minimum time files matched by a glob must stay unchanged before a change
+/// is reported, so saving several files at once reports one change.
+const GLOB_SETTLE: Duration = Duration::from_millis(100);
+
+/// How long events from the platform watcher are collected after the first
+/// one, so an editor writing a file twice reports one change.
+const EVENT_SETTLE: Duration = Duration::from_millis(50);
+
+/// How often a file watched by the platform watcher is checked anyway, in
+/// case an event was missed, e.g. on network filesystems.
+const EVENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Timing of the file watcher.
+#[derive(Debug, Clone, Copy)]
+pub struct WatchConfig {
+    /// How often the file is checked for changes.
+    pub interval: Duration,
+    /// How long the file has to stay unmodified before it's read, so writes
+    /// still in progress aren't picked up half-done.
+    pub debounce: Duration,
+}
+
+/// Stops the watcher threads spawned with it when dropped. Threads notice
+/// within one check interval, or [`EVENT_TIMEOUT`] for files watched by the
+/// platform watcher.
+#[derive(Debug, Default)]
+pub struct WatchGuard {
+    stopped: Arc<AtomicBool>,
+}
+
+impl WatchGuard {
+    fn stopped(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stopped)
+    }
+}
+
+impl Drop for WatchGuard {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Spawns a thread that sends the contents of the file at `path` every time
+/// it's modified, starting with its current contents, until `guard` is
+/// dropped. Files larger than `max_size` bytes are skipped, see
+/// [`read_limited`].
+///
+/// The thread wakes up on events from the platform watcher, and only falls
+/// back to checking the file every `config.interval` if that can't be set
+/// up.
+#[tracing::instrument(skip(guard))]
+pub fn spawn_watcher_thread(
+    path: PathBuf,
+    config: WatchConfig,
+    max_size: Option<u64>,
+    guard: &WatchGuard,
+) -> Result<mpsc::Receiver<String>, io::Error> {
+    tracing::trace!("Spawning shader watcher thread");
+    let (tx, rx) = mpsc::channel();
+
+    // The current contents are read up front, so they can be received as
+    // soon as this returns. Files that can't be read yet are left to the
+    // thread, which reports why.
+    let mut last = SystemTime::UNIX_EPOCH;
+    if let Ok(modified) = fs::metadata(&path).and_then(|m| m.modified())
+        && let Ok(source) = read_limited(&path, max_size)
+    {
+        tracing::info!("Read {} bytes from `{}`", source.len(), path.display());
+        tx.send(source).expect("the receiver is alive");
+        last = modified;
+    }
+
+    let wakeup = Wakeup::new(&path, config.interval);
+    let stopped = guard.stopped();
+    thread::Builder::new()
+        .name(format!("watcher {}", path.display()))
+        .spawn(move || -> io::Result<()> {
+            tracing::debug!("Shader watcher thread started");
+
+            // A missing file is reported once rather than on every retry,
+            // since it may not have been created yet.
+            let mut missing = false;
+
+            while !stopped.load(Ordering::Relaxed) {
+                // The file is looked up by name every time, since editors that
+                // save by renaming a new file over the old one leave any open
+                // handle pointing at the stale file.
+                let modified = match fs::metadata(&path).and_then(|m| m.modified()) {
+                    Ok(time) => time,
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                        if !missing {
+                            tracing::warn!(
+                                "Shader file `{}` doesn't exist, waiting for it to be created",
+                                path.display()
+                            );
+                            missing = true;
+                        }
+                        wakeup.wait();
+                        continue;
+                    }
+                    Err(err) => {
+                        tracing::error!(
+                            "Failed to open shader file `{}`: {err}. Retrying in 1 second",
+                            path.display()
+                        );
+                        thread::sleep(Duration::from_millis(1000));
+                        continue;
+                    }
+                };
+
+                missing = false;
+                if modified != last {
+                    if !config.debounce.is_zero() {
+                        thread::sleep(config.debounce);
+                        let settled = fs::metadata(&path).and_then(|m| m.modified());
+                        if settled.ok() != Some(modified) {
+                            tracing::trace!("Shader file still changing, waiting");
+                            continue;
+                        }
+                    }
+                    match read_limited(&path, max_size) {
+                        Ok(source) => {
+                            tracing::info!(
+                                "Shader file `{}` modified, read {} bytes",
+                                path.display(),
+                                source.len()
+                            );
+                            if tx.send(source).is_ok() {
+                                tracing::trace!("Shader source sent to main thread");
+                                last = modified;
+                            } else {
+                                tracing::warn!(
+                                    "Failed to send shader source, channel disconnected"
+                                );
+                                return Ok(());
+                            }
+                        }
+                        // Failed reads count as seen too, so the error is
+                        // reported once rather than on every check, until the
+                        // file is modified again.
+                        Err(err) if err.kind() == io::ErrorKind::FileTooLarge => {
+                            tracing::error!(
+                                "Skipping shader file `{}`: {err}. Is this the right file?",
+                                path.display()
+                            );
+                            last = modified;
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                            tracing::error!(
+                                "Skipping shader file `{}`: it isn't valid UTF-8 text",
+                                path.display()
+                            );
+                            last = modified;
+                        }
+                        Err(err) => {
+                            tracing::error!(
+                                "Failed to read shader file `{}`: {err}",
+                                path.display()
+                            );
+                            last = modified;
+                        }
+                    }
+                }
+
+                wakeup.wait();
+            }
+            tracing::debug!("Shader watcher thread stopped");
+            Ok(())
+        })?;
+    Ok(rx)
+}
+
+/// Wakes up a watcher thread when the file it watches may have changed.
+struct Wakeup {
+    /// The platform watcher and the changes it reported, `None` if it
+    /// couldn't be set up.
+    events: Option<(RecommendedWatcher, mpsc::Receiver<()>)>,
+    /// How often the file is checked without a platform watcher.
+    interval: Duration,
+}
+
+impl Wakeup {
+    /// Watches the directory of `path` rather than the file, so the file
+    /// can be created later or replaced by renaming another file over it.
+    fn new(path: &Path, interval: Duration) -> Self {
+        let name = path.file_name().map(ToOwned::to_owned);
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let (tx, rx) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            // Reading the file reports access events, which aren't changes.
+            let Ok(event) = event else { return };
+            if !matches!(event.kind, EventKind::Access(_))
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == name.as_deref())
+            {
+                let _ = tx.send(());
+            }
+        })
+        .and_then(|mut watcher| {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+            Ok(watcher)
+        });
+        match watcher {
+            Ok(watcher) => Self {
+                events: Some((watcher, rx)),
+                interval,
+            },
+            Err(err) => {
+                tracing::warn!(
+                    "Failed to watch `{}` for changes: {err}. Checking it every {interval:?} instead",
+                    dir.display()
+                );
+                Self {
+                    events: None,
+                    interval,
+                }
+            }
+        }
+    }
+
+    /// Blocks until the file may have changed. Events that follow the first
+    /// one in quick succession are taken along with it.
+    fn wait(&self) {
+        let Some((_, events)) = &self.events else {
+            thread::sleep(self.interval);
+            return;
+        };
+        if events.recv_timeout(EVENT_TIMEOUT).is_ok() {
+            while events.recv_timeout(EVENT_SETTLE).is_ok() {}
+        }
+    }
+}
+
+/// Reads the file at `path` once instead of watching it, see `--no-watch`.
+/// The receiver yields the contents if the file could be read, and nothing
+/// after that.
+#[tracing::instrument]
+pub fn read_once(path: &Path, max_size: Option<u64>) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    match read_limited(path, max_size) {
+        Ok(source) => {
+            tracing::info!("Read {} bytes from `{}`", source.len(), path.display());
+            tx.send(source).expect("the receiver is alive");
+        }
+        Err(err) => tracing::error!("Failed to read shader file `{}`: {err}", path.display()),
+    }
+    rx
+}
+
+/// Reads the file at `path` as text, without reading more than `max_size`
+/// bytes of it, so a large file given by mistake fails with
+/// [`io::ErrorKind::FileTooLarge`] instead of filling the memory. Files that
+/// aren't UTF-8 fail with [`io::ErrorKind::InvalidData`].
+fn read_limited(path: &Path, max_size: Option<u64>) -> io::Result<String> {
+    let Some(max_size) = max_size else {
+        return fs::read_to_string(path);
+    };
+    let too_large = || {
+        io::Error::new(
+            io::ErrorKind::FileTooLarge,
+            format!("it's larger than {max_size} bytes, see `--max-shader-size`"),
+        )
+    };
+    if fs::metadata(path)?.len() > max_size {
+        return Err(too_large());
+    }
+    // The file can still grow between the check and the read.
+    let mut source = String::new();
+    fs::File::open(path)?
+        .take(max_size + 1)
+        .read_to_string(&mut source)?;
+    if source.len() as u64 > max_size {
+        return Err(too_large());
+    }
+    Ok(source)
+}
+
+/// Spawns a thread that sends `()` every time a file matching one of
+/// `patterns` is modified, created or removed. Changes to several files in
+/// quick succession are reported once, after they settled. The thread stops
+/// when `guard` is dropped.
+#[tracing::instrument(skip(guard))]
+pub fn spawn_glob_watcher_thread(
+    patterns: Vec<String>,
+    config: WatchConfig,
+    guard: &WatchGuard,
+) -> Result<mpsc::Receiver<()>, io::Error> {
+    let patterns = patterns
+        .iter()
+        .map(|pattern| glob::Pattern::new(pattern).map(|_| pattern.clone()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let (tx, rx) = mpsc::channel();
+
+    let stopped = guard.stopped();
+    thread::Builder::new()
+        .name("glob watcher".to_owned())
+        .spawn(move || {
+            let mut last = snapshot(&patterns);
+            tracing::debug!("Watching {} files", last.len());
+            loop {
+                thread::sleep(config.interval);
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                let mut current = snapshot(&patterns);
+                if current == last {
+                    continue;
+                }
+                loop {
+                    thread::sleep(config.debounce.max(GLOB_SETTLE));
+                    if stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let settled = snapshot(&patterns);
+                    if settled == current {
+                        break;
+                    }
+                    current = settled;
+                }
+                tracing::info!("Watched files changed");
+                last = current;
+                if tx.send(()).is_err() {
+                    return;
+                }
+            }
+            tracing::debug!("Glob watcher thread stopped");
+        })?;
+    Ok(rx)
+}
+
+/// Modification times of the files matching `patterns`.
+fn snapshot(patterns: &[String]) -> BTreeMap<PathBuf, SystemTime> {
+    patterns
+        .iter()
+        .filter_map(|pattern| glob::glob(pattern).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter_map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            Some((path, modified))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_are_reported_without_polling() {
+        let dir = std::env::temp_dir().join(format!("shadertoy-watcher-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("shader.wgsl");
+        fs::write(&path, "first").unwrap();
+
+        // With an interval this long, only the platform watcher can report
+        // the change in time.
+        let config = WatchConfig {
+            interval: Duration::from_secs(3600),
+            debounce: Duration::ZERO,
+        };
+        let guard = WatchGuard::default();
+        let rx = spawn_watcher_thread(path.clone(), config, None, &guard).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "first");
+
+        // Give the thread time to start waiting, then write twice like an
+        // editor might.
+        thread::sleep(Duration::from_millis(200));
+        fs::write(&path, "second").unwrap();
+        fs::write(&path, "third").unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "third");
+        assert!(rx.recv_timeout(EVENT_TIMEOUT + EVENT_SETTLE).is_err());
+
+        drop(guard);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}